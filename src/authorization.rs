@@ -0,0 +1,76 @@
+/*
+ * Copyright 2019 Walmart Inc.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * -----------------------------------------------------------------------------
+ */
+
+//! Bearer tokens for splinterd deployments that sit behind an authenticating proxy.
+//!
+//! The token is sent with the REST requests made to splinterd only. The admin and scabbard
+//! websockets are opened without it, so the proxy must let them through unauthenticated.
+
+use std::error::Error;
+use std::fmt;
+use std::path::PathBuf;
+
+/// Supplies the bearer token attached to every request made to splinterd.
+#[derive(Clone)]
+pub enum TokenProvider {
+    /// A token that never changes
+    Static(String),
+    /// A file holding the token; it is re-read on every request so rotated tokens are picked up
+    File(PathBuf),
+}
+
+impl TokenProvider {
+    pub fn token(&self) -> Result<String, TokenError> {
+        match self {
+            TokenProvider::Static(token) => Ok(token.clone()),
+            TokenProvider::File(path) => std::fs::read_to_string(path)
+                .map(|token| token.trim().to_string())
+                .map_err(|err| {
+                    TokenError(format!(
+                        "Unable to read token file {}: {}",
+                        path.display(),
+                        err
+                    ))
+                }),
+        }
+    }
+
+    /// Returns the value of the `Authorization` header for the current token.
+    pub fn authorization_header(&self) -> Result<String, TokenError> {
+        Ok(format!("Bearer {}", self.token()?))
+    }
+}
+
+impl fmt::Debug for TokenProvider {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TokenProvider::Static(_) => write!(f, "TokenProvider::Static"),
+            TokenProvider::File(path) => write!(f, "TokenProvider::File({})", path.display()),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct TokenError(pub String);
+
+impl Error for TokenError {}
+
+impl fmt::Display for TokenError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Unable to get authorization token: {}", self.0)
+    }
+}
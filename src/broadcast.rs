@@ -0,0 +1,127 @@
+/*
+ * Copyright 2019 Walmart Inc.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * -----------------------------------------------------------------------------
+ */
+
+//! Fan-out of admin event state changes to clients of the REST API.
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+use futures::sync::mpsc::{unbounded, UnboundedReceiver, UnboundedSender};
+use serde_json::Value;
+
+/// A state change with the sequence number it was broadcast under
+#[derive(Debug, Clone)]
+pub struct StateChange {
+    id: u64,
+    event: Value,
+}
+
+impl StateChange {
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Returns the change as a JSON object including its id.
+    pub fn to_json(&self) -> Value {
+        let mut value = self.event.clone();
+        if let Value::Object(fields) = &mut value {
+            fields.insert("id".to_string(), json!(self.id));
+        }
+        value
+    }
+}
+
+struct Inner {
+    next_id: u64,
+    subscribers: Vec<UnboundedSender<StateChange>>,
+    /// the most recent changes, oldest first
+    history: VecDeque<StateChange>,
+    history_size: usize,
+}
+
+/// Delivers every state change to each current subscriber, and keeps the most recent ones so
+/// that a subscriber can resume after a disconnect.
+///
+/// Clones share the same subscribers.
+#[derive(Clone)]
+pub struct Broadcaster {
+    inner: Arc<Mutex<Inner>>,
+}
+
+impl Broadcaster {
+    pub fn new(history_size: usize) -> Self {
+        Broadcaster {
+            inner: Arc::new(Mutex::new(Inner {
+                next_id: 0,
+                subscribers: Vec::new(),
+                history: VecDeque::with_capacity(history_size),
+                history_size,
+            })),
+        }
+    }
+
+    /// Returns a stream of the state changes broadcast from now on.
+    pub fn subscribe(&self) -> UnboundedReceiver<StateChange> {
+        self.subscribe_after(None)
+    }
+
+    /// Returns a stream of the state changes broadcast from now on, preceded by the retained
+    /// changes that came after `last_id`.
+    ///
+    /// Changes older than the retained history are lost to the subscriber.
+    pub fn subscribe_after(&self, last_id: Option<u64>) -> UnboundedReceiver<StateChange> {
+        let (sender, receiver) = unbounded();
+        match self.inner.lock() {
+            Ok(mut inner) => {
+                if let Some(last_id) = last_id {
+                    for change in inner.history.iter().filter(|change| change.id > last_id) {
+                        // the receiver is still held here, so this cannot fail
+                        let _ = sender.unbounded_send(change.clone());
+                    }
+                }
+                inner.subscribers.push(sender);
+            }
+            Err(_) => error!("Broadcaster lock was poisoned, subscriber not added"),
+        }
+        receiver
+    }
+
+    /// Sends a state change to every subscriber, forgetting those that have gone away.
+    pub fn broadcast(&self, event: Value) {
+        let mut inner = match self.inner.lock() {
+            Ok(inner) => inner,
+            Err(_) => {
+                error!("Broadcaster lock was poisoned, state change not sent");
+                return;
+            }
+        };
+        let change = StateChange {
+            id: inner.next_id,
+            event,
+        };
+        inner.next_id += 1;
+        inner
+            .subscribers
+            .retain(|subscriber| subscriber.unbounded_send(change.clone()).is_ok());
+        if inner.history_size > 0 {
+            if inner.history.len() == inner.history_size {
+                inner.history.pop_front();
+            }
+            inner.history.push_back(change);
+        }
+    }
+}
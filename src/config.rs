@@ -21,12 +21,19 @@ use futures::{
     future::{self, Either},
     Future, Stream,
 };
-use hyper::{Client as HyperClient, StatusCode, Uri};
+use hyper::header::AUTHORIZATION;
+use hyper::{Body, Client as HyperClient, Request, StatusCode, Uri};
 use serde_json::Value;
+use std::collections::BTreeMap;
+use std::io::Read;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
 use splinter::node_registry::Node;
 use tokio::runtime::Runtime;
 
+use crate::authorization::TokenProvider;
 use crate::error::{ConfigurationError, GetNodeError};
+use crate::metrics::Metrics;
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct DeploymentConfig {
@@ -36,6 +43,813 @@ pub struct DeploymentConfig {
     tp_path: String,
     kafka_topic: String,
     kafka_url: String,
+    #[serde(default = "default_circuit_management_types")]
+    circuit_management_types: Vec<String>,
+    #[serde(default = "default_splinterd_reconnect_limit")]
+    splinterd_reconnect_limit: u64,
+    #[serde(default = "default_submit_retry_limit")]
+    submit_retry_limit: u32,
+    #[serde(default = "default_submit_retry_backoff_millis")]
+    submit_retry_backoff_millis: u64,
+    #[serde(default = "default_event_queue_depth")]
+    event_queue_depth: usize,
+    #[serde(default = "default_event_queue_workers")]
+    event_queue_workers: usize,
+    #[serde(default)]
+    event_queue_full_policy: QueueFullPolicy,
+    #[serde(default = "default_event_batch_size")]
+    event_batch_size: usize,
+    #[serde(default)]
+    export_mode: ExportMode,
+    #[serde(default = "default_aggregation_period_secs")]
+    aggregation_period_secs: u64,
+    #[serde(default = "default_aggregation_min_group_size")]
+    aggregation_min_group_size: u64,
+    #[serde(default = "default_shutdown_timeout_secs")]
+    shutdown_timeout_secs: u64,
+    #[serde(default = "default_event_dedup_window_secs")]
+    event_dedup_window_secs: u64,
+    #[serde(default)]
+    event_filters: Vec<FilterRule>,
+    #[serde(default)]
+    event_filter_default: FilterAction,
+    #[serde(default = "default_event_history_size")]
+    event_history_size: usize,
+    #[serde(default)]
+    api_keys: Vec<ApiKeyConfig>,
+    #[serde(default)]
+    cors: CorsConfig,
+    #[serde(default = "default_submit_rate_limit_per_minute")]
+    submit_rate_limit_per_minute: u64,
+    #[serde(default = "default_submit_rate_limit_burst")]
+    submit_rate_limit_burst: u64,
+    #[serde(default = "default_idempotency_key_ttl_secs")]
+    idempotency_key_ttl_secs: u64,
+    #[serde(default = "default_node_cache_ttl_secs")]
+    node_cache_ttl_secs: u64,
+    #[serde(default)]
+    compression: CompressionConfig,
+    #[serde(default = "default_failed_event_history_size")]
+    failed_event_history_size: usize,
+    #[serde(default)]
+    rest_api_tls: Option<TlsConfig>,
+    #[serde(default)]
+    rest_api_unix_socket: Option<String>,
+    #[serde(default = "default_rest_api_tcp_enabled")]
+    rest_api_tcp_enabled: bool,
+    #[serde(default)]
+    export_sinks: Vec<ExportSinkConfig>,
+    #[serde(default = "default_webhook_delivery_history_size")]
+    webhook_delivery_history_size: usize,
+    #[serde(default)]
+    state_export_prefixes: Vec<String>,
+    #[serde(default = "default_export_run_history_size")]
+    export_run_history_size: usize,
+    #[serde(default = "default_batch_status_cache_size")]
+    batch_status_cache_size: usize,
+    #[serde(default)]
+    signing_key_file: Option<String>,
+    #[serde(default)]
+    signing_key_env: Option<String>,
+    #[serde(default)]
+    server_side_signing: bool,
+    #[serde(default)]
+    remote_signer: Option<RemoteSignerConfig>,
+    #[serde(default)]
+    registered_keys: Vec<RegisteredKeyConfig>,
+}
+
+/// What is exported
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportMode {
+    /// Every event, as it is received
+    Records,
+    /// Only periodic counts of events per type
+    Aggregate,
+}
+
+impl Default for ExportMode {
+    fn default() -> Self {
+        ExportMode::Records
+    }
+}
+
+/// What to do with a message when the publisher queue is full
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum QueueFullPolicy {
+    /// Wait for a free slot, slowing down the websocket reader
+    Block,
+    /// Discard the message
+    Drop,
+}
+
+impl Default for QueueFullPolicy {
+    fn default() -> Self {
+        QueueFullPolicy::Block
+    }
+}
+
+/// What to do with an admin event matched by a filter rule
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum FilterAction {
+    Keep,
+    Drop,
+    /// Keeps the event, exporting the messages about its circuit only to the rule's sink
+    Route,
+}
+
+impl Default for FilterAction {
+    fn default() -> Self {
+        FilterAction::Keep
+    }
+}
+
+/// Selects admin events by the circuit they concern.
+///
+/// Every non-empty criterion must match; an empty one matches any circuit.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct FilterRule {
+    action: FilterAction,
+    #[serde(default)]
+    circuit_management_types: Vec<String>,
+    /// public keys of the circuit requester, as hex
+    #[serde(default)]
+    requesters: Vec<String>,
+    /// matches if any member of the circuit is listed
+    #[serde(default)]
+    member_node_ids: Vec<String>,
+    /// name of the export sink the route action sends the circuit's messages to
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    sink: Option<String>,
+}
+
+impl FilterRule {
+    pub fn action(&self) -> FilterAction {
+        self.action
+    }
+
+    pub fn sink(&self) -> Option<&str> {
+        self.sink.as_ref().map(String::as_str)
+    }
+
+    pub fn requesters(&self) -> &[String] {
+        &self.requesters
+    }
+
+    /// Returns true if the rule selects a circuit with the given properties.
+    pub fn matches(
+        &self,
+        circuit_management_type: &str,
+        requester: &str,
+        member_node_ids: &[&str],
+    ) -> bool {
+        (self.circuit_management_types.is_empty()
+            || self
+                .circuit_management_types
+                .iter()
+                .any(|management_type| management_type == circuit_management_type))
+            && (self.requesters.is_empty()
+                || self
+                    .requesters
+                    .iter()
+                    .any(|key| key.eq_ignore_ascii_case(requester)))
+            && (self.member_node_ids.is_empty()
+                || self
+                    .member_node_ids
+                    .iter()
+                    .any(|node_id| member_node_ids.contains(&node_id.as_str())))
+    }
+}
+
+/// Checks that export sink names are unique and that every route rule names one of them, and
+/// that only route rules name a sink.
+pub fn check_filter_rules(
+    rules: &[FilterRule],
+    export_sinks: &[ExportSinkConfig],
+) -> Result<(), String> {
+    let mut names = Vec::new();
+    for name in export_sinks.iter().filter_map(ExportSinkConfig::name) {
+        if names.contains(&name) {
+            return Err(format!("a unique name for the export sink {}", name));
+        }
+        names.push(name);
+    }
+    rules
+        .iter()
+        .try_for_each(|rule| match (rule.action, rule.sink()) {
+            (FilterAction::Route, None) => Err("the sink of a route event filter".to_string()),
+            (FilterAction::Route, Some(sink)) if !names.contains(&sink) => Err(format!(
+                "the export sink {} routed to by an event filter",
+                sink
+            )),
+            (FilterAction::Route, Some(_)) | (_, None) => Ok(()),
+            (_, Some(sink)) => Err(format!(
+                "a route action for the event filter naming the sink {}",
+                sink
+            )),
+        })
+}
+
+/// What a client of the REST API is allowed to do, each role including those below it
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, PartialOrd)]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    /// Query filters and subscribe to state changes
+    ReadOnly,
+    /// Also submit votes
+    Member,
+    /// Also propose circuits and manage filters and API keys
+    Admin,
+}
+
+/// A key given without a role may only read, like a key created at runtime.
+impl Default for Role {
+    fn default() -> Self {
+        Role::ReadOnly
+    }
+}
+
+/// prefix of the environment variables overriding the CORS configuration
+const CORS_ENV_PREFIX: &str = "EVENT_LISTENER_CORS_";
+
+/// Which browser origins may call the REST API
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CorsConfig {
+    /// an empty list disables CORS, "*" allows any origin
+    #[serde(default)]
+    allowed_origins: Vec<String>,
+    #[serde(default = "default_cors_allowed_methods")]
+    allowed_methods: Vec<String>,
+    #[serde(default = "default_cors_allowed_headers")]
+    allowed_headers: Vec<String>,
+    #[serde(default = "default_cors_max_age_secs")]
+    max_age_secs: u64,
+}
+
+impl Default for CorsConfig {
+    fn default() -> Self {
+        CorsConfig {
+            allowed_origins: Vec::new(),
+            allowed_methods: default_cors_allowed_methods(),
+            allowed_headers: default_cors_allowed_headers(),
+            max_age_secs: default_cors_max_age_secs(),
+        }
+    }
+}
+
+impl CorsConfig {
+    /// Replaces each setting that has an environment variable set, so the policy can be
+    /// adjusted per deployment without editing the configuration file.
+    fn with_env_overrides(mut self) -> Result<Self, ConfigurationError> {
+        let list = |name: &str| {
+            std::env::var(format!("{}{}", CORS_ENV_PREFIX, name))
+                .ok()
+                .map(|value| {
+                    value
+                        .split(',')
+                        .map(str::trim)
+                        .filter(|item| !item.is_empty())
+                        .map(ToOwned::to_owned)
+                        .collect::<Vec<_>>()
+                })
+        };
+        if let Some(origins) = list("ALLOWED_ORIGINS") {
+            self.allowed_origins = origins;
+        }
+        if let Some(methods) = list("ALLOWED_METHODS") {
+            self.allowed_methods = methods;
+        }
+        if let Some(headers) = list("ALLOWED_HEADERS") {
+            self.allowed_headers = headers;
+        }
+        if let Ok(max_age) = std::env::var(format!("{}MAX_AGE_SECS", CORS_ENV_PREFIX)) {
+            self.max_age_secs = max_age.parse().map_err(|_| {
+                ConfigurationError::MissingValue(format!("{}MAX_AGE_SECS", CORS_ENV_PREFIX))
+            })?;
+        }
+        Ok(self)
+    }
+
+    pub fn allowed_origins(&self) -> &[String] {
+        &self.allowed_origins
+    }
+
+    pub fn allowed_methods(&self) -> &[String] {
+        &self.allowed_methods
+    }
+
+    pub fn allowed_headers(&self) -> &[String] {
+        &self.allowed_headers
+    }
+
+    pub fn max_age_secs(&self) -> u64 {
+        self.max_age_secs
+    }
+}
+
+/// The certificate the REST API is served over HTTPS with
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TlsConfig {
+    /// PEM certificate chain, starting with the server's certificate
+    cert_path: String,
+    /// PEM private key of the certificate
+    key_path: String,
+    /// PEM certificates of the CAs client certificates must be signed by; when set, clients
+    /// without a valid certificate are refused
+    #[serde(default)]
+    client_ca_path: Option<String>,
+    /// address of a plain HTTP listener redirecting every request to HTTPS
+    #[serde(default)]
+    redirect_bind: Option<String>,
+}
+
+impl TlsConfig {
+    pub fn cert_path(&self) -> &str {
+        &self.cert_path
+    }
+
+    pub fn key_path(&self) -> &str {
+        &self.key_path
+    }
+
+    pub fn client_ca_path(&self) -> Option<&str> {
+        self.client_ca_path.as_ref().map(String::as_str)
+    }
+
+    pub fn redirect_bind(&self) -> Option<&str> {
+        self.redirect_bind.as_ref().map(String::as_str)
+    }
+}
+
+/// A signing service holding the signing key, such as one backed by an HSM or KMS
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RemoteSignerConfig {
+    /// URL messages are posted to for signing
+    url: String,
+    /// public key of the service's signing key, as hex
+    public_key: String,
+    /// PEM certificates of the CAs the service's certificate must be signed by, in addition to
+    /// the system's
+    #[serde(default)]
+    ca_path: Option<String>,
+    /// PEM certificate presented to the service
+    #[serde(default)]
+    client_cert_path: Option<String>,
+    /// PEM private key of the client certificate
+    #[serde(default)]
+    client_key_path: Option<String>,
+}
+
+impl RemoteSignerConfig {
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    pub fn public_key(&self) -> &str {
+        &self.public_key
+    }
+
+    pub fn ca_path(&self) -> Option<&str> {
+        self.ca_path.as_ref().map(String::as_str)
+    }
+
+    pub fn client_cert_path(&self) -> Option<&str> {
+        self.client_cert_path.as_ref().map(String::as_str)
+    }
+
+    pub fn client_key_path(&self) -> Option<&str> {
+        self.client_key_path.as_ref().map(String::as_str)
+    }
+}
+
+/// Which REST API responses are compressed
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CompressionConfig {
+    #[serde(default = "default_compression_min_size_bytes")]
+    min_size_bytes: u64,
+    /// an empty list disables compression
+    #[serde(default = "default_compression_content_types")]
+    content_types: Vec<String>,
+}
+
+impl Default for CompressionConfig {
+    fn default() -> Self {
+        CompressionConfig {
+            min_size_bytes: default_compression_min_size_bytes(),
+            content_types: default_compression_content_types(),
+        }
+    }
+}
+
+impl CompressionConfig {
+    pub fn min_size_bytes(&self) -> u64 {
+        self.min_size_bytes
+    }
+
+    pub fn content_types(&self) -> &[String] {
+        &self.content_types
+    }
+}
+
+/// An export sink and the messages written to it
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ExportSinkConfig {
+    /// names the sink in the route action of event filter rules
+    #[serde(default)]
+    name: Option<String>,
+    #[serde(flatten)]
+    sink: SinkConfig,
+    #[serde(flatten)]
+    filter: MessageFilter,
+}
+
+impl ExportSinkConfig {
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_ref().map(String::as_str)
+    }
+
+    pub fn sink(&self) -> &SinkConfig {
+        &self.sink
+    }
+
+    pub fn filter(&self) -> &MessageFilter {
+        &self.filter
+    }
+}
+
+/// Selects exported messages by their type and circuit.
+///
+/// Every non-empty criterion must match; an empty one matches any message.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct MessageFilter {
+    /// types of the messages selected, such as "proposal_vote"
+    #[serde(default)]
+    event_types: Vec<String>,
+    #[serde(default)]
+    circuit_ids: Vec<String>,
+}
+
+impl MessageFilter {
+    pub fn event_types(&self) -> &[String] {
+        &self.event_types
+    }
+
+    /// Returns true if the filter selects every message.
+    pub fn is_empty(&self) -> bool {
+        self.event_types.is_empty() && self.circuit_ids.is_empty()
+    }
+
+    /// Returns true if the filter selects a message of the given type about the given circuit.
+    pub fn matches(&self, event_type: &str, circuit_id: &str) -> bool {
+        (self.event_types.is_empty()
+            || self
+                .event_types
+                .iter()
+                .any(|selected| selected == event_type))
+            && (self.circuit_ids.is_empty() || self.circuit_ids.iter().any(|id| id == circuit_id))
+    }
+}
+
+/// A destination exported messages are written to, selected by its `type`
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum SinkConfig {
+    /// A Kafka topic, possibly on other brokers than kafka_url
+    Kafka {
+        brokers: Vec<String>,
+        /// "{type}" is replaced by the type of each message, such as "proposal_vote"
+        topic: String,
+        #[serde(default)]
+        required_acks: KafkaAcks,
+        /// encode messages with Avro, registering their schemas in this schema registry,
+        /// instead of writing protobuf
+        #[serde(default)]
+        schema_registry_url: Option<String>,
+    },
+    /// A NATS subject
+    Nats {
+        /// tried in turn until one accepts the connection
+        servers: Vec<String>,
+        /// "{type}" is replaced by the type of each message, such as "proposal_vote"
+        subject: String,
+        #[serde(default)]
+        auth_token: Option<String>,
+        /// wait for the server to confirm it has processed each batch
+        #[serde(default = "default_nats_confirm")]
+        confirm: bool,
+    },
+    /// JSON records bulk-indexed into Elasticsearch or OpenSearch
+    Elasticsearch {
+        url: String,
+        /// "{date}" is replaced by the current period of the rollover
+        #[serde(default = "default_elasticsearch_index")]
+        index: String,
+        #[serde(default)]
+        rollover: IndexRollover,
+        #[serde(default)]
+        username: Option<String>,
+        #[serde(default)]
+        password: Option<String>,
+        #[serde(default)]
+        fields: BTreeMap<String, String>,
+    },
+    /// JSON Lines appended to a file, or written to standard output if the path is "-"
+    Ndjson {
+        path: String,
+        #[serde(default)]
+        fields: BTreeMap<String, String>,
+        #[serde(default)]
+        compression: FileCompression,
+        /// gzip level from 0 to 9, or zstd level from 1 to 21; the algorithm's default if omitted
+        #[serde(default)]
+        compression_level: Option<u32>,
+    },
+    /// JSON records posted to a URL, signed with an HMAC-SHA256 of a timestamp and the body keyed
+    /// with secret
+    Webhook {
+        url: String,
+        secret: String,
+        #[serde(default = "default_webhook_retry_limit")]
+        retry_limit: u32,
+        #[serde(default = "default_webhook_retry_backoff_millis")]
+        retry_backoff_millis: u64,
+        /// number of messages that may wait to be delivered
+        #[serde(default = "default_webhook_queue_depth")]
+        queue_depth: usize,
+        #[serde(default)]
+        fields: BTreeMap<String, String>,
+    },
+}
+
+/// How often an Elasticsearch sink starts writing to a new index
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum IndexRollover {
+    /// Always write to the same index
+    None,
+    Daily,
+    Monthly,
+}
+
+impl Default for IndexRollover {
+    fn default() -> Self {
+        IndexRollover::Daily
+    }
+}
+
+/// How a file sink compresses what it writes
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum FileCompression {
+    None,
+    Gzip,
+    Zstd,
+}
+
+impl Default for FileCompression {
+    fn default() -> Self {
+        FileCompression::None
+    }
+}
+
+/// Which brokers must acknowledge a Kafka write before it succeeds
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum KafkaAcks {
+    /// None, the write is not confirmed
+    None,
+    /// The partition leader
+    One,
+    /// Every in-sync replica
+    All,
+}
+
+impl Default for KafkaAcks {
+    fn default() -> Self {
+        KafkaAcks::One
+    }
+}
+
+/// A key accepted from programmatic clients of the REST API
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ApiKeyConfig {
+    name: String,
+    /// hex SHA-256 hash of the key, so the configuration does not hold the key itself
+    key_sha256: String,
+    #[serde(default)]
+    role: Role,
+}
+
+impl ApiKeyConfig {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn key_sha256(&self) -> &str {
+        &self.key_sha256
+    }
+
+    pub fn role(&self) -> Role {
+        self.role
+    }
+}
+
+/// The public key of a user, with who it belongs to
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RegisteredKeyConfig {
+    /// secp256k1 public key, as hex
+    public_key: String,
+    /// name shown for the key's owner
+    name: String,
+    #[serde(default)]
+    node_id: Option<String>,
+    #[serde(default)]
+    organization: Option<String>,
+}
+
+impl RegisteredKeyConfig {
+    pub fn public_key(&self) -> &str {
+        &self.public_key
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn node_id(&self) -> Option<&str> {
+        self.node_id.as_ref().map(String::as_str)
+    }
+
+    pub fn organization(&self) -> Option<&str> {
+        self.organization.as_ref().map(String::as_str)
+    }
+}
+
+/// default circuit management types to register for when none are configured
+fn default_circuit_management_types() -> Vec<String> {
+    vec!["consortium".to_string()]
+}
+
+/// default number of messages that may wait to be written to Kafka, per worker
+fn default_event_queue_depth() -> usize {
+    1024
+}
+
+/// default number of threads writing to Kafka
+fn default_event_queue_workers() -> usize {
+    1
+}
+
+/// default maximum number of queued messages written to Kafka in one request
+fn default_event_batch_size() -> usize {
+    100
+}
+
+/// default length in seconds of the period summarized in aggregate export mode
+fn default_aggregation_period_secs() -> u64 {
+    3600
+}
+
+/// default number of events below which a group is left out of an activity summary
+fn default_aggregation_min_group_size() -> u64 {
+    5
+}
+
+/// default number of failed reconnection attempts before failing over to the next splinterd
+fn default_splinterd_reconnect_limit() -> u64 {
+    10
+}
+
+/// default number of seconds to wait for connections to close and queued messages to be
+/// written when shutting down
+fn default_shutdown_timeout_secs() -> u64 {
+    30
+}
+
+/// default number of times a batch submission is retried after a retriable failure
+fn default_submit_retry_limit() -> u32 {
+    5
+}
+
+/// default delay in milliseconds before the first retry, doubled on each attempt
+fn default_submit_retry_backoff_millis() -> u64 {
+    500
+}
+
+/// default number of seconds an event is remembered to discard redeliveries
+fn default_event_dedup_window_secs() -> u64 {
+    600
+}
+
+/// default number of state changes kept for clients resuming an event stream
+fn default_event_history_size() -> usize {
+    1000
+}
+
+/// default methods browsers may use across origins
+fn default_cors_allowed_methods() -> Vec<String> {
+    vec!["GET".into(), "POST".into(), "PUT".into(), "DELETE".into()]
+}
+
+/// default request headers browsers may send across origins
+fn default_cors_allowed_headers() -> Vec<String> {
+    vec![
+        "Content-Type".into(),
+        "X-Api-Key".into(),
+        "Idempotency-Key".into(),
+    ]
+}
+
+/// default number of seconds browsers may cache a preflight response
+fn default_cors_max_age_secs() -> u64 {
+    3600
+}
+
+/// default size in bytes below which responses are not compressed
+fn default_compression_min_size_bytes() -> u64 {
+    1024
+}
+
+/// default content types of the responses that are compressed
+fn default_compression_content_types() -> Vec<String> {
+    vec![
+        "application/json".into(),
+        "text/csv".into(),
+        "text/plain".into(),
+    ]
+}
+
+/// default number of write requests a client may make per minute
+fn default_submit_rate_limit_per_minute() -> u64 {
+    60
+}
+
+/// default number of write requests a client may make at once before being rate limited
+fn default_submit_rate_limit_burst() -> u64 {
+    10
+}
+
+/// default number of seconds a /submit response is kept for idempotent retries
+fn default_idempotency_key_ttl_secs() -> u64 {
+    86400
+}
+
+/// default number of seconds splinterd's node registry is cached for GET /nodes
+fn default_node_cache_ttl_secs() -> u64 {
+    60
+}
+
+/// default number of failed admin events kept for reprocessing
+fn default_failed_event_history_size() -> usize {
+    1000
+}
+
+/// default is to listen on the --bind address
+fn default_rest_api_tcp_enabled() -> bool {
+    true
+}
+
+/// default name of the indices an Elasticsearch sink writes to
+fn default_elasticsearch_index() -> String {
+    "circuit-events-{date}".to_string()
+}
+
+/// default is to wait for the NATS server to confirm each batch
+fn default_nats_confirm() -> bool {
+    true
+}
+
+/// default number of times a failed webhook delivery is retried
+fn default_webhook_retry_limit() -> u32 {
+    3
+}
+
+/// default delay in milliseconds before retrying a webhook delivery, doubled on each attempt
+fn default_webhook_retry_backoff_millis() -> u64 {
+    1000
+}
+
+/// default number of messages a webhook sink queues for delivery
+fn default_webhook_queue_depth() -> usize {
+    1024
+}
+
+/// default number of webhook deliveries kept for GET /admin/webhook-deliveries
+fn default_webhook_delivery_history_size() -> usize {
+    1000
+}
+
+/// default number of recent batches kept per export sink
+fn default_export_run_history_size() -> usize {
+    100
+}
+
+/// default number of final batch statuses remembered
+fn default_batch_status_cache_size() -> usize {
+    10000
 }
 
 impl DeploymentConfig {
@@ -48,11 +862,39 @@ impl DeploymentConfig {
             Ok(file) => file,
             Err(err) => return Err(ConfigurationError::MissingValue(format!("Invalid deployment configuration {}", err.to_string()))),
         };
-        let resultant: Result<DeploymentConfig, serde_yaml::Error> = serde_yaml::from_reader(f);
+        DeploymentConfig::parse(f)
+    }
+
+    /// Parses and checks a deployment configuration given as YAML, or JSON.
+    pub fn parse<R: Read>(reader: R) -> Result<Self, ConfigurationError> {
+        let resultant: Result<DeploymentConfig, serde_yaml::Error> =
+            serde_yaml::from_reader(reader);
         let parsed = match resultant {
             Ok(parsed) => parsed,
             Err(err) => return Err(ConfigurationError::MissingValue(format!("Invalid deployment configuration {}", err.to_string()))),
         };
+        if parsed.circuit_management_types.is_empty() {
+            return Err(ConfigurationError::MissingValue("circuit_management_types".to_string()));
+        }
+        if parsed.event_queue_workers == 0 {
+            return Err(ConfigurationError::MissingValue("event_queue_workers".to_string()));
+        }
+        if parsed.event_batch_size == 0 {
+            return Err(ConfigurationError::MissingValue("event_batch_size".to_string()));
+        }
+        if parsed.aggregation_period_secs == 0 {
+            return Err(ConfigurationError::MissingValue("aggregation_period_secs".to_string()));
+        }
+        if !parsed.rest_api_tcp_enabled && parsed.rest_api_unix_socket.is_none() {
+            return Err(ConfigurationError::MissingValue("rest_api_unix_socket".to_string()));
+        }
+        if parsed.event_filter_default == FilterAction::Route {
+            return Err(ConfigurationError::MissingValue(
+                "event_filter_default of keep or drop".to_string(),
+            ));
+        }
+        check_filter_rules(&parsed.event_filters, &parsed.export_sinks)
+            .map_err(ConfigurationError::MissingValue)?;
         Ok(DeploymentConfig {
             tp_name: parsed.tp_name,
             tp_version: parsed.tp_version,
@@ -60,6 +902,43 @@ impl DeploymentConfig {
             tp_path: parsed.tp_path,
             kafka_topic: parsed.kafka_topic,
             kafka_url: parsed.kafka_url,
+            circuit_management_types: parsed.circuit_management_types,
+            splinterd_reconnect_limit: parsed.splinterd_reconnect_limit,
+            submit_retry_limit: parsed.submit_retry_limit,
+            submit_retry_backoff_millis: parsed.submit_retry_backoff_millis,
+            event_queue_depth: parsed.event_queue_depth,
+            event_queue_workers: parsed.event_queue_workers,
+            event_queue_full_policy: parsed.event_queue_full_policy,
+            event_batch_size: parsed.event_batch_size,
+            export_mode: parsed.export_mode,
+            aggregation_period_secs: parsed.aggregation_period_secs,
+            aggregation_min_group_size: parsed.aggregation_min_group_size,
+            shutdown_timeout_secs: parsed.shutdown_timeout_secs,
+            event_dedup_window_secs: parsed.event_dedup_window_secs,
+            event_filters: parsed.event_filters,
+            event_filter_default: parsed.event_filter_default,
+            event_history_size: parsed.event_history_size,
+            api_keys: parsed.api_keys,
+            cors: parsed.cors.with_env_overrides()?,
+            submit_rate_limit_per_minute: parsed.submit_rate_limit_per_minute,
+            submit_rate_limit_burst: parsed.submit_rate_limit_burst,
+            idempotency_key_ttl_secs: parsed.idempotency_key_ttl_secs,
+            node_cache_ttl_secs: parsed.node_cache_ttl_secs,
+            compression: parsed.compression,
+            failed_event_history_size: parsed.failed_event_history_size,
+            rest_api_tls: parsed.rest_api_tls,
+            rest_api_unix_socket: parsed.rest_api_unix_socket,
+            rest_api_tcp_enabled: parsed.rest_api_tcp_enabled,
+            export_sinks: parsed.export_sinks,
+            webhook_delivery_history_size: parsed.webhook_delivery_history_size,
+            state_export_prefixes: parsed.state_export_prefixes,
+            export_run_history_size: parsed.export_run_history_size,
+            batch_status_cache_size: parsed.batch_status_cache_size,
+            signing_key_file: parsed.signing_key_file,
+            signing_key_env: parsed.signing_key_env,
+            server_side_signing: parsed.server_side_signing,
+            remote_signer: parsed.remote_signer,
+            registered_keys: parsed.registered_keys,
         })
     }
 
@@ -86,33 +965,250 @@ impl DeploymentConfig {
     pub fn kafka_url(&self) -> &str {
         &self.kafka_url
     }
+
+    pub fn circuit_management_types(&self) -> &[String] {
+        &self.circuit_management_types
+    }
+
+    pub fn splinterd_reconnect_limit(&self) -> u64 {
+        self.splinterd_reconnect_limit
+    }
+
+    pub fn submit_retry_limit(&self) -> u32 {
+        self.submit_retry_limit
+    }
+
+    pub fn submit_retry_backoff_millis(&self) -> u64 {
+        self.submit_retry_backoff_millis
+    }
+
+    pub fn event_queue_depth(&self) -> usize {
+        self.event_queue_depth
+    }
+
+    pub fn event_queue_workers(&self) -> usize {
+        self.event_queue_workers
+    }
+
+    pub fn event_queue_full_policy(&self) -> QueueFullPolicy {
+        self.event_queue_full_policy
+    }
+
+    pub fn event_batch_size(&self) -> usize {
+        self.event_batch_size
+    }
+
+    pub fn export_mode(&self) -> ExportMode {
+        self.export_mode
+    }
+
+    pub fn aggregation_period_secs(&self) -> u64 {
+        self.aggregation_period_secs
+    }
+
+    pub fn aggregation_min_group_size(&self) -> u64 {
+        self.aggregation_min_group_size
+    }
+
+    pub fn shutdown_timeout_secs(&self) -> u64 {
+        self.shutdown_timeout_secs
+    }
+
+    pub fn event_dedup_window_secs(&self) -> u64 {
+        self.event_dedup_window_secs
+    }
+
+    pub fn event_filters(&self) -> &[FilterRule] {
+        &self.event_filters
+    }
+
+    pub fn event_filter_default(&self) -> FilterAction {
+        self.event_filter_default
+    }
+
+    pub fn event_history_size(&self) -> usize {
+        self.event_history_size
+    }
+
+    pub fn api_keys(&self) -> &[ApiKeyConfig] {
+        &self.api_keys
+    }
+
+    pub fn cors(&self) -> &CorsConfig {
+        &self.cors
+    }
+
+    pub fn submit_rate_limit_per_minute(&self) -> u64 {
+        self.submit_rate_limit_per_minute
+    }
+
+    pub fn submit_rate_limit_burst(&self) -> u64 {
+        self.submit_rate_limit_burst
+    }
+
+    pub fn idempotency_key_ttl_secs(&self) -> u64 {
+        self.idempotency_key_ttl_secs
+    }
+
+    pub fn node_cache_ttl_secs(&self) -> u64 {
+        self.node_cache_ttl_secs
+    }
+
+    pub fn compression(&self) -> &CompressionConfig {
+        &self.compression
+    }
+
+    pub fn failed_event_history_size(&self) -> usize {
+        self.failed_event_history_size
+    }
+
+    pub fn rest_api_tls(&self) -> Option<&TlsConfig> {
+        self.rest_api_tls.as_ref()
+    }
+
+    pub fn rest_api_unix_socket(&self) -> Option<&str> {
+        self.rest_api_unix_socket.as_ref().map(String::as_str)
+    }
+
+    pub fn rest_api_tcp_enabled(&self) -> bool {
+        self.rest_api_tcp_enabled
+    }
+
+    pub fn export_sinks(&self) -> &[ExportSinkConfig] {
+        &self.export_sinks
+    }
+
+    pub fn webhook_delivery_history_size(&self) -> usize {
+        self.webhook_delivery_history_size
+    }
+
+    pub fn state_export_prefixes(&self) -> &[String] {
+        &self.state_export_prefixes
+    }
+
+    pub fn export_run_history_size(&self) -> usize {
+        self.export_run_history_size
+    }
+
+    pub fn batch_status_cache_size(&self) -> usize {
+        self.batch_status_cache_size
+    }
+
+    pub fn signing_key_file(&self) -> Option<&str> {
+        self.signing_key_file.as_ref().map(String::as_str)
+    }
+
+    pub fn signing_key_env(&self) -> Option<&str> {
+        self.signing_key_env.as_ref().map(String::as_str)
+    }
+
+    pub fn server_side_signing(&self) -> bool {
+        self.server_side_signing
+    }
+
+    pub fn remote_signer(&self) -> Option<&RemoteSignerConfig> {
+        self.remote_signer.as_ref()
+    }
+
+    pub fn registered_keys(&self) -> &[RegisteredKeyConfig] {
+        &self.registered_keys
+    }
+}
+
+/// The splinterd endpoints to connect to, in order of preference, and which one is in use.
+///
+/// Clones share the active endpoint, so a failover is seen by every part of the event listener.
+#[derive(Debug, Clone)]
+pub struct SplinterdEndpoints {
+    urls: Vec<String>,
+    active: Arc<AtomicUsize>,
+}
+
+impl SplinterdEndpoints {
+    fn new(urls: Vec<String>) -> Self {
+        SplinterdEndpoints {
+            urls,
+            active: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    /// Returns the endpoint currently in use.
+    pub fn active(&self) -> &str {
+        &self.urls[self.active.load(Ordering::SeqCst) % self.urls.len()]
+    }
+
+    /// Switches away from `failed_url` to the next endpoint, wrapping around to the first.
+    /// Returns true if this call switched endpoints.
+    ///
+    /// Each admin websocket fails over on its own; if another connection has already moved
+    /// away from `failed_url`, the endpoint it chose is kept instead of skipping ahead again.
+    pub fn fail_over(&self, failed_url: &str) -> bool {
+        let current = self.active.load(Ordering::SeqCst);
+        self.urls[current % self.urls.len()] == failed_url
+            && self
+                .active
+                .compare_exchange(current, current + 1, Ordering::SeqCst, Ordering::SeqCst)
+                .is_ok()
+    }
+
+    /// Returns true if there is another endpoint to fail over to.
+    pub fn has_alternatives(&self) -> bool {
+        self.urls.len() > 1
+    }
+
+    /// Returns every endpoint, in order of preference.
+    pub fn urls(&self) -> &[String] {
+        &self.urls
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct EventListenerConfig {
-    splinterd_url: String,
+    splinterd_endpoints: SplinterdEndpoints,
+    rest_api_endpoint: String,
     deployment_config: DeploymentConfig,
 }
 
 impl EventListenerConfig {
+    /// Returns the splinterd endpoint currently in use.
     pub fn splinterd_url(&self) -> &str {
-        &self.splinterd_url
+        self.splinterd_endpoints.active()
+    }
+
+    pub fn splinterd_endpoints(&self) -> &SplinterdEndpoints {
+        &self.splinterd_endpoints
+    }
+
+    pub fn rest_api_endpoint(&self) -> &str {
+        &self.rest_api_endpoint
     }
 
     pub fn deployment_config(&self) -> &DeploymentConfig {
         &self.deployment_config
     }
+
+    /// Returns a copy of the configuration using another deployment configuration, such as one
+    /// proposed to replace it. The copy fails over between the splinterd endpoints on its own.
+    pub fn with_deployment_config(&self, deployment_config: DeploymentConfig) -> Self {
+        EventListenerConfig {
+            splinterd_endpoints: SplinterdEndpoints::new(self.splinterd_endpoints.urls.clone()),
+            rest_api_endpoint: self.rest_api_endpoint.clone(),
+            deployment_config,
+        }
+    }
 }
 
 pub struct DataReaderConfigBuilder {
-    splinterd_url: Option<String>,
+    splinterd_urls: Option<Vec<String>>,
+    rest_api_endpoint: Option<String>,
     config_file: Option<String>,
 }
 
 impl Default for DataReaderConfigBuilder {
     fn default() -> Self {
         Self {
-            splinterd_url: Some("http://127.0.0.1:8080".to_owned()),
+            splinterd_urls: Some(vec!["http://127.0.0.1:8080".to_owned()]),
+            rest_api_endpoint: Some("127.0.0.1:8000".to_owned()),
             config_file: Some("deployment-config.yaml".to_owned()),
         }
     }
@@ -121,10 +1217,14 @@ impl Default for DataReaderConfigBuilder {
 impl DataReaderConfigBuilder {
     pub fn with_cli_args(&mut self, matches: &clap::ArgMatches<'_>) -> Self {
         Self {
-            splinterd_url: matches
-                .value_of("splinterd_url")
+            splinterd_urls: matches
+                .values_of("splinterd_url")
+                .map(|urls| urls.map(ToOwned::to_owned).collect())
+                .or_else(|| self.splinterd_urls.take()),
+            rest_api_endpoint: matches
+                .value_of("bind")
                 .map(ToOwned::to_owned)
-                .or_else(|| self.splinterd_url.take()),
+                .or_else(|| self.rest_api_endpoint.take()),
             config_file: matches
                 .value_of("config")
                 .map(ToOwned::to_owned)
@@ -133,28 +1233,70 @@ impl DataReaderConfigBuilder {
     }
 
     pub fn build(mut self) -> Result<EventListenerConfig, ConfigurationError> {
+        let splinterd_urls = self
+            .splinterd_urls
+            .take()
+            .filter(|urls| !urls.is_empty())
+            .ok_or_else(|| ConfigurationError::MissingValue("splinterd_url".to_owned()))?;
+        let rest_api_endpoint = self
+            .rest_api_endpoint
+            .take()
+            .ok_or_else(|| ConfigurationError::MissingValue("rest_api_endpoint".to_owned()))?;
         Ok(EventListenerConfig {
-            splinterd_url: self
-                .splinterd_url
-                .take()
-                .ok_or_else(|| ConfigurationError::MissingValue("splinterd_url".to_owned()))?,
+            splinterd_endpoints: SplinterdEndpoints::new(splinterd_urls),
+            rest_api_endpoint,
             deployment_config: DeploymentConfig::from(self.config_file.take())?,
         })
     }
 }
 
-pub fn get_node(splinterd_url: &str) -> Result<Node, GetNodeError> {
+/// Fetches the splinterd node from the active endpoint, failing over to each of the others in
+/// turn until one of them responds.
+pub fn get_node(
+    endpoints: &SplinterdEndpoints,
+    token_provider: Option<&TokenProvider>,
+    metrics: &Metrics,
+) -> Result<Node, GetNodeError> {
+    let mut attempts = 0;
+    loop {
+        let splinterd_url = endpoints.active().to_string();
+        let err = match get_node_from(&splinterd_url, token_provider) {
+            Ok(node) => return Ok(node),
+            Err(err) => err,
+        };
+        attempts += 1;
+        if attempts >= endpoints.urls().len() {
+            return Err(err);
+        }
+        if endpoints.fail_over(&splinterd_url) {
+            metrics.splinterd_failover();
+        }
+        warn!(
+            "{}, failing over from {} to {}",
+            err,
+            splinterd_url,
+            endpoints.active()
+        );
+    }
+}
+
+fn get_node_from(
+    splinterd_url: &str,
+    token_provider: Option<&TokenProvider>,
+) -> Result<Node, GetNodeError> {
     let mut runtime = Runtime::new()
         .map_err(|err| GetNodeError(format!("Failed to get set up runtime: {}", err)))?;
     let client = HyperClient::new();
     let splinterd_url = splinterd_url.to_owned();
+    let token_provider = token_provider.cloned();
     let uri = format!("{}/status", splinterd_url)
         .parse::<Uri>()
         .map_err(|err| GetNodeError(format!("Failed to get set up request: {}", err)))?;
+    let req = get_request(uri, token_provider.as_ref())?;
 
     runtime.block_on(
         client
-            .get(uri)
+            .request(req)
             .map_err(|err| {
                 GetNodeError(format!(
                     "Failed to get splinter node metadata: {}",
@@ -204,9 +1346,13 @@ pub fn get_node(splinterd_url: &str) -> Result<Node, GetNodeError> {
                                     err
                                 ))))
                 };
+                let req = match get_request(uri, token_provider.as_ref()) {
+                    Ok(req) => req,
+                    Err(err) => return Either::A(future::err(err)),
+                };
 
                 Either::B(client
-                    .get(uri)
+                    .request(req)
                     .map_err(|err| {
                         GetNodeError(format!(
                             "Failed to get splinter node: {}",
@@ -248,3 +1394,166 @@ pub fn get_node(splinterd_url: &str) -> Result<Node, GetNodeError> {
             }),
     )
 }
+
+/// number of circuits requested from splinterd at a time
+const CIRCUIT_PAGE_SIZE: usize = 100;
+
+/// Fetches the circuits this node is a member of, as listed by splinterd's /admin/circuits,
+/// failing over between the endpoints like `get_node`.
+pub fn get_circuits(
+    endpoints: &SplinterdEndpoints,
+    token_provider: Option<&TokenProvider>,
+    metrics: &Metrics,
+) -> Result<Vec<Value>, GetNodeError> {
+    let mut attempts = 0;
+    loop {
+        let splinterd_url = endpoints.active().to_string();
+        let err = match get_circuits_from(&splinterd_url, token_provider) {
+            Ok(circuits) => return Ok(circuits),
+            Err(err) => err,
+        };
+        attempts += 1;
+        if attempts >= endpoints.urls().len() {
+            return Err(err);
+        }
+        if endpoints.fail_over(&splinterd_url) {
+            metrics.splinterd_failover();
+        }
+        warn!(
+            "{}, failing over from {} to {}",
+            err,
+            splinterd_url,
+            endpoints.active()
+        );
+    }
+}
+
+/// Fetches every page of circuits from one splinterd endpoint.
+fn get_circuits_from(
+    splinterd_url: &str,
+    token_provider: Option<&TokenProvider>,
+) -> Result<Vec<Value>, GetNodeError> {
+    let mut runtime = Runtime::new()
+        .map_err(|err| GetNodeError(format!("Failed to get set up runtime: {}", err)))?;
+    let client = HyperClient::new();
+    let mut circuits = Vec::new();
+    loop {
+        let uri = format!(
+            "{}/admin/circuits?limit={}&offset={}",
+            splinterd_url,
+            CIRCUIT_PAGE_SIZE,
+            circuits.len()
+        )
+        .parse::<Uri>()
+        .map_err(|err| GetNodeError(format!("Failed to get set up request: {}", err)))?;
+        let req = get_request(uri, token_provider)?;
+        let (status, body) = runtime.block_on(
+            client
+                .request(req)
+                .and_then(|resp| {
+                    let status = resp.status();
+                    resp.into_body().concat2().map(move |body| (status, body))
+                })
+                .map_err(|err| GetNodeError(format!("Failed to get circuits: {}", err))),
+        )?;
+        if status != StatusCode::OK {
+            return Err(GetNodeError(format!(
+                "Failed to get circuits. Splinterd responded with status {}",
+                status
+            )));
+        }
+
+        let page: Value = serde_json::from_slice(&body)
+            .map_err(|err| GetNodeError(format!("Failed to get circuits: {}", err)))?;
+        let data = page["data"].as_array().ok_or_else(|| {
+            GetNodeError("Failed to get circuits: the response holds no data".to_string())
+        })?;
+        circuits.extend(data.iter().cloned());
+        let total = page["paging"]["total"].as_u64().map(|total| total as usize);
+        if data.len() < CIRCUIT_PAGE_SIZE || total.map_or(false, |total| circuits.len() >= total) {
+            return Ok(circuits);
+        }
+    }
+}
+
+/// Builds a GET request, attaching the bearer token when one is configured.
+fn get_request(
+    uri: Uri,
+    token_provider: Option<&TokenProvider>,
+) -> Result<Request<Body>, GetNodeError> {
+    let mut builder = Request::builder();
+    builder.method("GET").uri(uri);
+    if let Some(token_provider) = token_provider {
+        let authorization = token_provider
+            .authorization_header()
+            .map_err(|err| GetNodeError(err.to_string()))?;
+        builder.header(AUTHORIZATION, authorization);
+    }
+    builder
+        .body(Body::empty())
+        .map_err(|err| GetNodeError(format!("Failed to get set up request: {}", err)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const REQUESTER: &str = "02d1fbda50dbcd0d3c286a6a9fa71aa7ce2d97159b90ddd463e0816422d621e135";
+
+    fn rule(yaml: &str) -> FilterRule {
+        serde_yaml::from_str(yaml).unwrap()
+    }
+
+    fn sinks(yaml: &str) -> Vec<ExportSinkConfig> {
+        serde_yaml::from_str(yaml).unwrap()
+    }
+
+    #[test]
+    fn empty_rule_matches_any_circuit() {
+        let rule = rule("action: drop");
+        assert!(rule.matches("gameroom", REQUESTER, &[]));
+        assert!(rule.matches("", "", &["node-a"]));
+    }
+
+    #[test]
+    fn rule_matches_when_every_criterion_does() {
+        let rule = rule(&format!(
+            "action: keep\n\
+             circuit_management_types: [gameroom, grid]\n\
+             requesters: [\"{}\"]\n\
+             member_node_ids: [node-a]",
+            REQUESTER.to_uppercase()
+        ));
+        assert!(rule.matches("grid", REQUESTER, &["node-b", "node-a"]));
+        assert!(!rule.matches("other", REQUESTER, &["node-a"]));
+        assert!(!rule.matches("grid", "03ab", &["node-a"]));
+        assert!(!rule.matches("grid", REQUESTER, &["node-b"]));
+    }
+
+    #[test]
+    fn route_rules_must_name_a_configured_sink() {
+        let export_sinks = sinks(
+            "- name: archive\n  type: nats\n  servers: [\"nats://nats:4222\"]\n  subject: events",
+        );
+        assert!(check_filter_rules(&[rule("action: route\nsink: archive")], &export_sinks).is_ok());
+        assert!(check_filter_rules(&[rule("action: route")], &export_sinks).is_err());
+        assert!(check_filter_rules(&[rule("action: route\nsink: other")], &export_sinks).is_err());
+        assert!(check_filter_rules(&[rule("action: keep\nsink: archive")], &export_sinks).is_err());
+        assert!(check_filter_rules(&[rule("action: drop")], &export_sinks).is_ok());
+    }
+
+    #[test]
+    fn export_sink_names_must_be_unique() {
+        let export_sinks = sinks(
+            "- name: archive\n  type: nats\n  servers: [\"nats://a:4222\"]\n  subject: events\n\
+             - name: archive\n  type: nats\n  servers: [\"nats://b:4222\"]\n  subject: events",
+        );
+        assert!(check_filter_rules(&[], &export_sinks).is_err());
+    }
+
+    #[test]
+    fn api_key_without_a_role_is_read_only() {
+        let key: ApiKeyConfig = serde_yaml::from_str("name: ci\nkey_sha256: ab12").unwrap();
+        assert_eq!(key.role(), Role::ReadOnly);
+    }
+}
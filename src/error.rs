@@ -16,17 +16,21 @@
 use std::error::Error;
 use std::fmt;
 
-use sawtooth_sdk::signing::Error as KeyGenError;
-
 use crate::event_handler::EventHandlerError;
+use crate::publisher::PublisherError;
+use crate::rest_api::RestApiServerError;
+use crate::signer::SignerError;
 
 #[derive(Debug)]
 pub enum EventListenerError {
     LoggingInitializationError(flexi_logger::FlexiLoggerError),
     ConfigurationError(Box<ConfigurationError>),
     AppAuthHandlerError(EventHandlerError),
-    KeyGenError(KeyGenError),
+    SignerError(SignerError),
     GetNodeError(GetNodeError),
+    PublisherError(PublisherError),
+    ShutdownSignalError(ctrlc::Error),
+    RestApiError(RestApiServerError),
 }
 
 impl Error for EventListenerError {
@@ -35,8 +39,11 @@ impl Error for EventListenerError {
             EventListenerError::LoggingInitializationError(err) => Some(err),
             EventListenerError::ConfigurationError(err) => Some(err),
             EventListenerError::AppAuthHandlerError(err) => Some(err),
-            EventListenerError::KeyGenError(err) => Some(err),
+            EventListenerError::SignerError(err) => Some(err),
             EventListenerError::GetNodeError(err) => Some(err),
+            EventListenerError::PublisherError(err) => Some(err),
+            EventListenerError::ShutdownSignalError(err) => Some(err),
+            EventListenerError::RestApiError(err) => Some(err),
         }
     }
 }
@@ -53,16 +60,25 @@ impl fmt::Display for EventListenerError {
                 "The application authorization handler returned an error: {}",
                 e
             ),
-            EventListenerError::KeyGenError(e) => write!(
+            EventListenerError::SignerError(e) => {
+                write!(f, "an error occurred while loading the signer: {}", e)
+            }
+            EventListenerError::GetNodeError(e) => write!(
                 f,
-                "an error occurred while generating a new key pair: {}",
+                "an error occurred while getting splinterd node information: {}",
                 e
             ),
-            EventListenerError::GetNodeError(e) => write!(
+            EventListenerError::PublisherError(e) => {
+                write!(f, "an error occurred while starting the publisher: {}", e)
+            }
+            EventListenerError::ShutdownSignalError(e) => write!(
                 f,
-                "an error occurred while getting splinterd node information: {}",
+                "an error occurred while setting up the shutdown signal handler: {}",
                 e
             ),
+            EventListenerError::RestApiError(e) => {
+                write!(f, "an error occurred while running the REST API: {}", e)
+            }
         }
     }
 }
@@ -79,9 +95,27 @@ impl From<EventHandlerError> for EventListenerError {
     }
 }
 
-impl From<KeyGenError> for EventListenerError {
-    fn from(err: KeyGenError) -> EventListenerError {
-        EventListenerError::KeyGenError(err)
+impl From<SignerError> for EventListenerError {
+    fn from(err: SignerError) -> EventListenerError {
+        EventListenerError::SignerError(err)
+    }
+}
+
+impl From<PublisherError> for EventListenerError {
+    fn from(err: PublisherError) -> EventListenerError {
+        EventListenerError::PublisherError(err)
+    }
+}
+
+impl From<ctrlc::Error> for EventListenerError {
+    fn from(err: ctrlc::Error) -> EventListenerError {
+        EventListenerError::ShutdownSignalError(err)
+    }
+}
+
+impl From<RestApiServerError> for EventListenerError {
+    fn from(err: RestApiServerError) -> EventListenerError {
+        EventListenerError::RestApiError(err)
     }
 }
 
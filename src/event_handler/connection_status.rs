@@ -0,0 +1,152 @@
+/*
+ * Copyright 2019 Walmart Inc.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * -----------------------------------------------------------------------------
+ */
+
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use std::time::UNIX_EPOCH;
+
+use crate::clock::Clock;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConnectionState {
+    /// The websocket has been started but has not opened yet
+    Connecting,
+    Connected,
+    /// The connection was lost and is being re-established
+    Reconnecting,
+    /// The connection was lost and will not be retried
+    Down,
+}
+
+/// The state of the admin websocket for one circuit management type
+#[derive(Debug, Clone, Serialize)]
+pub struct ConnectionInfo {
+    state: ConnectionState,
+    splinterd_url: String,
+    /// Time the last admin event was received, in seconds since the epoch
+    last_event_time: Option<u64>,
+    reconnect_count: u64,
+}
+
+impl ConnectionInfo {
+    pub fn state(&self) -> ConnectionState {
+        self.state
+    }
+
+    pub fn last_event_time(&self) -> Option<u64> {
+        self.last_event_time
+    }
+
+    pub fn reconnect_count(&self) -> u64 {
+        self.reconnect_count
+    }
+}
+
+/// A shared handle reporting whether admin event ingestion is live.
+///
+/// Clones share the same state; the event handler updates it from the websocket callbacks.
+#[derive(Clone)]
+pub struct ConnectionStatus {
+    connections: Arc<Mutex<BTreeMap<String, ConnectionInfo>>>,
+    clock: Arc<dyn Clock>,
+}
+
+impl ConnectionStatus {
+    pub fn new(clock: Arc<dyn Clock>) -> Self {
+        ConnectionStatus {
+            connections: Arc::new(Mutex::new(BTreeMap::new())),
+            clock,
+        }
+    }
+
+    /// Returns the state of every admin websocket, keyed by circuit management type.
+    pub fn snapshot(&self) -> BTreeMap<String, ConnectionInfo> {
+        match self.connections.lock() {
+            Ok(connections) => connections.clone(),
+            Err(_) => {
+                error!("Connection status lock was poisoned");
+                BTreeMap::new()
+            }
+        }
+    }
+
+    /// Returns true if every admin websocket is connected.
+    pub fn is_live(&self) -> bool {
+        let connections = self.snapshot();
+        !connections.is_empty()
+            && connections
+                .values()
+                .all(|info| info.state == ConnectionState::Connected)
+    }
+
+    pub(super) fn connecting(&self, circuit_management_type: &str, splinterd_url: &str) {
+        self.update(circuit_management_type, |info| {
+            info.state = ConnectionState::Connecting;
+            info.splinterd_url = splinterd_url.to_string();
+        })
+    }
+
+    pub(super) fn connected(&self, circuit_management_type: &str) {
+        self.update(circuit_management_type, |info| {
+            info.state = ConnectionState::Connected
+        })
+    }
+
+    pub(super) fn event_received(&self, circuit_management_type: &str) {
+        let now = self
+            .clock
+            .now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_secs())
+            .ok();
+        self.update(circuit_management_type, |info| {
+            info.state = ConnectionState::Connected;
+            info.last_event_time = now;
+        })
+    }
+
+    pub(super) fn reconnecting(&self, circuit_management_type: &str) {
+        self.update(circuit_management_type, |info| {
+            info.state = ConnectionState::Reconnecting;
+            info.reconnect_count += 1;
+        })
+    }
+
+    pub(super) fn down(&self, circuit_management_type: &str) {
+        self.update(circuit_management_type, |info| {
+            info.state = ConnectionState::Down
+        })
+    }
+
+    fn update<F>(&self, circuit_management_type: &str, f: F)
+    where
+        F: FnOnce(&mut ConnectionInfo),
+    {
+        match self.connections.lock() {
+            Ok(mut connections) => f(connections
+                .entry(circuit_management_type.to_string())
+                .or_insert_with(|| ConnectionInfo {
+                    state: ConnectionState::Connecting,
+                    splinterd_url: String::new(),
+                    last_event_time: None,
+                    reconnect_count: 0,
+                })),
+            Err(_) => error!("Connection status lock was poisoned"),
+        }
+    }
+}
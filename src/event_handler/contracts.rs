@@ -0,0 +1,105 @@
+/*
+ * Copyright 2019 Walmart Inc.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * -----------------------------------------------------------------------------
+ */
+
+//! The Sabre contracts deployed on each circuit, learned from contract registry state changes.
+
+use std::collections::BTreeMap;
+use std::sync::{Arc, RwLock};
+
+use sabre_sdk::protocol::state::ContractRegistryList;
+use sabre_sdk::protos::FromBytes;
+
+/// A version of a contract in a circuit's contract registry
+#[derive(Debug, Clone, Serialize)]
+pub struct DeployedContract {
+    name: String,
+    version: String,
+    /// SHA-512 of the contract's WebAssembly, as hex
+    contract_sha512: String,
+    /// public key of the uploader, as hex
+    creator: String,
+}
+
+/// The contracts of every circuit, by the contract registry address listing them.
+///
+/// Contracts are known once a change to their registry is seen, and kept until the event
+/// listener restarts. Clones share the same contracts.
+#[derive(Clone, Default)]
+pub struct ContractInventory {
+    circuits: Arc<RwLock<BTreeMap<String, BTreeMap<String, Vec<DeployedContract>>>>>,
+}
+
+impl ContractInventory {
+    /// Replaces the contracts listed at a contract registry address with those in its state.
+    pub fn update(&self, circuit_id: &str, address: &str, data: &[u8]) -> Result<(), String> {
+        let list = ContractRegistryList::from_bytes(data).map_err(|err| err.to_string())?;
+        let contracts = list
+            .registries()
+            .iter()
+            .flat_map(|registry| {
+                registry
+                    .versions()
+                    .iter()
+                    .map(move |version| DeployedContract {
+                        name: registry.name().to_string(),
+                        version: version.version().to_string(),
+                        contract_sha512: version.contract_sha512().to_string(),
+                        creator: version.creator().to_string(),
+                    })
+            })
+            .collect();
+        let mut circuits = self
+            .circuits
+            .write()
+            .map_err(|_| "Contract inventory lock was poisoned".to_string())?;
+        circuits
+            .entry(circuit_id.to_string())
+            .or_default()
+            .insert(address.to_string(), contracts);
+        Ok(())
+    }
+
+    /// Forgets the contracts listed at a deleted contract registry address.
+    pub fn remove(&self, circuit_id: &str, address: &str) {
+        match self.circuits.write() {
+            Ok(mut circuits) => {
+                if let Some(registries) = circuits.get_mut(circuit_id) {
+                    registries.remove(address);
+                }
+            }
+            Err(_) => error!("Contract inventory lock was poisoned"),
+        }
+    }
+
+    /// Returns the contracts deployed on the circuit, ordered by name and version.
+    pub fn list(&self, circuit_id: &str) -> Vec<DeployedContract> {
+        match self.circuits.read() {
+            Ok(circuits) => {
+                let mut contracts = circuits
+                    .get(circuit_id)
+                    .map(|registries| registries.values().flatten().cloned().collect::<Vec<_>>())
+                    .unwrap_or_default();
+                contracts.sort_by(|a, b| (&a.name, &a.version).cmp(&(&b.name, &b.version)));
+                contracts
+            }
+            Err(_) => {
+                error!("Contract inventory lock was poisoned");
+                Vec::new()
+            }
+        }
+    }
+}
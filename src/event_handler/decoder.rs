@@ -0,0 +1,154 @@
+/*
+ * Copyright 2019 Walmart Inc.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * -----------------------------------------------------------------------------
+ */
+
+//! Decodes exported scabbard state into structured records.
+
+use std::sync::Arc;
+
+use crypto::digest::Digest;
+use crypto::sha2::Sha512;
+use sabre_sdk::protocol::state::{ContractList, ContractRegistryList, NamespaceRegistryList};
+use sabre_sdk::protos::FromBytes;
+use serde_json::Value;
+
+use super::sabre::{CONTRACT_PREFIX, CONTRACT_REGISTRY_PREFIX, NAMESPACE_REGISTRY_PREFIX};
+
+/// Turns the raw state at an address into a structured record.
+pub trait PayloadDecoder: Send + Sync {
+    /// Returns `None` if the decoder does not handle the address, otherwise the decoded state
+    /// or the reason it could not be decoded.
+    fn decode(&self, address: &str, data: &[u8]) -> Option<Result<Value, String>>;
+}
+
+/// The decoders tried, in order, on every exported state change.
+///
+/// Clones share the same decoders.
+#[derive(Clone)]
+pub struct PayloadDecoders {
+    decoders: Vec<Arc<dyn PayloadDecoder>>,
+}
+
+impl Default for PayloadDecoders {
+    /// Decodes Sabre's contract registry, namespace registry and contract state.
+    fn default() -> Self {
+        PayloadDecoders {
+            decoders: vec![Arc::new(SabreStateDecoder)],
+        }
+    }
+}
+
+impl PayloadDecoders {
+    /// Returns the record produced by the first decoder that handles the address.
+    pub fn decode(&self, address: &str, data: &[u8]) -> Option<Result<Value, String>> {
+        self.decoders
+            .iter()
+            .find_map(|decoder| decoder.decode(address, data))
+    }
+}
+
+/// Decodes the state kept by Sabre itself.
+pub struct SabreStateDecoder;
+
+impl PayloadDecoder for SabreStateDecoder {
+    fn decode(&self, address: &str, data: &[u8]) -> Option<Result<Value, String>> {
+        if address.starts_with(CONTRACT_REGISTRY_PREFIX) {
+            Some(decode_contract_registries(data))
+        } else if address.starts_with(NAMESPACE_REGISTRY_PREFIX) {
+            Some(decode_namespace_registries(data))
+        } else if address.starts_with(CONTRACT_PREFIX) {
+            Some(decode_contracts(data))
+        } else {
+            None
+        }
+    }
+}
+
+fn decode_contract_registries(data: &[u8]) -> Result<Value, String> {
+    let list = ContractRegistryList::from_bytes(data).map_err(|err| err.to_string())?;
+    let registries = list
+        .registries()
+        .iter()
+        .map(|registry| {
+            let versions = registry
+                .versions()
+                .iter()
+                .map(|version| {
+                    json!({
+                        "version": version.version(),
+                        "contract_sha512": version.contract_sha512(),
+                        "creator": version.creator(),
+                    })
+                })
+                .collect::<Vec<_>>();
+            json!({
+                "name": registry.name(),
+                "owners": registry.owners(),
+                "versions": versions,
+            })
+        })
+        .collect::<Vec<_>>();
+    Ok(json!({ "kind": "contract_registry", "registries": registries }))
+}
+
+fn decode_namespace_registries(data: &[u8]) -> Result<Value, String> {
+    let list = NamespaceRegistryList::from_bytes(data).map_err(|err| err.to_string())?;
+    let registries = list
+        .registries()
+        .iter()
+        .map(|registry| {
+            let permissions = registry
+                .permissions()
+                .iter()
+                .map(|permission| {
+                    json!({
+                        "contract_name": permission.contract_name(),
+                        "read": permission.read(),
+                        "write": permission.write(),
+                    })
+                })
+                .collect::<Vec<_>>();
+            json!({
+                "namespace": registry.namespace(),
+                "owners": registry.owners(),
+                "permissions": permissions,
+            })
+        })
+        .collect::<Vec<_>>();
+    Ok(json!({ "kind": "namespace_registry", "registries": registries }))
+}
+
+/// Describes contracts by their hash rather than including their WebAssembly.
+fn decode_contracts(data: &[u8]) -> Result<Value, String> {
+    let list = ContractList::from_bytes(data).map_err(|err| err.to_string())?;
+    let contracts = list
+        .contracts()
+        .iter()
+        .map(|contract| {
+            let mut sha = Sha512::new();
+            sha.input(contract.contract());
+            json!({
+                "name": contract.name(),
+                "version": contract.version(),
+                "inputs": contract.inputs(),
+                "outputs": contract.outputs(),
+                "creator": contract.creator(),
+                "contract_sha512": sha.result_str(),
+            })
+        })
+        .collect::<Vec<_>>();
+    Ok(json!({ "kind": "contract", "contracts": contracts }))
+}
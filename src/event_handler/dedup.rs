@@ -0,0 +1,128 @@
+/*
+ * Copyright 2019 Walmart Inc.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * -----------------------------------------------------------------------------
+ */
+
+use std::collections::{HashSet, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crypto::digest::Digest;
+use crypto::sha2::Sha256;
+use splinter::admin::messages::AdminServiceEvent;
+
+struct Window {
+    seen: HashSet<String>,
+    /// hashes in the order they were first seen, to expire them
+    order: VecDeque<(Instant, String)>,
+}
+
+/// Remembers the admin events received within a sliding window, so events splinterd redelivers
+/// after a reconnect are not exported twice.
+///
+/// Events are identified by a hash of their content. Clones share the same window.
+#[derive(Clone)]
+pub struct EventDeduplicator {
+    window: Duration,
+    state: Arc<Mutex<Window>>,
+}
+
+impl EventDeduplicator {
+    /// Creates a deduplicator remembering events for `window`; a zero window disables it.
+    pub fn new(window: Duration) -> Self {
+        EventDeduplicator {
+            window,
+            state: Arc::new(Mutex::new(Window {
+                seen: HashSet::new(),
+                order: VecDeque::new(),
+            })),
+        }
+    }
+
+    /// Returns true if the event is not a repeat of one seen within the window, and records it.
+    pub fn first_delivery(&self, event: &AdminServiceEvent) -> bool {
+        if self.window == Duration::from_secs(0) {
+            return true;
+        }
+
+        let hash = match serde_json::to_vec(event) {
+            Ok(bytes) => {
+                let mut sha = Sha256::new();
+                sha.input(&bytes);
+                sha.result_str()
+            }
+            Err(err) => {
+                warn!(
+                    "Unable to hash admin event, skipping deduplication: {}",
+                    err
+                );
+                return true;
+            }
+        };
+        self.first_seen(hash, Instant::now())
+    }
+
+    /// Records a hash seen at `now`; returns false if it was already seen within the window.
+    fn first_seen(&self, hash: String, now: Instant) -> bool {
+        let mut state = match self.state.lock() {
+            Ok(state) => state,
+            Err(_) => {
+                error!("Deduplication lock was poisoned, skipping deduplication");
+                return true;
+            }
+        };
+
+        while let Some((seen_at, _)) = state.order.front() {
+            if now.duration_since(*seen_at) < self.window {
+                break;
+            }
+            if let Some((_, expired)) = state.order.pop_front() {
+                state.seen.remove(&expired);
+            }
+        }
+
+        if !state.seen.insert(hash.clone()) {
+            return false;
+        }
+        state.order.push_back((now, hash));
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn first_seen_refuses_repeats_within_the_window() {
+        let deduplicator = EventDeduplicator::new(Duration::from_secs(60));
+        let start = Instant::now();
+        assert!(deduplicator.first_seen("a".into(), start));
+        assert!(deduplicator.first_seen("b".into(), start));
+        assert!(!deduplicator.first_seen("a".into(), start + Duration::from_secs(59)));
+        assert!(!deduplicator.clone().first_seen("b".into(), start));
+    }
+
+    #[test]
+    fn first_seen_forgets_hashes_after_the_window() {
+        let deduplicator = EventDeduplicator::new(Duration::from_secs(60));
+        let start = Instant::now();
+        assert!(deduplicator.first_seen("a".into(), start));
+        assert!(deduplicator.first_seen("b".into(), start + Duration::from_secs(30)));
+        let later = start + Duration::from_secs(60);
+        assert!(deduplicator.first_seen("a".into(), later));
+        assert!(!deduplicator.first_seen("b".into(), later));
+    }
+}
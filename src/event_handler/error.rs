@@ -20,16 +20,19 @@ use std::error::Error;
 use std::fmt;
 
 use futures::future;
+use hyper::StatusCode;
 use sabre_sdk::protocol::payload::{
     CreateContractActionBuildError, CreateContractRegistryActionBuildError,
     CreateNamespaceRegistryActionBuildError, CreateNamespaceRegistryPermissionActionBuildError,
-    SabrePayloadBuildError,
+    SabrePayloadBuildError, UpdateNamespaceRegistryOwnersActionBuildError,
 };
 use sabre_sdk::protos::ProtoConversionError as SabreProtoConversionError;
 use sawtooth_sdk::signing::Error as SigningError;
 use splinter::events;
 
 use crate::application_metadata::ApplicationMetadataError;
+use crate::publisher::PublisherError;
+use crate::signer::SignerError;
 
 #[derive(Debug)]
 pub enum EventHandlerError {
@@ -40,7 +43,10 @@ pub enum EventHandlerError {
     SabreError(String),
     SawtoothError(String),
     SigningError(String),
-    BatchSubmitError(String),
+    /// The configured signer failed, such as the signing service
+    SignerError(SignerError),
+    BatchSubmitError(BatchSubmitError),
+    PublishError(PublisherError),
 }
 
 impl Error for EventHandlerError {
@@ -52,8 +58,10 @@ impl Error for EventHandlerError {
             EventHandlerError::SabreError(_) => None,
             EventHandlerError::SawtoothError(_) => None,
             EventHandlerError::SigningError(_) => None,
-            EventHandlerError::BatchSubmitError(_) => None,
+            EventHandlerError::SignerError(err) => Some(err),
+            EventHandlerError::BatchSubmitError(err) => Some(err),
             EventHandlerError::WebSocketError(err) => Some(err),
+            EventHandlerError::PublishError(err) => Some(err),
         }
     }
 }
@@ -79,16 +87,75 @@ impl fmt::Display for EventHandlerError {
             EventHandlerError::SigningError(msg) => {
                 write!(f, "A signing error occurred: {}", msg)
             }
-            EventHandlerError::BatchSubmitError(msg) => write!(
+            EventHandlerError::SignerError(err) => write!(f, "A signing error occurred: {}", err),
+            EventHandlerError::BatchSubmitError(err) => write!(
                 f,
                 "An error occurred while submitting a batch to the scabbard service: {}",
-                msg
+                err
             ),
             EventHandlerError::WebSocketError(msg) => write!(f, "WebsocketError {}", msg),
+            EventHandlerError::PublishError(err) => {
+                write!(f, "Unable to queue message for Kafka: {}", err)
+            }
         }
     }
 }
 
+/// The reason a batch submission to a scabbard service failed.
+#[derive(Debug)]
+pub enum BatchSubmitError {
+    /// The request could not be built; retrying will not help
+    InvalidRequest(String),
+    /// The service could not be reached or the connection was lost
+    ConnectionError(String),
+    /// The service responded with a non-success status
+    ResponseError { status: StatusCode, message: String },
+}
+
+impl BatchSubmitError {
+    /// Returns true if the same submission may succeed when attempted again.
+    ///
+    /// Connection failures and 5xx responses are transient; 4xx responses mean the batch was
+    /// rejected and resubmitting it would be rejected again.
+    pub fn is_retriable(&self) -> bool {
+        match self {
+            BatchSubmitError::InvalidRequest(_) => false,
+            BatchSubmitError::ConnectionError(_) => true,
+            BatchSubmitError::ResponseError { status, .. } => status.is_server_error(),
+        }
+    }
+}
+
+impl Error for BatchSubmitError {}
+
+impl fmt::Display for BatchSubmitError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BatchSubmitError::InvalidRequest(msg) => write!(f, "Invalid request: {}", msg),
+            BatchSubmitError::ConnectionError(msg) => {
+                write!(f, "The client encountered an error {}", msg)
+            }
+            BatchSubmitError::ResponseError { status, message } => write!(
+                f,
+                "The server returned an error. Status: {}, {}",
+                status, message
+            ),
+        }
+    }
+}
+
+impl From<BatchSubmitError> for EventHandlerError {
+    fn from(err: BatchSubmitError) -> EventHandlerError {
+        EventHandlerError::BatchSubmitError(err)
+    }
+}
+
+impl From<PublisherError> for EventHandlerError {
+    fn from(err: PublisherError) -> EventHandlerError {
+        EventHandlerError::PublishError(err)
+    }
+}
+
 impl From<std::io::Error> for EventHandlerError {
     fn from(err: std::io::Error) -> EventHandlerError {
         EventHandlerError::IOError(err)
@@ -142,6 +209,7 @@ impl_from_sabre_errors!(
     CreateContractRegistryActionBuildError,
     CreateNamespaceRegistryActionBuildError,
     CreateNamespaceRegistryPermissionActionBuildError,
+    UpdateNamespaceRegistryOwnersActionBuildError,
     SabreProtoConversionError,
     SabrePayloadBuildError
 );
@@ -152,6 +220,12 @@ impl From<SigningError> for EventHandlerError {
     }
 }
 
+impl From<SignerError> for EventHandlerError {
+    fn from(err: SignerError) -> Self {
+        EventHandlerError::SignerError(err)
+    }
+}
+
 impl<T> Into<future::FutureResult<T, EventHandlerError>> for EventHandlerError {
     fn into(self) -> future::FutureResult<T, EventHandlerError> {
         future::err(self)
@@ -0,0 +1,172 @@
+/*
+ * Copyright 2019 Walmart Inc.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * -----------------------------------------------------------------------------
+ */
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+use splinter::admin::messages::AdminServiceEvent;
+
+use super::filter::proposal_of;
+use super::EventHandlerError;
+use crate::clock::Clock;
+use crate::proto::pubsub::Message;
+
+/// An admin event that could not be exported, because it could not be processed or because an
+/// export sink failed to accept the message made from it
+#[derive(Debug, Clone, Serialize)]
+pub struct FailedEvent {
+    id: u64,
+    event_type: &'static str,
+    circuit_id: String,
+    error: String,
+    /// Time of the last failure, in seconds since the epoch
+    failed_at: u64,
+    attempts: u32,
+    #[serde(skip)]
+    event: AdminServiceEvent,
+    /// the message a sink failed to accept, which is published again rather than processing
+    /// the event again
+    #[serde(skip)]
+    message: Option<Message>,
+}
+
+impl FailedEvent {
+    pub(super) fn event(&self) -> &AdminServiceEvent {
+        &self.event
+    }
+
+    pub(super) fn message(&self) -> Option<&Message> {
+        self.message.as_ref()
+    }
+
+    pub(super) fn circuit_id(&self) -> &str {
+        &self.circuit_id
+    }
+}
+
+struct Inner {
+    next_id: u64,
+    /// failed events, oldest first
+    events: VecDeque<FailedEvent>,
+    capacity: usize,
+}
+
+/// Keeps the most recent admin events that failed to be exported, so they can be retried.
+///
+/// Clones share the same events.
+#[derive(Clone)]
+pub struct FailedEvents {
+    inner: Arc<Mutex<Inner>>,
+    clock: Arc<dyn Clock>,
+}
+
+impl FailedEvents {
+    /// Creates a store keeping up to `capacity` events; a zero capacity keeps none.
+    pub fn new(capacity: usize, clock: Arc<dyn Clock>) -> Self {
+        FailedEvents {
+            inner: Arc::new(Mutex::new(Inner {
+                next_id: 0,
+                events: VecDeque::new(),
+                capacity,
+            })),
+            clock,
+        }
+    }
+
+    /// Returns the failed events, oldest first.
+    pub fn list(&self) -> Vec<FailedEvent> {
+        match self.inner.lock() {
+            Ok(inner) => inner.events.iter().cloned().collect(),
+            Err(_) => {
+                error!("Failed events lock was poisoned");
+                Vec::new()
+            }
+        }
+    }
+
+    /// Records a new failure of an event.
+    pub(super) fn record(&self, event: AdminServiceEvent, err: &EventHandlerError) {
+        self.insert(None, 1, event, None, err.to_string())
+    }
+
+    /// Records that an export sink failed to accept the message made from an event.
+    pub fn record_export_failure(&self, event: AdminServiceEvent, message: Message, err: &str) {
+        self.insert(None, 1, event, Some(message), err.to_string())
+    }
+
+    /// Removes the failed event with the given id, to be retried.
+    pub(super) fn take(&self, id: u64) -> Option<FailedEvent> {
+        let mut inner = match self.inner.lock() {
+            Ok(inner) => inner,
+            Err(_) => {
+                error!("Failed events lock was poisoned");
+                return None;
+            }
+        };
+        let index = inner.events.iter().position(|failed| failed.id == id)?;
+        inner.events.remove(index)
+    }
+
+    /// Puts back an event that failed again when retried.
+    pub(super) fn retry_failed(&self, failed: FailedEvent, err: &EventHandlerError) {
+        self.insert(
+            Some(failed.id),
+            failed.attempts + 1,
+            failed.event,
+            failed.message,
+            err.to_string(),
+        )
+    }
+
+    fn insert(
+        &self,
+        id: Option<u64>,
+        attempts: u32,
+        event: AdminServiceEvent,
+        message: Option<Message>,
+        error: String,
+    ) {
+        let failed_at = self.clock.now_secs();
+        let mut inner = match self.inner.lock() {
+            Ok(inner) => inner,
+            Err(_) => {
+                error!("Failed events lock was poisoned, failed event not kept");
+                return;
+            }
+        };
+        if inner.capacity == 0 {
+            return;
+        }
+        let id = id.unwrap_or_else(|| {
+            inner.next_id += 1;
+            inner.next_id
+        });
+        if inner.events.len() == inner.capacity {
+            inner.events.pop_front();
+        }
+        inner.events.push_back(FailedEvent {
+            id,
+            event_type: super::event_type(&event),
+            circuit_id: proposal_of(&event).circuit_id.clone(),
+            error,
+            failed_at,
+            attempts,
+            event,
+            message,
+        });
+    }
+}
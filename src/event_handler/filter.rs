@@ -0,0 +1,282 @@
+/*
+ * Copyright 2019 Walmart Inc.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * -----------------------------------------------------------------------------
+ */
+
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+use serde_json::Value;
+use splinter::admin::messages::{AdminServiceEvent, CircuitProposal};
+
+use super::to_hex;
+use crate::config::{DeploymentConfig, FilterAction, FilterRule};
+
+/// What the filter rules decide for an admin event
+#[derive(Debug, Clone, PartialEq)]
+pub enum FilterDecision {
+    Keep,
+    Drop,
+    /// Keep the event, exporting the messages about its circuit only to the named export sink
+    Route(String),
+}
+
+/// What the filter rules match of a circuit
+#[derive(Debug, Clone, Default)]
+struct CircuitTraits {
+    management_type: String,
+    /// hex public key of the requester; empty for circuits only known from splinterd's list
+    requester: String,
+    member_node_ids: Vec<String>,
+}
+
+/// Decides which admin events are processed, so circuits unrelated to this deployment on a
+/// shared splinterd are not exported, and which export sink the messages about a circuit are
+/// routed to.
+///
+/// Clones share the same rules, which can be replaced while the event listener is running, and
+/// the same circuits.
+#[derive(Clone)]
+pub struct EventFilter {
+    default_action: FilterAction,
+    rules: Arc<RwLock<Vec<FilterRule>>>,
+    /// what the rules match of each circuit seen, by circuit id
+    circuits: Arc<RwLock<HashMap<String, CircuitTraits>>>,
+}
+
+impl EventFilter {
+    pub fn new(config: &DeploymentConfig) -> Self {
+        EventFilter {
+            default_action: config.event_filter_default(),
+            rules: Arc::new(RwLock::new(config.event_filters().to_vec())),
+            circuits: Arc::new(RwLock::new(HashMap::new())),
+        }
+    }
+
+    pub fn default_action(&self) -> FilterAction {
+        self.default_action
+    }
+
+    pub fn rules(&self) -> Vec<FilterRule> {
+        match self.rules.read() {
+            Ok(rules) => rules.clone(),
+            Err(_) => {
+                error!("Event filter lock was poisoned");
+                Vec::new()
+            }
+        }
+    }
+
+    pub fn set_rules(&self, rules: Vec<FilterRule>) {
+        match self.rules.write() {
+            Ok(mut current) => *current = rules,
+            Err(_) => error!("Event filter lock was poisoned, rules not replaced"),
+        }
+    }
+
+    /// Remembers what the rules match of the event's circuit, for routing its messages.
+    pub fn remember(&self, event: &AdminServiceEvent) {
+        let proposal = proposal_of(event);
+        self.insert(
+            &proposal.circuit_id,
+            CircuitTraits {
+                management_type: proposal.circuit.circuit_management_type.clone(),
+                requester: to_hex(&proposal.requester),
+                member_node_ids: proposal
+                    .circuit
+                    .members
+                    .iter()
+                    .map(|member| member.node_id.clone())
+                    .collect(),
+            },
+        );
+    }
+
+    /// Remembers the circuits listed by splinterd's /admin/circuits, whose admin events were
+    /// sent before the event listener started. The list does not give their requesters, so
+    /// rules naming requesters do not match them until one of their admin events is seen.
+    pub fn seed(&self, circuits: &[Value]) {
+        for circuit in circuits {
+            let circuit_id = match circuit["id"].as_str() {
+                Some(circuit_id) => circuit_id,
+                None => continue,
+            };
+            let management_type = circuit["circuit_management_type"]
+                .as_str()
+                .or_else(|| circuit["management_type"].as_str())
+                .unwrap_or_default();
+            let member_node_ids = circuit["members"]
+                .as_array()
+                .map(|members| {
+                    members
+                        .iter()
+                        .filter_map(|member| member.as_str().or_else(|| member["node_id"].as_str()))
+                        .map(ToOwned::to_owned)
+                        .collect()
+                })
+                .unwrap_or_default();
+            self.insert(
+                circuit_id,
+                CircuitTraits {
+                    management_type: management_type.to_string(),
+                    requester: String::new(),
+                    member_node_ids,
+                },
+            );
+        }
+    }
+
+    fn insert(&self, circuit_id: &str, traits: CircuitTraits) {
+        match self.circuits.write() {
+            Ok(mut circuits) => {
+                circuits.insert(circuit_id.to_string(), traits);
+            }
+            Err(_) => error!("Event filter lock was poisoned, circuit not remembered"),
+        }
+    }
+
+    /// Returns the decision of the first rule matching the event's circuit, or the default
+    /// action if none does.
+    pub fn decide(&self, event: &AdminServiceEvent) -> FilterDecision {
+        let proposal = proposal_of(event);
+        let member_node_ids = proposal
+            .circuit
+            .members
+            .iter()
+            .map(|member| member.node_id.as_str())
+            .collect::<Vec<_>>();
+        self.decide_for(
+            &proposal.circuit.circuit_management_type,
+            &to_hex(&proposal.requester),
+            &member_node_ids,
+        )
+    }
+
+    /// Returns the export sink the current rules route the messages about the circuit to, or
+    /// `None` if they are written to every sink, as they are for unknown circuits.
+    pub fn route_of(&self, circuit_id: &str) -> Option<String> {
+        let traits = match self.circuits.read() {
+            Ok(circuits) => circuits.get(circuit_id).cloned()?,
+            Err(_) => {
+                error!("Event filter lock was poisoned, message not routed");
+                return None;
+            }
+        };
+        let member_node_ids = traits
+            .member_node_ids
+            .iter()
+            .map(String::as_str)
+            .collect::<Vec<_>>();
+        match self.decide_for(&traits.management_type, &traits.requester, &member_node_ids) {
+            FilterDecision::Route(sink) => Some(sink),
+            FilterDecision::Keep | FilterDecision::Drop => None,
+        }
+    }
+
+    fn decide_for(
+        &self,
+        management_type: &str,
+        requester: &str,
+        member_node_ids: &[&str],
+    ) -> FilterDecision {
+        let rules = self.rules();
+        let rule = rules
+            .iter()
+            .find(|rule| rule.matches(management_type, requester, member_node_ids));
+        match rule {
+            Some(rule) => match (rule.action(), rule.sink()) {
+                (FilterAction::Keep, _) => FilterDecision::Keep,
+                (FilterAction::Drop, _) => FilterDecision::Drop,
+                (FilterAction::Route, Some(sink)) => FilterDecision::Route(sink.to_string()),
+                // rules are checked when loaded, so a route always names its sink
+                (FilterAction::Route, None) => FilterDecision::Keep,
+            },
+            None if self.default_action == FilterAction::Drop => FilterDecision::Drop,
+            None => FilterDecision::Keep,
+        }
+    }
+}
+
+/// Returns the proposal an admin event is about
+pub(super) fn proposal_of(event: &AdminServiceEvent) -> &CircuitProposal {
+    match event {
+        AdminServiceEvent::ProposalSubmitted(proposal)
+        | AdminServiceEvent::CircuitReady(proposal) => proposal,
+        AdminServiceEvent::ProposalVote((proposal, _))
+        | AdminServiceEvent::ProposalAccepted((proposal, _))
+        | AdminServiceEvent::ProposalRejected((proposal, _)) => proposal,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filter(rules: &str) -> EventFilter {
+        EventFilter {
+            default_action: FilterAction::Keep,
+            rules: Arc::new(RwLock::new(serde_yaml::from_str(rules).unwrap())),
+            circuits: Arc::new(RwLock::new(HashMap::new())),
+        }
+    }
+
+    fn circuit(id: &str, members: &[&str]) -> Value {
+        json!({
+            "id": id,
+            "circuit_management_type": "gameroom",
+            "members": members,
+        })
+    }
+
+    #[test]
+    fn route_of_follows_the_rules_matching_the_circuit() {
+        let filter = filter("- action: route\n  member_node_ids: [partner]\n  sink: archive");
+        filter.seed(&[
+            circuit("01234-abcde", &["node-a", "partner"]),
+            circuit("56789-fghij", &["node-a", "node-b"]),
+        ]);
+
+        assert_eq!(filter.route_of("01234-abcde"), Some("archive".to_string()));
+        assert_eq!(filter.route_of("56789-fghij"), None);
+        assert_eq!(filter.route_of("unknown"), None);
+    }
+
+    #[test]
+    fn route_of_follows_replaced_rules() {
+        let filter = filter("- action: route\n  member_node_ids: [partner]\n  sink: archive");
+        filter.seed(&[circuit("01234-abcde", &["node-a", "partner"])]);
+
+        filter.set_rules(
+            serde_yaml::from_str("- action: keep\n  member_node_ids: [partner]").unwrap(),
+        );
+        assert_eq!(filter.route_of("01234-abcde"), None);
+
+        filter.set_rules(
+            serde_yaml::from_str(
+                "- action: route\n  circuit_management_types: [gameroom]\n  sink: games",
+            )
+            .unwrap(),
+        );
+        assert_eq!(filter.route_of("01234-abcde"), Some("games".to_string()));
+    }
+
+    #[test]
+    fn seeded_circuits_have_no_requester() {
+        let filter = filter("- action: route\n  requesters: [\"02ab\"]\n  sink: archive");
+        filter.seed(&[circuit("01234-abcde", &["node-a"])]);
+
+        assert_eq!(filter.route_of("01234-abcde"), None);
+    }
+}
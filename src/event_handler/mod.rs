@@ -16,30 +16,53 @@
  * -----------------------------------------------------------------------------
  */
 
+mod connection_status;
+mod contracts;
+mod dedup;
+mod decoder;
 mod error;
-pub use error::EventHandlerError;
+mod failed_events;
+mod filter;
+mod roster;
+pub use connection_status::{ConnectionInfo, ConnectionState, ConnectionStatus};
+pub use contracts::{ContractInventory, DeployedContract};
+pub use decoder::PayloadDecoders;
+pub use error::{BatchSubmitError, EventHandlerError};
+pub use failed_events::{FailedEvent, FailedEvents};
+pub use filter::EventFilter;
+pub use roster::{CircuitService, ServiceRoster};
 pub mod sabre;
 mod state_delta;
 
 use std::fmt::Write;
-use std::time::{SystemTime, Duration};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
 
 use splinter::{
     admin::messages::{
-        AdminServiceEvent, CircuitProposal, CreateCircuit, SplinterNode, SplinterService,
+        AdminServiceEvent, CircuitProposal, CreateCircuit, SplinterNode, SplinterService, Vote,
     },
     events::{Igniter, WebSocketClient, WebSocketError, WsResponse},
 };
 use state_delta::SabreProcessor;
 
 use crate::application_metadata::ApplicationMetadata;
-
+use crate::authorization::TokenProvider;
+use crate::broadcast::Broadcaster;
+use crate::clock::Clock;
+use crate::key_registry::KeyRegistry;
+use crate::metrics::Metrics;
+use crate::publisher::Publisher;
+use crate::signer::Signer;
+
+use self::dedup::EventDeduplicator;
+use self::filter::FilterDecision;
 use self::sabre::setup_tp;
 use db_models::models::{NewConsortiumProposal, NewConsortiumMember, Consortium, NewConsortiumService, NewProposalVoteRecord};
 use crate::config::EventListenerConfig;
-use kafka::producer::{Producer, RequiredAcks, Record};
 use crate::proto::pubsub::{Message, Message_MessageType, ProposalSubmit, ProposalVote, ProposalAccept, ProposalReject, ProposalReady};
 use protobuf::Message as Msg;
+use serde_json::Value;
 
 /// default value if the client should attempt to reconnet if ws connection is lost
 const RECONNECT: bool = true;
@@ -50,47 +73,248 @@ const RECONNECT_LIMIT: u64 = 10;
 /// default timeout in seconds if no message is received from server
 const CONNECTION_TIMEOUT: u64 = 60;
 
-pub fn run(
+/// Everything needed to handle admin events, shared by every websocket the handler opens
+#[derive(Clone)]
+struct HandlerContext {
     config: EventListenerConfig,
     node_id: String,
-    private_key: String,
+    signer: Option<Arc<dyn Signer>>,
+    token_provider: Option<TokenProvider>,
+    publisher: Publisher,
+    connection_status: ConnectionStatus,
+    deduplicator: EventDeduplicator,
+    filter: EventFilter,
+    metrics: Metrics,
+    clock: Arc<dyn Clock>,
+    broadcaster: Broadcaster,
+    failed_events: FailedEvents,
+    decoders: PayloadDecoders,
+    roster: ServiceRoster,
+    contracts: ContractInventory,
+    keys: KeyRegistry,
+}
+
+/// Re-exports admin events on request, such as those that failed to be exported.
+#[derive(Clone)]
+pub struct EventReprocessor {
+    context: HandlerContext,
+    igniter: Igniter,
+}
+
+impl EventReprocessor {
+    /// Returns the admin events that failed to be exported, oldest first.
+    pub fn failed_events(&self) -> Vec<FailedEvent> {
+        self.context.failed_events.list()
+    }
+
+    /// Exports the failed event with the given id again; it is kept if it fails again.
+    ///
+    /// An event whose message a sink failed to accept has that message published again, and is
+    /// recorded anew if a sink fails again. Returns `Ok(false)` if there is no such failed event.
+    pub fn retry(&self, id: u64) -> Result<bool, EventHandlerError> {
+        let failed = match self.context.failed_events.take(id) {
+            Some(failed) => failed,
+            None => return Ok(false),
+        };
+        let event = failed.event().clone();
+        let result = match failed.message() {
+            Some(message) => self
+                .context
+                .publisher
+                .publish_from_event(failed.circuit_id(), message.clone(), Arc::new(event))
+                .map_err(EventHandlerError::from),
+            None => handle_admin_event(event, &self.context, self.igniter.clone()),
+        };
+        match result {
+            Ok(()) => Ok(true),
+            Err(err) => {
+                self.context.failed_events.retry_failed(failed, &err);
+                Err(err)
+            }
+        }
+    }
+
+    /// Exports a proposal and each of its votes again, as read from splinterd, so that
+    /// consumers can rebuild their record of it.
+    pub fn resync(&self, proposal: CircuitProposal) -> Result<(), EventHandlerError> {
+        let voters = proposal
+            .votes
+            .iter()
+            .map(|vote| vote.public_key.clone())
+            .collect::<Vec<_>>();
+        handle_admin_event(
+            AdminServiceEvent::ProposalSubmitted(proposal.clone()),
+            &self.context,
+            self.igniter.clone(),
+        )?;
+        voters.into_iter().try_for_each(|voter| {
+            handle_admin_event(
+                AdminServiceEvent::ProposalVote((proposal.clone(), voter)),
+                &self.context,
+                self.igniter.clone(),
+            )
+        })
+    }
+}
+
+/// The handles the event handler shares with the rest of the event listener.
+pub struct EventHandlerResources {
+    pub config: EventListenerConfig,
+    /// the identity of the splinterd node the event listener serves
+    pub node_id: String,
+    /// signs the Sabre setup transactions; without one they are not sent
+    pub signer: Option<Arc<dyn Signer>>,
+    pub token_provider: Option<TokenProvider>,
+    pub publisher: Publisher,
+    pub filter: EventFilter,
+    pub metrics: Metrics,
+    pub clock: Arc<dyn Clock>,
+    pub broadcaster: Broadcaster,
+    pub decoders: PayloadDecoders,
+    pub roster: ServiceRoster,
+    pub contracts: ContractInventory,
+    pub keys: KeyRegistry,
+    /// where admin events that fail to be exported are kept, shared with the publisher
+    pub failed_events: FailedEvents,
+}
+
+/// Registers for the admin events of every configured circuit management type.
+///
+/// Returns a handle reporting the state of the resulting websocket connections, and one
+/// re-exporting admin events on request.
+pub fn run(
+    resources: EventHandlerResources,
     igniter: Igniter,
+) -> Result<(ConnectionStatus, EventReprocessor), EventHandlerError> {
+    let config = resources.config;
+    let connection_status = ConnectionStatus::new(resources.clock.clone());
+    let context = HandlerContext {
+        config: config.clone(),
+        node_id: resources.node_id,
+        signer: resources.signer,
+        token_provider: resources.token_provider,
+        publisher: resources.publisher,
+        connection_status: connection_status.clone(),
+        deduplicator: EventDeduplicator::new(Duration::from_secs(
+            config.deployment_config().event_dedup_window_secs(),
+        )),
+        filter: resources.filter,
+        metrics: resources.metrics,
+        clock: resources.clock,
+        broadcaster: resources.broadcaster,
+        failed_events: resources.failed_events,
+        decoders: resources.decoders,
+        roster: resources.roster,
+        contracts: resources.contracts,
+        keys: resources.keys,
+    };
+
+    config
+        .deployment_config()
+        .circuit_management_types()
+        .iter()
+        .try_for_each(|circuit_management_type| {
+            register(circuit_management_type, context.clone(), &igniter)
+        })?;
+
+    Ok((connection_status, EventReprocessor { context, igniter }))
+}
+
+/// Opens an admin websocket that receives the events of every circuit with the given circuit
+/// management type.
+fn register(
+    circuit_management_type: &str,
+    context: HandlerContext,
+    igniter: &Igniter,
 ) -> Result<(), EventHandlerError> {
+    let splinterd_url = context.config.splinterd_url().to_string();
+    let reconnect_limit = context.config.deployment_config().splinterd_reconnect_limit();
+    let connection_status = context.connection_status.clone();
+    let metrics = context.metrics.clone();
+    let failover_context = context.clone();
+    let message_management_type = circuit_management_type.to_string();
 
     // TODO: Resubscribe to all the earlier circuits
     let mut ws = WebSocketClient::new(
-        &format!("{}/ws/admin/register/consortium", config.splinterd_url()),
+        &format!(
+            "{}/ws/admin/register/{}",
+            splinterd_url, circuit_management_type
+        ),
         move |ctx, event| {
-            if let Err(err) = process_admin_event(
-                event,
-                &node_id,
-                &private_key,
-                config.clone(),
-                ctx.igniter(),
-            ) {
+            context
+                .connection_status
+                .event_received(&message_management_type);
+            if !context.deduplicator.first_delivery(&event) {
+                debug!("Discarding redelivered admin event");
+                return WsResponse::Empty;
+            }
+            if context.filter.decide(&event) == FilterDecision::Drop {
+                debug!("Admin event dropped by filter rules");
+                return WsResponse::Empty;
+            }
+
+            if let Err(err) = handle_admin_event(event.clone(), &context, ctx.igniter()) {
                 error!("Failed to process admin event: {}", err);
+                context.failed_events.record(event, &err);
             }
             WsResponse::Empty
         },
     );
 
     ws.set_reconnect(RECONNECT);
-    ws.set_reconnect_limit(RECONNECT_LIMIT);
+    ws.set_reconnect_limit(reconnect_limit);
     ws.set_timeout(CONNECTION_TIMEOUT);
 
+    let open_status = connection_status.clone();
+    let open_management_type = circuit_management_type.to_string();
+    ws.on_open(move |_| {
+        open_status.connected(&open_management_type);
+        WsResponse::Empty
+    });
+
+    let circuit_management_type = circuit_management_type.to_string();
+    connection_status.connecting(&circuit_management_type, &splinterd_url);
     ws.on_error(move |err, ctx| {
-        error!("An error occured while listening for admin events {}", err);
+        error!(
+            "An error occured while listening for {} admin events {}",
+            circuit_management_type, err
+        );
         match err {
             WebSocketError::ParserError { .. } => {
                 debug!("Protocol error, closing connection");
+                metrics.invalid_message();
+                connection_status.down(&circuit_management_type);
                 Ok(())
             }
             WebSocketError::ReconnectError(_) => {
-                debug!("Failed to reconnect. Closing WebSocket.");
+                let endpoints = failover_context.config.splinterd_endpoints();
+                if !endpoints.has_alternatives() {
+                    debug!("Failed to reconnect. Closing WebSocket.");
+                    connection_status.down(&circuit_management_type);
+                    return Ok(());
+                }
+
+                if endpoints.fail_over(&splinterd_url) {
+                    metrics.splinterd_failover();
+                }
+                let next_url = endpoints.active();
+                warn!(
+                    "Failed to reconnect to splinterd at {}, failing over to {}",
+                    splinterd_url, next_url
+                );
+                if let Err(err) = register(
+                    &circuit_management_type,
+                    failover_context.clone(),
+                    &ctx.igniter(),
+                ) {
+                    error!("Unable to register with splinterd at {}: {}", next_url, err);
+                    connection_status.down(&circuit_management_type);
+                }
                 Ok(())
             }
             _ => {
                 debug!("Attempting to restart connection");
+                connection_status.reconnecting(&circuit_management_type);
                 ctx.start_ws()
             }
         }
@@ -99,28 +323,46 @@ pub fn run(
     igniter.start_ws(&ws).map_err(EventHandlerError::from)
 }
 
+/// Exports an admin event, recording metrics and broadcasting its state change if it succeeds.
+///
+/// The event's circuit is remembered by the filter first, so the messages about it are routed by
+/// the current rules.
+fn handle_admin_event(
+    event: AdminServiceEvent,
+    context: &HandlerContext,
+    igniter: Igniter,
+) -> Result<(), EventHandlerError> {
+    context.filter.remember(&event);
+    let event_type = event_type(&event);
+    let state_change = state_change(&event);
+    let start = Instant::now();
+    let result = process_admin_event(event, context, igniter);
+    context
+        .metrics
+        .event_processed(event_type, start.elapsed(), result.is_ok());
+    match &result {
+        Ok(()) => context.broadcaster.broadcast(state_change),
+        Err(EventHandlerError::InvalidMessageError(_)) => context.metrics.invalid_message(),
+        Err(_) => (),
+    }
+    result
+}
+
 fn process_admin_event(
     admin_event: AdminServiceEvent,
-    node_id: &str,
-    private_key: &str,
-    config: EventListenerConfig,
+    context: &HandlerContext,
     igniter: Igniter,
 ) -> Result<(), EventHandlerError> {
-
-    let mut producer =
-        match Producer::from_hosts(vec!(config.deployment_config().kafka_url().to_string()))
-            .with_ack_timeout(Duration::from_secs(5))
-            .with_required_acks(RequiredAcks::One)
-            .create() {
-            Ok(created) => created,
-            Err(err) => return Err(EventHandlerError::InvalidMessageError(err.to_string())),
-    };
-    let topic = config.deployment_config().kafka_topic().to_string();
-
+    let node_id = &context.node_id;
+    let config = context.config.clone();
+    let token_provider = context.token_provider.clone();
+    let publisher = &context.publisher;
     let url = config.splinterd_url();
+    // kept with the messages made from the event, so sink failures can be recorded
+    let source = Arc::new(admin_event.clone());
     match admin_event {
         AdminServiceEvent::ProposalSubmitted(msg_proposal) => {
-            let time = SystemTime::now();
+            let time = context.clock.now();
 
             // convert requester public key to hex
             let requester = to_hex(&msg_proposal.requester);
@@ -150,14 +392,7 @@ fn process_admin_event(
             let mut message = Message::new();
             message.set_field_type(Message_MessageType::PROPOSAL_SUBMIT);
             message.set_message(message_bytes);
-            let to_send_bytes = match message.write_to_bytes() {
-                Ok(bytes) => bytes,
-                Err(err) => return Err(EventHandlerError::InvalidMessageError(err.to_string())),
-            };
-            match producer.send(&Record::from_value(&topic, to_send_bytes)) {
-                Ok(_) => info!("Wrote to Kafka about Proposal Update"),
-                Err(err) => return Err(EventHandlerError::InvalidMessageError(err.to_string())),
-            }
+            publisher.publish_from_event(&msg_proposal.circuit_id, message, source)?;
             Ok(())
         }
         AdminServiceEvent::ProposalVote((msg_proposal, signer_public_key)) => {
@@ -170,18 +405,29 @@ fn process_admin_event(
                     EventHandlerError::InvalidMessageError("Missing vote from signer".to_string())
                 })?;
             let proposal_id: i64 = 1234;
-            let time = SystemTime::now();
+            let time = context.clock.now();
             let vote = NewProposalVoteRecord {
                 proposal_id,
                 voter_public_key: to_hex(&signer_public_key),
                 voter_node_id: vote.voter_node_id.to_string(),
-                vote: "Accept".to_string(),
+                vote: format!("{:?}", vote.vote),
                 created_time: time,
             };
+            let remaining_votes = remaining_votes(&msg_proposal);
             let mut proposal_vote = ProposalVote::new();
             proposal_vote.set_voter(vote.voter_public_key.clone());
             proposal_vote.set_voter_node_id(vote.voter_node_id.clone());
             proposal_vote.set_circuit_id(msg_proposal.circuit_id.clone());
+            proposal_vote.set_voter_public_key(signer_public_key.clone());
+            proposal_vote.set_circuit_hash(circuit_hash(&msg_proposal)?);
+            proposal_vote.set_voter_name(voter_name(
+                &context.keys,
+                &msg_proposal.circuit_id,
+                &vote.voter_public_key,
+            ));
+            proposal_vote.set_vote(vote.vote.clone());
+            proposal_vote.set_remaining_votes(remaining_votes);
+            proposal_vote.set_status(proposal_status(&msg_proposal, remaining_votes).to_string());
             let message_bytes = match proposal_vote.write_to_bytes() {
                 Ok(bytes) => bytes,
                 Err(err) => return Err(EventHandlerError::InvalidMessageError(err.to_string())),
@@ -189,19 +435,12 @@ fn process_admin_event(
             let mut message = Message::new();
             message.set_field_type(Message_MessageType::PROPOSAL_VOTE);
             message.set_message(message_bytes);
-            let to_send_bytes = match message.write_to_bytes() {
-                Ok(bytes) => bytes,
-                Err(err) => return Err(EventHandlerError::InvalidMessageError(err.to_string())),
-            };
-            match producer.send(&Record::from_value(&topic, to_send_bytes)) {
-                Ok(_) => info!("Wrote to Kafka about Proposal Update"),
-                Err(err) => return Err(EventHandlerError::InvalidMessageError(err.to_string())),
-            }
+            publisher.publish_from_event(&msg_proposal.circuit_id, message, source)?;
             Ok(())
         }
         AdminServiceEvent::ProposalAccepted((msg_proposal, signer_public_key)) => {
 //            let proposal = get_pending_proposal_with_circuit_id(&pool, &msg_proposal.circuit_id)?;
-            let time = SystemTime::now();
+            let time = context.clock.now();
             let vote = msg_proposal
                 .votes
                 .iter()
@@ -222,6 +461,13 @@ fn process_admin_event(
             proposal_accept.set_voter(vote.voter_public_key.clone());
             proposal_accept.set_voter_node_id(vote.voter_node_id.clone());
             proposal_accept.set_circuit_id(msg_proposal.circuit_id.clone());
+            proposal_accept.set_voter_public_key(signer_public_key.clone());
+            proposal_accept.set_circuit_hash(circuit_hash(&msg_proposal)?);
+            proposal_accept.set_voter_name(voter_name(
+                &context.keys,
+                &msg_proposal.circuit_id,
+                &vote.voter_public_key,
+            ));
             let message_bytes = match proposal_accept.write_to_bytes() {
                 Ok(bytes) => bytes,
                 Err(err) => return Err(EventHandlerError::InvalidMessageError(err.to_string())),
@@ -229,20 +475,13 @@ fn process_admin_event(
             let mut message = Message::new();
             message.set_field_type(Message_MessageType::PROPOSAL_ACCEPT);
             message.set_message(message_bytes);
-            let to_send_bytes = match message.write_to_bytes() {
-                Ok(bytes) => bytes,
-                Err(err) => return Err(EventHandlerError::InvalidMessageError(err.to_string())),
-            };
-            match producer.send(&Record::from_value(&topic, to_send_bytes)) {
-                Ok(_) => info!("Wrote to Kafka about Proposal Update"),
-                Err(err) => return Err(EventHandlerError::InvalidMessageError(err.to_string())),
-            }
+            publisher.publish_from_event(&msg_proposal.circuit_id, message, source)?;
             Ok(())
         }
         AdminServiceEvent::ProposalRejected((msg_proposal, signer_public_key)) => {
 //            let proposal = get_pending_proposal_with_circuit_id(&pool, &msg_proposal.circuit_id)?;
             let proposal_id: i64 = 1234;
-            let time = SystemTime::now();
+            let time = context.clock.now();
             let vote = msg_proposal
                 .votes
                 .iter()
@@ -262,6 +501,13 @@ fn process_admin_event(
             proposal_reject.set_voter(vote.voter_public_key.clone());
             proposal_reject.set_voter_node_id(vote.voter_node_id.clone());
             proposal_reject.set_circuit_id(msg_proposal.circuit_id.clone());
+            proposal_reject.set_voter_public_key(signer_public_key.clone());
+            proposal_reject.set_circuit_hash(circuit_hash(&msg_proposal)?);
+            proposal_reject.set_voter_name(voter_name(
+                &context.keys,
+                &msg_proposal.circuit_id,
+                &vote.voter_public_key,
+            ));
             let message_bytes = match proposal_reject.write_to_bytes() {
                 Ok(bytes) => bytes,
                 Err(err) => return Err(EventHandlerError::InvalidMessageError(err.to_string())),
@@ -269,14 +515,7 @@ fn process_admin_event(
             let mut message = Message::new();
             message.set_field_type(Message_MessageType::PROPOSAL_REJECT);
             message.set_message(message_bytes);
-            let to_send_bytes = match message.write_to_bytes() {
-                Ok(bytes) => bytes,
-                Err(err) => return Err(EventHandlerError::InvalidMessageError(err.to_string())),
-            };
-            match producer.send(&Record::from_value(&topic, to_send_bytes)) {
-                Ok(_) => info!("Wrote to Kafka about Proposal Update"),
-                Err(err) => return Err(EventHandlerError::InvalidMessageError(err.to_string())),
-            }
+            publisher.publish_from_event(&msg_proposal.circuit_id, message, source)?;
             Ok(())
         }
         AdminServiceEvent::CircuitReady(msg_proposal) => {
@@ -309,8 +548,11 @@ fn process_admin_event(
                     )))
                 }
             };
+            context
+                .roster
+                .add(&msg_proposal.circuit_id, &service_id, &scabbard_admin_keys);
 
-            let time = SystemTime::now();
+            let time = context.clock.now();
             let requester = to_hex(&msg_proposal.requester);
             let proposal = parse_proposal(&msg_proposal, time, requester.clone());
             let mut proposal_ready = ProposalReady::new();
@@ -324,20 +566,14 @@ fn process_admin_event(
             let mut message = Message::new();
             message.set_field_type(Message_MessageType::PROPOSAL_READY);
             message.set_message(message_bytes);
-            let to_send_bytes = match message.write_to_bytes() {
-                Ok(bytes) => bytes,
-                Err(err) => return Err(EventHandlerError::InvalidMessageError(err.to_string())),
-            };
-            match producer.send(&Record::from_value(&topic, to_send_bytes)) {
-                Ok(_) => info!("Wrote to Kafka about Proposal Update"),
-                Err(err) => return Err(EventHandlerError::InvalidMessageError(err.to_string())),
-            }
+            publisher.publish_from_event(&msg_proposal.circuit_id, message, source)?;
 
             let processor = SabreProcessor::new(
                 &msg_proposal.circuit_id,
+                &service_id,
                 &proposal.requester_node_id,
                 &proposal.requester,
-                config.clone(),
+                context,
             );
 
             let mut xo_ws = WebSocketClient::new(
@@ -354,16 +590,27 @@ fn process_admin_event(
             );
 
             let url_to_string = url.to_string();
-            let private_key_to_string = private_key.to_string();
+            let signer = context.signer.clone();
             xo_ws.on_open(move |ctx| {
                 debug!("Starting State Delta Export");
+                let signer = match &signer {
+                    Some(signer) => signer,
+                    None => {
+                        warn!(
+                            "No signer is configured, not setting up Sabre on circuit {}",
+                            msg_proposal.circuit_id
+                        );
+                        return WsResponse::Empty;
+                    }
+                };
                 let future = match setup_tp(
-                    &private_key_to_string,
+                    signer.as_ref(),
                     scabbard_admin_keys.clone(),
                     &url_to_string,
                     &msg_proposal.circuit_id.clone(),
                     &service_id.clone(),
                     config.clone(),
+                    token_provider.clone(),
                 ) {
                     Ok(f) => f,
                     Err(err) => {
@@ -409,6 +656,127 @@ fn process_admin_event(
     }
 }
 
+/// Names the type of an admin event, for metrics
+fn event_type(event: &AdminServiceEvent) -> &'static str {
+    match event {
+        AdminServiceEvent::ProposalSubmitted(_) => "proposal_submitted",
+        AdminServiceEvent::ProposalVote(_) => "proposal_vote",
+        AdminServiceEvent::ProposalAccepted(_) => "proposal_accepted",
+        AdminServiceEvent::ProposalRejected(_) => "proposal_rejected",
+        AdminServiceEvent::CircuitReady(_) => "circuit_ready",
+    }
+}
+
+/// Describes the change an admin event makes to its proposal, for clients of the REST API
+fn state_change(event: &AdminServiceEvent) -> Value {
+    match event {
+        AdminServiceEvent::ProposalSubmitted(proposal) => json!({
+            "type": event_type(event),
+            "circuit_id": proposal.circuit_id,
+            "requester_node_id": proposal.requester_node_id,
+            "status": "Pending",
+        }),
+        AdminServiceEvent::ProposalVote((proposal, signer_public_key)) => {
+            let remaining_votes = remaining_votes(proposal);
+            let vote = proposal
+                .votes
+                .iter()
+                .find(|vote| &vote.public_key == signer_public_key)
+                .map(|vote| format!("{:?}", vote.vote));
+            json!({
+                "type": event_type(event),
+                "circuit_id": proposal.circuit_id,
+                "voter": to_hex(signer_public_key),
+                "vote": vote,
+                "remaining_votes": remaining_votes,
+                "status": proposal_status(proposal, remaining_votes),
+            })
+        }
+        AdminServiceEvent::ProposalAccepted((proposal, _)) => json!({
+            "type": event_type(event),
+            "circuit_id": proposal.circuit_id,
+            "status": "Accepted",
+        }),
+        AdminServiceEvent::ProposalRejected((proposal, _)) => json!({
+            "type": event_type(event),
+            "circuit_id": proposal.circuit_id,
+            "status": "Rejected",
+        }),
+        AdminServiceEvent::CircuitReady(proposal) => json!({
+            "type": event_type(event),
+            "circuit_id": proposal.circuit_id,
+            "status": "Ready",
+        }),
+    }
+}
+
+/// Returns the number of member nodes that have yet to vote on the proposal.
+///
+/// The requester's node approves the proposal by submitting it, so every other member has to
+/// vote.
+fn remaining_votes(proposal: &CircuitProposal) -> u32 {
+    count_remaining_votes(
+        proposal
+            .circuit
+            .members
+            .iter()
+            .map(|member| member.node_id.as_str()),
+        &proposal.requester_node_id,
+        proposal.votes.len(),
+    )
+}
+
+fn count_remaining_votes<'a, I>(
+    member_node_ids: I,
+    requester_node_id: &str,
+    votes_cast: usize,
+) -> u32
+where
+    I: Iterator<Item = &'a str>,
+{
+    let voters = member_node_ids
+        .filter(|node_id| *node_id != requester_node_id)
+        .count();
+    voters.saturating_sub(votes_cast) as u32
+}
+
+/// Returns the hash of the proposed circuit, which votes are signed over, as raw bytes.
+fn circuit_hash(proposal: &CircuitProposal) -> Result<Vec<u8>, EventHandlerError> {
+    from_hex(&proposal.circuit_hash).map_err(|err| {
+        EventHandlerError::InvalidMessageError(format!("invalid circuit hash: {}", err))
+    })
+}
+
+/// Returns the name registered for a voter's public key, or an empty name, logging votes signed
+/// with a key the key registry does not accept.
+fn voter_name(keys: &KeyRegistry, circuit_id: &str, voter_public_key: &str) -> String {
+    if let Err(reason) = keys.check_voter(voter_public_key) {
+        warn!(
+            "Vote on proposal {} by an unaccepted key: {}",
+            circuit_id, reason
+        );
+    }
+    keys.name(voter_public_key).unwrap_or_default()
+}
+
+/// Computes the status of a proposal from the votes cast so far; a single rejection rejects it.
+fn proposal_status(proposal: &CircuitProposal, remaining_votes: u32) -> &'static str {
+    status_from_votes(
+        proposal.votes.iter().any(|vote| vote.vote == Vote::Reject),
+        remaining_votes,
+    )
+}
+
+fn status_from_votes(rejected: bool, remaining_votes: u32) -> &'static str {
+    if rejected {
+        "Rejected"
+    } else if remaining_votes == 0 {
+        "Accepted"
+    } else {
+        "Pending"
+    }
+}
+
 fn parse_proposal(
     proposal: &CircuitProposal,
     timestamp: SystemTime,
@@ -502,3 +870,48 @@ pub fn to_hex(bytes: &[u8]) -> String {
 
     buf
 }
+
+/// Decodes a hex string, such as a public key or hash reported by splinterd.
+pub fn from_hex(hex: &str) -> Result<Vec<u8>, String> {
+    if hex.len() % 2 != 0 || !hex.is_ascii() {
+        return Err(format!("invalid hex string: {}", hex));
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16))
+        .collect::<Result<Vec<u8>, _>>()
+        .map_err(|err| format!("invalid hex string {}: {}", hex, err))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MEMBERS: [&str; 3] = ["node-a", "node-b", "node-c"];
+
+    fn remaining(requester_node_id: &str, votes_cast: usize) -> u32 {
+        count_remaining_votes(MEMBERS.iter().cloned(), requester_node_id, votes_cast)
+    }
+
+    #[test]
+    fn every_member_but_the_requester_votes() {
+        assert_eq!(remaining("node-a", 0), 2);
+        assert_eq!(remaining("node-a", 1), 1);
+        assert_eq!(remaining("node-a", 2), 0);
+    }
+
+    #[test]
+    fn remaining_votes_never_go_below_zero() {
+        assert_eq!(remaining("node-a", 3), 0);
+        // a requester outside the circuit leaves every member to vote
+        assert_eq!(remaining("node-z", 0), 3);
+    }
+
+    #[test]
+    fn a_single_rejection_rejects_the_proposal() {
+        assert_eq!(status_from_votes(true, 2), "Rejected");
+        assert_eq!(status_from_votes(true, 0), "Rejected");
+        assert_eq!(status_from_votes(false, 0), "Accepted");
+        assert_eq!(status_from_votes(false, 1), "Pending");
+    }
+}
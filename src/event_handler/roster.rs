@@ -0,0 +1,190 @@
+/*
+ * Copyright 2019 Walmart Inc.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * -----------------------------------------------------------------------------
+ */
+
+//! The scabbard services this node runs, learned from the circuits it joins.
+
+use std::collections::BTreeMap;
+use std::sync::{Arc, RwLock};
+
+use serde_json::Value;
+
+/// The scabbard service of this node on a circuit
+#[derive(Debug, Clone, Serialize)]
+pub struct CircuitService {
+    circuit_id: String,
+    service_id: String,
+    /// public keys allowed to administer Sabre on the service, as hex
+    admin_keys: Vec<String>,
+}
+
+impl CircuitService {
+    pub fn circuit_id(&self) -> &str {
+        &self.circuit_id
+    }
+
+    pub fn service_id(&self) -> &str {
+        &self.service_id
+    }
+
+    pub fn admin_keys(&self) -> &[String] {
+        &self.admin_keys
+    }
+}
+
+/// The scabbard services of this node, by circuit id.
+///
+/// The services of the circuits that exist at startup are added from splinterd, then a service is
+/// added when its circuit is ready. Clones share the same services.
+#[derive(Clone, Default)]
+pub struct ServiceRoster {
+    services: Arc<RwLock<BTreeMap<String, CircuitService>>>,
+}
+
+impl ServiceRoster {
+    pub fn add(&self, circuit_id: &str, service_id: &str, admin_keys: &[String]) {
+        match self.services.write() {
+            Ok(mut services) => {
+                services.insert(
+                    circuit_id.to_string(),
+                    CircuitService {
+                        circuit_id: circuit_id.to_string(),
+                        service_id: service_id.to_string(),
+                        admin_keys: admin_keys.to_vec(),
+                    },
+                );
+            }
+            Err(_) => error!("Service roster lock was poisoned"),
+        }
+    }
+
+    /// Adds this node's service on each of the circuits listed by splinterd's /admin/circuits.
+    pub fn seed(&self, circuits: &[Value], node_id: &str) {
+        for circuit in circuits {
+            let circuit_id = match circuit["id"].as_str() {
+                Some(circuit_id) => circuit_id,
+                None => continue,
+            };
+            match service_of(circuit, node_id) {
+                Some((service_id, admin_keys)) => self.add(circuit_id, service_id, &admin_keys),
+                None => debug!(
+                    "Circuit {} does not have any services for this node: {}",
+                    circuit_id, node_id
+                ),
+            }
+        }
+    }
+
+    /// Returns this node's service on the given circuit, if it is known.
+    pub fn get(&self, circuit_id: &str) -> Option<CircuitService> {
+        match self.services.read() {
+            Ok(services) => services.get(circuit_id).cloned(),
+            Err(_) => {
+                error!("Service roster lock was poisoned");
+                None
+            }
+        }
+    }
+
+    /// Returns every known service, ordered by circuit id.
+    pub fn list(&self) -> Vec<CircuitService> {
+        match self.services.read() {
+            Ok(services) => services.values().cloned().collect(),
+            Err(_) => {
+                error!("Service roster lock was poisoned");
+                Vec::new()
+            }
+        }
+    }
+}
+
+/// Returns the id and the admin keys of the node's service on a circuit listed by splinterd.
+///
+/// Scabbard is given its admin keys as the JSON list in its admin_keys argument; splinterd lists
+/// a service's arguments either as an object or as key and value pairs.
+fn service_of<'a>(circuit: &'a Value, node_id: &str) -> Option<(&'a str, Vec<String>)> {
+    let service = circuit["roster"].as_array()?.iter().find(|service| {
+        service["allowed_nodes"]
+            .as_array()
+            .map_or(false, |nodes| nodes.iter().any(|node| node == node_id))
+    })?;
+    let service_id = service["service_id"].as_str()?;
+    let admin_keys = match &service["arguments"] {
+        Value::Object(arguments) => arguments.get("admin_keys").and_then(Value::as_str),
+        Value::Array(arguments) => arguments
+            .iter()
+            .find(|argument| argument[0] == "admin_keys")
+            .and_then(|argument| argument[1].as_str()),
+        _ => None,
+    }
+    .and_then(|admin_keys| serde_json::from_str(admin_keys).ok())
+    .unwrap_or_default();
+    Some((service_id, admin_keys))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn seed_adds_the_service_of_this_node() {
+        let circuits = vec![
+            json!({
+                "id": "01234-abcde",
+                "roster": [
+                    {
+                        "service_id": "gr00",
+                        "allowed_nodes": ["node-a"],
+                        "arguments": { "admin_keys": "[\"02ab\"]" },
+                    },
+                    {
+                        "service_id": "gr01",
+                        "allowed_nodes": ["node-b"],
+                        "arguments": [["admin_keys", "[\"03cd\"]"]],
+                    },
+                ],
+            }),
+            json!({
+                "id": "56789-fghij",
+                "roster": [{ "service_id": "gr00", "allowed_nodes": ["node-a"] }],
+            }),
+        ];
+        let roster = ServiceRoster::default();
+        roster.seed(&circuits, "node-b");
+
+        let services = roster.list();
+        assert_eq!(services.len(), 1);
+        assert_eq!(services[0].circuit_id(), "01234-abcde");
+        assert_eq!(services[0].service_id(), "gr01");
+        assert_eq!(services[0].admin_keys(), ["03cd".to_string()]);
+    }
+
+    #[test]
+    fn seed_keeps_a_service_without_admin_keys() {
+        let roster = ServiceRoster::default();
+        roster.seed(
+            &[json!({
+                "id": "56789-fghij",
+                "roster": [{ "service_id": "gr00", "allowed_nodes": ["node-a"] }],
+            })],
+            "node-a",
+        );
+
+        let service = roster.get("56789-fghij").unwrap();
+        assert_eq!(service.service_id(), "gr00");
+        assert!(service.admin_keys().is_empty());
+    }
+}
@@ -21,66 +21,74 @@
 use std::fs::File;
 use std::io::{BufReader, Read};
 use std::path::Path;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
 use crypto::digest::Digest;
 use crypto::sha2::Sha512;
-use futures::future::{self, Future};
+use futures::future::{self, Either, Future, Loop};
 use futures::stream::Stream;
+use hyper::client::HttpConnector;
+use hyper::header::AUTHORIZATION;
 use hyper::{Body, Client, Request, StatusCode};
 use protobuf::Message;
 use sabre_sdk::protocol::payload::{
     Action, CreateContractActionBuilder, CreateContractRegistryActionBuilder,
     CreateNamespaceRegistryActionBuilder, CreateNamespaceRegistryPermissionActionBuilder,
-    SabrePayloadBuilder,
+    SabrePayloadBuilder, UpdateNamespaceRegistryOwnersActionBuilder,
 };
 use sabre_sdk::protocol::ADMINISTRATORS_SETTING_ADDRESS;
 use sabre_sdk::protos::IntoBytes as SabreIntoBytes;
 use sawtooth_sdk::messages::batch::{Batch, BatchHeader, BatchList};
 use sawtooth_sdk::messages::transaction::{Transaction, TransactionHeader};
-use sawtooth_sdk::signing::secp256k1::Secp256k1PrivateKey;
-use sawtooth_sdk::signing::{create_context, CryptoFactory, Signer};
+use tokio::timer::Delay;
 
-use super::EventHandlerError;
+use super::{BatchSubmitError, EventHandlerError};
+use crate::authorization::TokenProvider;
 use crate::config::{EventListenerConfig, DeploymentConfig};
+use crate::signer::Signer;
 
 /// The Sawtooth Sabre transaction family name (sabre)
-const SABRE_FAMILY_NAME: &str = "sabre";
+pub const SABRE_FAMILY_NAME: &str = "sabre";
 /// The Sawtooth Sabre transaction family version (0.4)
-const SABRE_FAMILY_VERSION: &str = "0.4";
+pub const SABRE_FAMILY_VERSION: &str = "0.4";
 
 /// The namespace registry prefix for global state (00ec00)
-const NAMESPACE_REGISTRY_PREFIX: &str = "00ec00";
+pub(super) const NAMESPACE_REGISTRY_PREFIX: &str = "00ec00";
 
 /// The contract registry prefix for global state (00ec01)
-const CONTRACT_REGISTRY_PREFIX: &str = "00ec01";
+pub(super) const CONTRACT_REGISTRY_PREFIX: &str = "00ec01";
 
 /// The contract prefix for global state (00ec02)
-const CONTRACT_PREFIX: &str = "00ec02";
+pub(super) const CONTRACT_PREFIX: &str = "00ec02";
 
 /// The smart permission prefix for global state (00ec03)
 const SMART_PERMISSION_PREFIX: &str = "00ec03";
 
 const PIKE_PREFIX: &str = "cad11d";
 
+/// Caps the exponential backoff between batch submission retries at 2^10 times the base delay
+const MAX_BACKOFF_EXPONENT: u32 = 10;
+
+/// A Sabre payload and the state addresses its transaction reads and writes
+pub struct SabreAction {
+    pub payload: Vec<u8>,
+    pub addresses: Vec<String>,
+}
+
 /// Create and submit the Sabre transactions to setup the XO smart contract.
 pub fn setup_tp(
-    private_key: &str,
+    signer: &dyn Signer,
     scabbard_admin_keys: Vec<String>,
     splinterd_url: &str,
     circuit_id: &str,
     service_id: &str,
     config: EventListenerConfig,
+    token_provider: Option<TokenProvider>,
 ) -> Result<Box<dyn Future<Item = (), Error = ()> + Send + 'static>, EventHandlerError> {
-    let context = create_context("secp256k1")?;
-    let factory = CryptoFactory::new(&*context);
-    let private_key = Secp256k1PrivateKey::from_hex(private_key)?;
-    let signer = factory.new_signer(&private_key);
-
     // The node with the first key in the list of scabbard admins is responsible for setting up xo
-    let public_key = signer.get_public_key()?.as_hex();
+    let public_key = signer.public_key();
     let is_submitter = match scabbard_admin_keys.get(0) {
-        Some(submitting_key) => &public_key == submitting_key,
+        Some(submitting_key) => public_key == submitting_key.as_str(),
         None => false,
     };
     if !is_submitter {
@@ -90,70 +98,126 @@ pub fn setup_tp(
     let tp_name = config.deployment_config().tp_name();
     // Create the transactions and batch them
     let txns = vec![
-        create_contract_registry_txn(scabbard_admin_keys.clone(), &signer, tp_name)?,
-        upload_contract_txn(&signer, config.deployment_config())?,
-        create_tp_namespace_registry_txn(scabbard_admin_keys.clone(), &signer, config.deployment_config())?,
-        tp_namespace_permissions_txn(&signer, config.deployment_config())?,
-        create_pike_namespace_registry_txn(scabbard_admin_keys, &signer)?,
-        pike_namespace_permissions_txn(&signer, config.deployment_config())?,
+        create_contract_registry_txn(scabbard_admin_keys.clone(), signer, tp_name)?,
+        upload_contract_txn(signer, config.deployment_config())?,
+        create_tp_namespace_registry_txn(scabbard_admin_keys.clone(), signer, config.deployment_config())?,
+        tp_namespace_permissions_txn(signer, config.deployment_config())?,
+        create_pike_namespace_registry_txn(scabbard_admin_keys, signer)?,
+        pike_namespace_permissions_txn(signer, config.deployment_config())?,
     ];
-    let batch = create_batch(txns, &signer)?;
+    let batch = create_batch(txns, signer)?;
     let batch_list = create_batch_list_from_one(batch);
     let payload = batch_list.write_to_bytes().map_err(|err| {
         EventHandlerError::SawtoothError(format!("failed to serialize batch list: {}", err))
     })?;
     // Submit the batch to the scabbard service
-    let body_stream = futures::stream::once::<_, std::io::Error>(Ok(payload));
-    let req = Request::builder()
-        .uri(format!(
-            "{}/scabbard/{}/{}/batches",
-            splinterd_url, circuit_id, service_id
-        ))
-        .method("POST")
-        .body(Body::wrap_stream(body_stream))
-        .map_err(|err| EventHandlerError::BatchSubmitError(format!("{}", err)))?;
+    let url = format!(
+        "{}/scabbard/{}/{}/batches",
+        splinterd_url, circuit_id, service_id
+    );
+    let retry_limit = config.deployment_config().submit_retry_limit();
+    let backoff = Duration::from_millis(config.deployment_config().submit_retry_backoff_millis());
+
+    Ok(Box::new(
+        submit_batch_list_with_retry(url, payload, token_provider, retry_limit, backoff)
+            .map_err(|err| error!("Failed to submit batch to scabbard: {}", err)),
+    ))
+}
 
+/// Submits a serialized batch list, retrying with exponential backoff while the failure is
+/// retriable.
+///
+/// Resubmitting is safe because the batch, and therefore its header signature, is identical on
+/// every attempt, so a retry can never apply the same transactions twice.
+fn submit_batch_list_with_retry(
+    url: String,
+    payload: Vec<u8>,
+    token_provider: Option<TokenProvider>,
+    retry_limit: u32,
+    backoff: Duration,
+) -> impl Future<Item = (), Error = BatchSubmitError> {
     let client = Client::new();
 
-    Ok(Box::new(
-        client
-            .request(req)
-            .then(|response| match response {
-                Ok(res) => {
-                    let status = res.status();
-                    let body = res
-                        .into_body()
-                        .concat2()
-                        .wait()
-                        .map_err(|err| {
-                            EventHandlerError::BatchSubmitError(format!(
-                                "The client encountered an error {}",
-                                err
-                            ))
-                        })?
-                        .to_vec();
-
-                    match status {
-                        StatusCode::ACCEPTED => Ok(()),
-                        _ => Err(EventHandlerError::BatchSubmitError(format!(
-                            "The server returned an error. Status: {}, {}",
-                            status,
-                            String::from_utf8(body)?
-                        ))),
-                    }
+    future::loop_fn(0, move |attempt: u32| {
+        submit_batch_list(&client, &url, payload.clone(), token_provider.as_ref()).then(move |result| match result {
+            Ok(()) => Either::A(future::ok(Loop::Break(()))),
+            Err(err) => {
+                if !err.is_retriable() || attempt >= retry_limit {
+                    return Either::A(future::err(err));
                 }
-                Err(err) => Err(EventHandlerError::BatchSubmitError(format!(
-                    "The client encountered an error {}",
+
+                let delay = backoff * 2u32.pow(attempt.min(MAX_BACKOFF_EXPONENT));
+                warn!(
+                    "Batch submission attempt {} failed, retrying in {:?}: {}",
+                    attempt + 1,
+                    delay,
                     err
-                ))),
-            })
-            .map_err(|_| ()),
-    ))
+                );
+                Either::B(
+                    Delay::new(Instant::now() + delay)
+                        .map_err(|err| BatchSubmitError::ConnectionError(err.to_string()))
+                        .map(move |_| Loop::Continue(attempt + 1)),
+                )
+            }
+        })
+    })
+}
+
+/// Makes a single attempt at submitting a serialized batch list.
+fn submit_batch_list(
+    client: &Client<HttpConnector>,
+    url: &str,
+    payload: Vec<u8>,
+    token_provider: Option<&TokenProvider>,
+) -> Box<dyn Future<Item = (), Error = BatchSubmitError> + Send> {
+    let mut builder = Request::builder();
+    builder.uri(url).method("POST");
+    if let Some(token_provider) = token_provider {
+        match token_provider.authorization_header() {
+            Ok(authorization) => {
+                builder.header(AUTHORIZATION, authorization);
+            }
+            Err(err) => {
+                return Box::new(future::err(BatchSubmitError::InvalidRequest(
+                    err.to_string(),
+                )))
+            }
+        }
+    }
+    let req = match builder.body(Body::from(payload)) {
+        Ok(req) => req,
+        Err(err) => {
+            return Box::new(future::err(BatchSubmitError::InvalidRequest(
+                err.to_string(),
+            )))
+        }
+    };
+
+    Box::new(client.request(req).then(|response| match response {
+        Ok(res) => {
+            let status = res.status();
+            let body = res
+                .into_body()
+                .concat2()
+                .wait()
+                .map_err(|err| BatchSubmitError::ConnectionError(err.to_string()))?
+                .to_vec();
+
+            match status {
+                StatusCode::ACCEPTED => Ok(()),
+                _ => Err(BatchSubmitError::ResponseError {
+                    status,
+                    message: String::from_utf8_lossy(&body).into_owned(),
+                }),
+            }
+        }
+        Err(err) => Err(BatchSubmitError::ConnectionError(err.to_string())),
+    }))
 }
 
 fn create_contract_registry_txn(
     owners: Vec<String>,
-    signer: &Signer,
+    signer: &dyn Signer,
     tp_name: &str,
 ) -> Result<Transaction, EventHandlerError> {
     let action = CreateContractRegistryActionBuilder::new()
@@ -172,7 +236,7 @@ fn create_contract_registry_txn(
     create_txn(addresses, payload, signer)
 }
 
-fn upload_contract_txn(signer: &Signer, deploymentConfig: &DeploymentConfig) -> Result<Transaction, EventHandlerError> {
+fn upload_contract_txn(signer: &dyn Signer, deploymentConfig: &DeploymentConfig) -> Result<Transaction, EventHandlerError> {
     let contract_path = Path::new(deploymentConfig.tp_path());
     let contract_file = File::open(contract_path).map_err(|err| {
         EventHandlerError::SabreError(format!("Failed to load contract: {}", err))
@@ -188,11 +252,31 @@ fn upload_contract_txn(signer: &Signer, deploymentConfig: &DeploymentConfig) ->
         PIKE_PREFIX.into(),
         deploymentConfig.tp_prefix().to_string(),
     ];
+    let action = create_contract_action(
+        deploymentConfig.tp_name(),
+        deploymentConfig.tp_version(),
+        action_addresses.clone(),
+        action_addresses,
+        contract,
+    )?;
+
+    create_txn(action.addresses, action.payload, signer)
+}
+
+/// Builds the action uploading a compiled contract, which may read and write the state under
+/// the given inputs and outputs.
+pub fn create_contract_action(
+    name: &str,
+    version: &str,
+    inputs: Vec<String>,
+    outputs: Vec<String>,
+    contract: Vec<u8>,
+) -> Result<SabreAction, EventHandlerError> {
     let action = CreateContractActionBuilder::new()
-        .with_name(deploymentConfig.tp_name().to_string())
-        .with_version(deploymentConfig.tp_version().to_string())
-        .with_inputs(action_addresses.clone())
-        .with_outputs(action_addresses)
+        .with_name(name.to_string())
+        .with_version(version.to_string())
+        .with_inputs(inputs)
+        .with_outputs(outputs)
         .with_contract(contract)
         .build()?;
     let payload = SabrePayloadBuilder::new()
@@ -200,98 +284,122 @@ fn upload_contract_txn(signer: &Signer, deploymentConfig: &DeploymentConfig) ->
         .build()?
         .into_bytes()?;
     let addresses = vec![
-        compute_contract_registry_address(deploymentConfig.tp_name()),
-        compute_contract_address(deploymentConfig.tp_name(), deploymentConfig.tp_version()),
+        compute_contract_registry_address(name),
+        compute_contract_address(name, version),
     ];
 
-    create_txn(addresses, payload, signer)
+    Ok(SabreAction { payload, addresses })
 }
 
 fn create_tp_namespace_registry_txn(
     owners: Vec<String>,
-    signer: &Signer,
+    signer: &dyn Signer,
     deploymentConfig: &DeploymentConfig,
 ) -> Result<Transaction, EventHandlerError> {
-    let action = CreateNamespaceRegistryActionBuilder::new()
-        .with_namespace(deploymentConfig.tp_prefix().to_string())
-        .with_owners(owners)
-        .build()?;
-    let payload = SabrePayloadBuilder::new()
-        .with_action(Action::CreateNamespaceRegistry(action))
-        .build()?
-        .into_bytes()?;
-    let addresses = vec![
-        compute_namespace_registry_address(deploymentConfig.tp_prefix())?,
-        ADMINISTRATORS_SETTING_ADDRESS.into(),
-    ];
+    let action = create_namespace_registry_action(deploymentConfig.tp_prefix(), owners)?;
 
-    create_txn(addresses, payload, signer)
+    create_txn(action.addresses, action.payload, signer)
 }
 
-fn tp_namespace_permissions_txn(signer: &Signer, deploymentConfig: &DeploymentConfig) -> Result<Transaction, EventHandlerError> {
-    let action = CreateNamespaceRegistryPermissionActionBuilder::new()
-        .with_namespace(deploymentConfig.tp_prefix().to_string())
-        .with_contract_name(deploymentConfig.tp_name().to_string())
-        .with_read(true)
-        .with_write(true)
+fn tp_namespace_permissions_txn(signer: &dyn Signer, deploymentConfig: &DeploymentConfig) -> Result<Transaction, EventHandlerError> {
+    let action = namespace_permission_action(
+        deploymentConfig.tp_prefix(),
+        deploymentConfig.tp_name(),
+        true,
+        true,
+    )?;
+
+    create_txn(action.addresses, action.payload, signer)
+}
+
+fn create_pike_namespace_registry_txn(
+    owners: Vec<String>,
+    signer: &dyn Signer,
+) -> Result<Transaction, EventHandlerError> {
+    let action = create_namespace_registry_action(PIKE_PREFIX, owners)?;
+
+    create_txn(action.addresses, action.payload, signer)
+}
+
+fn pike_namespace_permissions_txn(signer: &dyn Signer, deploymentConfig: &DeploymentConfig) -> Result<Transaction, EventHandlerError> {
+    let action = namespace_permission_action(PIKE_PREFIX, deploymentConfig.tp_name(), true, false)?;
+
+    create_txn(action.addresses, action.payload, signer)
+}
+
+/// Builds the action creating the registry of a state namespace, owned by the given public keys.
+pub fn create_namespace_registry_action(
+    namespace: &str,
+    owners: Vec<String>,
+) -> Result<SabreAction, EventHandlerError> {
+    let action = CreateNamespaceRegistryActionBuilder::new()
+        .with_namespace(namespace.to_string())
+        .with_owners(owners)
         .build()?;
     let payload = SabrePayloadBuilder::new()
-        .with_action(Action::CreateNamespaceRegistryPermission(action))
+        .with_action(Action::CreateNamespaceRegistry(action))
         .build()?
         .into_bytes()?;
     let addresses = vec![
-        compute_namespace_registry_address(deploymentConfig.tp_prefix())?,
+        compute_namespace_registry_address(namespace)?,
         ADMINISTRATORS_SETTING_ADDRESS.into(),
     ];
 
-    create_txn(addresses, payload, signer)
+    Ok(SabreAction { payload, addresses })
 }
 
-fn create_pike_namespace_registry_txn(
+/// Builds the action replacing the owners of a namespace registry.
+pub fn update_namespace_registry_owners_action(
+    namespace: &str,
     owners: Vec<String>,
-    signer: &Signer,
-) -> Result<Transaction, EventHandlerError> {
-    let action = CreateNamespaceRegistryActionBuilder::new()
-        .with_namespace(PIKE_PREFIX.into())
+) -> Result<SabreAction, EventHandlerError> {
+    let action = UpdateNamespaceRegistryOwnersActionBuilder::new()
+        .with_namespace(namespace.to_string())
         .with_owners(owners)
         .build()?;
     let payload = SabrePayloadBuilder::new()
-        .with_action(Action::CreateNamespaceRegistry(action))
+        .with_action(Action::UpdateNamespaceRegistryOwners(action))
         .build()?
         .into_bytes()?;
     let addresses = vec![
-        compute_namespace_registry_address(PIKE_PREFIX)?,
+        compute_namespace_registry_address(namespace)?,
         ADMINISTRATORS_SETTING_ADDRESS.into(),
     ];
 
-    create_txn(addresses, payload, signer)
+    Ok(SabreAction { payload, addresses })
 }
 
-fn pike_namespace_permissions_txn(signer: &Signer, deploymentConfig: &DeploymentConfig) -> Result<Transaction, EventHandlerError> {
+/// Builds the action granting a contract read and/or write access to a namespace.
+pub fn namespace_permission_action(
+    namespace: &str,
+    contract_name: &str,
+    read: bool,
+    write: bool,
+) -> Result<SabreAction, EventHandlerError> {
     let action = CreateNamespaceRegistryPermissionActionBuilder::new()
-        .with_namespace(PIKE_PREFIX.into())
-        .with_contract_name(deploymentConfig.tp_name().to_string())
-        .with_read(true)
-        .with_write(false)
+        .with_namespace(namespace.to_string())
+        .with_contract_name(contract_name.to_string())
+        .with_read(read)
+        .with_write(write)
         .build()?;
     let payload = SabrePayloadBuilder::new()
         .with_action(Action::CreateNamespaceRegistryPermission(action))
         .build()?
         .into_bytes()?;
     let addresses = vec![
-        compute_namespace_registry_address(PIKE_PREFIX)?,
+        compute_namespace_registry_address(namespace)?,
         ADMINISTRATORS_SETTING_ADDRESS.into(),
     ];
 
-    create_txn(addresses, payload, signer)
+    Ok(SabreAction { payload, addresses })
 }
 
-fn create_txn(
+pub fn create_txn(
     addresses: Vec<String>,
     payload: Vec<u8>,
-    signer: &Signer,
+    signer: &dyn Signer,
 ) -> Result<Transaction, EventHandlerError> {
-    let public_key = signer.get_public_key()?.as_hex();
+    let public_key = signer.public_key().to_string();
 
     let mut txn = Transaction::new();
     let mut txn_header = TransactionHeader::new();
@@ -332,8 +440,8 @@ fn create_txn(
 /// * `txns` - list of Transactions
 /// * `signer` - the signer to be used to sign the transaction
 /// * `public_key` - the public key associated with the signer
-pub fn create_batch(txns: Vec<Transaction>, signer: &Signer) -> Result<Batch, EventHandlerError> {
-    let public_key = signer.get_public_key()?.as_hex();
+pub fn create_batch(txns: Vec<Transaction>, signer: &dyn Signer) -> Result<Batch, EventHandlerError> {
+    let public_key = signer.public_key().to_string();
 
     let mut batch = Batch::new();
     let mut batch_header = BatchHeader::new();
@@ -16,30 +16,47 @@
  * -----------------------------------------------------------------------------
  */
 
-use std::{error::Error, fmt, time::SystemTime};
+use std::{error::Error, fmt};
 use splinter::service::scabbard::StateChangeEvent;
 use crate::config::EventListenerConfig;
-use kafka::producer::{Producer, RequiredAcks, Record};
 use crate::proto::pubsub::{Message, Message_MessageType, CircuitCreated, CircuitPayload};
+use crate::publisher::Publisher;
+use super::contracts::ContractInventory;
+use super::decoder::PayloadDecoders;
+use super::sabre::CONTRACT_REGISTRY_PREFIX;
+use super::HandlerContext;
 use protobuf::Message as Msg;
-use std::time::Duration;
 
 pub struct SabreProcessor {
     circuit_id: String,
+    service_id: String,
     node_id: String,
     requester: String,
     contract_address: String,
     config: EventListenerConfig,
+    publisher: Publisher,
+    decoders: PayloadDecoders,
+    contracts: ContractInventory,
 }
 
 impl SabreProcessor {
-    pub fn new(circuit_id: &str, node_id: &str, requester: &str, config: EventListenerConfig) -> Self {
+    pub(super) fn new(
+        circuit_id: &str,
+        service_id: &str,
+        node_id: &str,
+        requester: &str,
+        context: &HandlerContext,
+    ) -> Self {
         SabreProcessor {
             circuit_id: circuit_id.into(),
+            service_id: service_id.into(),
             node_id: node_id.to_string(),
             requester: requester.to_string(),
-            contract_address: config.deployment_config().tp_prefix().to_string(),
-            config,
+            contract_address: context.config.deployment_config().tp_prefix().to_string(),
+            config: context.config.clone(),
+            publisher: context.publisher.clone(),
+            decoders: context.decoders.clone(),
+            contracts: context.contracts.clone(),
         }
     }
 
@@ -53,21 +70,11 @@ impl SabreProcessor {
     }
 
     fn handle_state_change(&self, change: &StateChangeEvent) -> Result<(), StateDeltaError> {
-
-        let mut producer =
-            match Producer::from_hosts(vec!(self.config.deployment_config().kafka_url().to_string()))
-                .with_ack_timeout(Duration::from_secs(5))
-                .with_required_acks(RequiredAcks::One)
-                .create() {
-                Ok(created) => created,
-                Err(err) => return Err(StateDeltaError::SDError(err.to_string())),
-            };
         debug!("Received state change: {}", change);
-        let topic = self.config.deployment_config().kafka_topic().to_string();
+        self.track_contracts(change);
         match change {
             StateChangeEvent::Set { key, .. } if key == &self.contract_address => {
                 debug!("TP contract created successfully");
-                let time = SystemTime::now();
                 let mut circuit_created = CircuitCreated::new();
                 circuit_created.set_requester(self.requester.clone());
                 circuit_created.set_requester_node_id(self.node_id.clone());
@@ -79,43 +86,16 @@ impl SabreProcessor {
                 let mut message = Message::new();
                 message.set_field_type(Message_MessageType::CIRCUIT_CREATED);
                 message.set_message(message_bytes);
-                let to_send_bytes = match message.write_to_bytes() {
-                    Ok(bytes) => bytes,
-                    Err(err) => return Err(StateDeltaError::SDError(err.to_string())),
-                };
-                match producer.send(&Record::from_value(&topic, to_send_bytes)) {
-                    Ok(_) => info!("Wrote to Kafka about Circuit Created"),
-                    Err(err) => return Err(StateDeltaError::SDError(err.to_string())),
-                }
+                self.publisher
+                    .publish(&self.circuit_id, message)
+                    .map_err(|err| StateDeltaError::SDError(err.to_string()))?;
                 Ok(())
             }
-            StateChangeEvent::Set { key, value } if &key[..6] == self.config.deployment_config().tp_prefix() => {
-                let time = SystemTime::now();
-                let mut circuit_payload = CircuitPayload::new();
-                circuit_payload.set_requester(self.requester.clone());
-                circuit_payload.set_requester_node_id(self.node_id.clone());
-                circuit_payload.set_circuit_id(self.circuit_id.clone());
-                circuit_payload.set_data(value.to_vec());
-                let message_bytes = match circuit_payload.write_to_bytes() {
-                    Ok(bytes) => bytes,
-                    Err(err) => return Err(StateDeltaError::SDError(err.to_string())),
-                };
-                let mut message = Message::new();
-                message.set_field_type(Message_MessageType::CIRCUIT_PAYLOAD);
-                message.set_message(message_bytes);
-                let to_send_bytes = match message.write_to_bytes() {
-                    Ok(bytes) => bytes,
-                    Err(err) => return Err(StateDeltaError::SDError(err.to_string())),
-                };
-                match producer.send(&Record::from_value(&topic, to_send_bytes)) {
-                    Ok(_) => info!("Wrote to Kafka about Circuit Payload"),
-                    Err(err) => return Err(StateDeltaError::SDError(err.to_string())),
-                }
-                Ok(())
+            StateChangeEvent::Set { key, value } if self.is_exported(key) => {
+                self.publish_payload(key, value, false)
             }
-            StateChangeEvent::Delete { .. } => {
-                debug!("Delete state skipping...");
-                Ok(())
+            StateChangeEvent::Delete { key } if self.is_exported(key) => {
+                self.publish_payload(key, &[], true)
             }
             _ => {
                 debug!("Unrecognized state change skipping...");
@@ -123,6 +103,66 @@ impl SabreProcessor {
             }
         }
     }
+
+    /// Keeps the contract inventory of the circuit up to date, whether or not the contract
+    /// registry is exported.
+    fn track_contracts(&self, change: &StateChangeEvent) {
+        match change {
+            StateChangeEvent::Set { key, value } if key.starts_with(CONTRACT_REGISTRY_PREFIX) => {
+                if let Err(err) = self.contracts.update(&self.circuit_id, key, value) {
+                    warn!("Unable to decode the contract registry at {}: {}", key, err);
+                }
+            }
+            StateChangeEvent::Delete { key } if key.starts_with(CONTRACT_REGISTRY_PREFIX) => {
+                self.contracts.remove(&self.circuit_id, key)
+            }
+            _ => (),
+        }
+    }
+
+    /// Returns true if changes to the state at the address are exported: those under tp_prefix
+    /// or any of state_export_prefixes.
+    fn is_exported(&self, address: &str) -> bool {
+        let deployment_config = self.config.deployment_config();
+        address.starts_with(deployment_config.tp_prefix())
+            || deployment_config
+                .state_export_prefixes()
+                .iter()
+                .any(|prefix| address.starts_with(prefix.as_str()))
+    }
+
+    fn publish_payload(
+        &self,
+        address: &str,
+        data: &[u8],
+        deleted: bool,
+    ) -> Result<(), StateDeltaError> {
+        let mut circuit_payload = CircuitPayload::new();
+        circuit_payload.set_requester(self.requester.clone());
+        circuit_payload.set_requester_node_id(self.node_id.clone());
+        circuit_payload.set_circuit_id(self.circuit_id.clone());
+        circuit_payload.set_data(data.to_vec());
+        circuit_payload.set_service_id(self.service_id.clone());
+        circuit_payload.set_address(address.to_string());
+        circuit_payload.set_deleted(deleted);
+        if !deleted {
+            match self.decoders.decode(address, data) {
+                Some(Ok(decoded)) => circuit_payload.set_decoded(decoded.to_string()),
+                Some(Err(err)) => warn!("Unable to decode the state at {}: {}", address, err),
+                None => (),
+            }
+        }
+        let message_bytes = match circuit_payload.write_to_bytes() {
+            Ok(bytes) => bytes,
+            Err(err) => return Err(StateDeltaError::SDError(err.to_string())),
+        };
+        let mut message = Message::new();
+        message.set_field_type(Message_MessageType::CIRCUIT_PAYLOAD);
+        message.set_message(message_bytes);
+        self.publisher
+            .publish(&self.circuit_id, message)
+            .map_err(|err| StateDeltaError::SDError(err.to_string()))
+    }
 }
 
 #[derive(Debug)]
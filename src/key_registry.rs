@@ -0,0 +1,228 @@
+/*
+ * Copyright 2019 Walmart Inc.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * -----------------------------------------------------------------------------
+ */
+
+//! The public keys of the users who sign circuit management payloads, with the name, node and
+//! organization of each.
+
+use std::collections::BTreeMap;
+use std::sync::{Arc, RwLock};
+
+use crate::clock::Clock;
+use crate::config::RegisteredKeyConfig;
+use crate::event_handler::from_hex;
+
+/// length of a compressed secp256k1 public key, in bytes
+const PUBLIC_KEY_LENGTH: usize = 33;
+
+/// A user's public key and who it belongs to
+#[derive(Debug, Clone, Serialize)]
+pub struct RegisteredKey {
+    /// secp256k1 public key, as lowercase hex
+    public_key: String,
+    name: String,
+    node_id: Option<String>,
+    organization: Option<String>,
+    active: bool,
+    /// Time the key was registered, in seconds since the epoch
+    registered_at: u64,
+    /// Time the key was deactivated, in seconds since the epoch
+    #[serde(skip_serializing_if = "Option::is_none")]
+    deactivated_at: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    deactivation_reason: Option<String>,
+}
+
+impl RegisteredKey {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn node_id(&self) -> Option<&str> {
+        self.node_id.as_ref().map(String::as_str)
+    }
+
+    pub fn organization(&self) -> Option<&str> {
+        self.organization.as_ref().map(String::as_str)
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.active
+    }
+}
+
+/// The registered keys, by public key.
+///
+/// Keys registered, updated or deactivated at runtime keep their state until the event listener
+/// restarts. Deactivated keys are kept so that votes they signed can still be attributed. Clones
+/// share the same keys.
+#[derive(Clone)]
+pub struct KeyRegistry {
+    keys: Arc<RwLock<BTreeMap<String, RegisteredKey>>>,
+    clock: Arc<dyn Clock>,
+}
+
+impl KeyRegistry {
+    pub fn new(registered_keys: &[RegisteredKeyConfig], clock: Arc<dyn Clock>) -> Self {
+        let registered_at = clock.now_secs();
+        let keys = registered_keys
+            .iter()
+            .map(|key| {
+                let public_key = key.public_key().to_lowercase();
+                (
+                    public_key.clone(),
+                    RegisteredKey {
+                        public_key,
+                        name: key.name().to_string(),
+                        node_id: key.node_id().map(ToOwned::to_owned),
+                        organization: key.organization().map(ToOwned::to_owned),
+                        active: true,
+                        registered_at,
+                        deactivated_at: None,
+                        deactivation_reason: None,
+                    },
+                )
+            })
+            .collect();
+        KeyRegistry {
+            keys: Arc::new(RwLock::new(keys)),
+            clock,
+        }
+    }
+
+    /// Returns the key, active or not.
+    pub fn get(&self, public_key: &str) -> Option<RegisteredKey> {
+        self.keys
+            .read()
+            .ok()
+            .and_then(|keys| keys.get(&public_key.to_lowercase()).cloned())
+    }
+
+    /// Returns every key, ordered by public key.
+    pub fn list(&self) -> Vec<RegisteredKey> {
+        self.keys
+            .read()
+            .map(|keys| keys.values().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// Returns the name of the key's owner, if the key is registered.
+    pub fn name(&self, public_key: &str) -> Option<String> {
+        self.get(public_key).map(|key| key.name)
+    }
+
+    /// Registers a key; returns false if it is already registered, even if deactivated, so that
+    /// a compromised key cannot be registered again.
+    pub fn register(
+        &self,
+        public_key: &str,
+        name: &str,
+        node_id: Option<&str>,
+        organization: Option<&str>,
+    ) -> Result<bool, String> {
+        let public_key = public_key.to_lowercase();
+        let mut keys = self
+            .keys
+            .write()
+            .map_err(|_| "Key registry lock was poisoned".to_string())?;
+        if keys.contains_key(&public_key) {
+            return Ok(false);
+        }
+        keys.insert(
+            public_key.clone(),
+            RegisteredKey {
+                public_key,
+                name: name.to_string(),
+                node_id: node_id.map(ToOwned::to_owned),
+                organization: organization.map(ToOwned::to_owned),
+                active: true,
+                registered_at: self.clock.now_secs(),
+                deactivated_at: None,
+                deactivation_reason: None,
+            },
+        );
+        Ok(true)
+    }
+
+    /// Replaces the name, node and organization of a key; returns the updated key, or `None` if
+    /// it is not registered.
+    pub fn update(
+        &self,
+        public_key: &str,
+        name: &str,
+        node_id: Option<&str>,
+        organization: Option<&str>,
+    ) -> Result<Option<RegisteredKey>, String> {
+        let mut keys = self
+            .keys
+            .write()
+            .map_err(|_| "Key registry lock was poisoned".to_string())?;
+        Ok(keys.get_mut(&public_key.to_lowercase()).map(|key| {
+            key.name = name.to_string();
+            key.node_id = node_id.map(ToOwned::to_owned);
+            key.organization = organization.map(ToOwned::to_owned);
+            key.clone()
+        }))
+    }
+
+    /// Deactivates a key, such as one that was compromised; returns the deactivated key, or
+    /// `None` if it is not registered. A key that is already deactivated keeps its reason.
+    pub fn deactivate(
+        &self,
+        public_key: &str,
+        reason: Option<&str>,
+    ) -> Result<Option<RegisteredKey>, String> {
+        let mut keys = self
+            .keys
+            .write()
+            .map_err(|_| "Key registry lock was poisoned".to_string())?;
+        Ok(keys.get_mut(&public_key.to_lowercase()).map(|key| {
+            if key.active {
+                key.active = false;
+                key.deactivated_at = Some(self.clock.now_secs());
+                key.deactivation_reason = reason.map(ToOwned::to_owned);
+            }
+            key.clone()
+        }))
+    }
+
+    /// Checks that a vote signed with the key is accepted: deactivated keys are always refused,
+    /// and once any key is registered, so are unregistered ones.
+    pub fn check_voter(&self, public_key: &str) -> Result<(), String> {
+        let keys = self
+            .keys
+            .read()
+            .map_err(|_| "Key registry lock was poisoned".to_string())?;
+        match keys.get(&public_key.to_lowercase()) {
+            Some(key) if key.active => Ok(()),
+            Some(_) => Err(format!("the key {} was deactivated", public_key)),
+            None if keys.is_empty() => Ok(()),
+            None => Err(format!("the key {} is not registered", public_key)),
+        }
+    }
+}
+
+/// Checks that a public key is a hex encoded, compressed secp256k1 public key.
+pub fn validate_public_key(public_key: &str) -> Result<(), String> {
+    let bytes = from_hex(public_key)?;
+    if bytes.len() != PUBLIC_KEY_LENGTH || (bytes[0] != 0x02 && bytes[0] != 0x03) {
+        return Err(format!(
+            "not a compressed secp256k1 public key: {}",
+            public_key
+        ));
+    }
+    Ok(())
+}
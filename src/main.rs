@@ -27,20 +27,41 @@ extern crate splinter;
 extern crate kafka;
 
 mod application_metadata;
+mod authorization;
+mod broadcast;
+mod clock;
 mod event_handler;
 mod config;
 mod error;
+mod key_registry;
+mod metrics;
 mod proto;
+mod publisher;
+mod rest_api;
+mod signer;
+mod validation;
 
+use std::sync::{mpsc, Arc};
 use std::thread;
+use std::time::{Duration, Instant};
 
 use flexi_logger::{style, DeferredNow, LogSpecBuilder, Logger};
 use log::Record;
-use sawtooth_sdk::signing::create_context;
 use splinter::events::Reactor;
 
-use crate::config::{get_node, DataReaderConfigBuilder};
-use crate::error::EventListenerError;
+use crate::authorization::TokenProvider;
+use crate::broadcast::Broadcaster;
+use crate::clock::{Clock, SystemClock};
+use crate::config::{get_circuits, get_node, DataReaderConfigBuilder};
+use crate::error::{ConfigurationError, EventListenerError};
+use crate::event_handler::{
+    ContractInventory, EventFilter, EventHandlerResources, FailedEvents, PayloadDecoders,
+    ServiceRoster,
+};
+use crate::key_registry::KeyRegistry;
+use crate::metrics::Metrics;
+use crate::publisher::Publisher;
+use crate::rest_api::RestApiResources;
 
 const APP_NAME: &str = env!("CARGO_PKG_NAME");
 const VERSION: &str = env!("CARGO_PKG_VERSION");
@@ -71,7 +92,15 @@ fn run() -> Result<(), EventListenerError> {
         (about: "Daemon Package for Listening to events on Splinter")
         (@arg verbose: -v +multiple "Log verbosely")
         (@arg config: -c --config +takes_value "config file to be used for the event listener service")
-        (@arg splinterd_url: --("splinterd-url") +takes_value "connection endpoint to SplinterD rest API")
+        (@arg splinterd_url: --("splinterd-url") +takes_value +multiple +use_delimiter
+            "connection endpoints to SplinterD rest API, in order of preference for failover")
+        (@arg splinterd_token: --("splinterd-token") +takes_value conflicts_with[splinterd_token_file]
+            "bearer token sent with REST requests to SplinterD; the admin websocket stays unauthenticated")
+        (@arg splinterd_token_file: --("splinterd-token-file") +takes_value
+            "file containing the bearer token sent with REST requests to SplinterD; the admin websocket stays unauthenticated")
+        (@arg bind: -b --bind +takes_value "connection endpoint for the event listener rest API")
+        (@arg validate_config: --("validate-config")
+            "validate the configuration and its connections, print a report and exit")
     )
     .get_matches();
 
@@ -91,37 +120,178 @@ fn run() -> Result<(), EventListenerError> {
     Logger::with(log_spec_builder.build())
         .format(log_format)
         .start()?;
-    let config = DataReaderConfigBuilder::default()
-        .with_cli_args(&matches)
-        .build()?;
 
-    // Generate a public/private key pair
-    let context = create_context("secp256k1")?;
-    let private_key = context.new_random_private_key()?;
-    let _public_key = context.get_public_key(&*private_key)?;
+    let token_provider = matches
+        .value_of("splinterd_token")
+        .map(|token| TokenProvider::Static(token.to_string()))
+        .or_else(|| {
+            matches
+                .value_of("splinterd_token_file")
+                .map(|path| TokenProvider::File(path.into()))
+        });
+
+    let builder = DataReaderConfigBuilder::default().with_cli_args(&matches);
+
+    if matches.is_present("validate_config") {
+        let report = validation::validate(builder, token_provider.as_ref());
+        match serde_json::to_string_pretty(&report) {
+            Ok(report_json) => println!("{}", report_json),
+            Err(err) => error!("Unable to serialize validation report: {}", err),
+        }
+        if !report.is_valid() {
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
+    let config = builder.build()?;
+
+    let signer = signer::load(config.deployment_config())?;
+    if config.deployment_config().server_side_signing() && signer.is_none() {
+        return Err(ConfigurationError::MissingValue(
+            "signing_key_file, signing_key_env or remote_signer, required by server_side_signing"
+                .into(),
+        )
+        .into());
+    }
+
+    let metrics = Metrics::default();
 
     // Get splinterd node information
-    let node = get_node(config.splinterd_url())?;
+    let node = get_node(
+        config.splinterd_endpoints(),
+        token_provider.as_ref(),
+        &metrics,
+    )?;
+    // the circuits that were ready before startup, whose events will not be seen again
+    let circuits = get_circuits(
+        config.splinterd_endpoints(),
+        token_provider.as_ref(),
+        &metrics,
+    )?;
+
+    let clock: Arc<dyn Clock> = Arc::new(SystemClock);
+    let failed_events = FailedEvents::new(
+        config.deployment_config().failed_event_history_size(),
+        clock.clone(),
+    );
+    let filter = EventFilter::new(config.deployment_config());
+    filter.seed(&circuits);
+    let publisher = Publisher::start(
+        config.deployment_config(),
+        &filter,
+        failed_events.clone(),
+        clock.clone(),
+    )?;
+    let shutdown_timeout = Duration::from_secs(config.deployment_config().shutdown_timeout_secs());
 
     let reactor = Reactor::new();
 
-    event_handler::run(
-        config,
-        node.identity.clone(),
-        private_key.as_hex(),
+    let broadcaster = Broadcaster::new(config.deployment_config().event_history_size());
+    let roster = ServiceRoster::default();
+    roster.seed(&circuits, &node.identity);
+    let contracts = ContractInventory::default();
+    let keys = KeyRegistry::new(config.deployment_config().registered_keys(), clock.clone());
+
+    let (connection_status, reprocessor) = event_handler::run(
+        EventHandlerResources {
+            config: config.clone(),
+            node_id: node.identity.clone(),
+            signer: signer.clone(),
+            token_provider: token_provider.clone(),
+            publisher: publisher.clone(),
+            filter: filter.clone(),
+            metrics: metrics.clone(),
+            clock: clock.clone(),
+            broadcaster: broadcaster.clone(),
+            decoders: PayloadDecoders::default(),
+            roster: roster.clone(),
+            contracts: contracts.clone(),
+            keys: keys.clone(),
+            failed_events,
+        },
         reactor.igniter(),
     )?;
 
-    if let Err(err) = reactor.shutdown() {
-        error!(
-            "Unable to cleanly shutdown application authorization handler reactor: {}",
-            err
-        );
+    let (rest_api_shutdown_handle, _rest_api_join_handle) = rest_api::run(RestApiResources {
+        config,
+        node_id: node.identity,
+        token_provider,
+        connection_status,
+        filter,
+        metrics,
+        publisher: publisher.clone(),
+        broadcaster,
+        reprocessor,
+        roster,
+        contracts,
+        keys,
+        signer,
+        clock,
+    })?;
+
+    let (shutdown_sender, shutdown_receiver) = mpsc::channel();
+    ctrlc::set_handler(move || {
+        if shutdown_sender.send(()).is_err() {
+            error!("Unable to signal shutdown");
+        }
+    })?;
+    // Block until the daemon is asked to stop
+    let _ = shutdown_receiver.recv();
+    info!("Shutting down, waiting at most {:?}", shutdown_timeout);
+    let deadline = Instant::now() + shutdown_timeout;
+
+    if let Err(err) = rest_api_shutdown_handle.shutdown() {
+        error!("Unable to cleanly shut down REST API server: {}", err);
+    }
+
+    // Close the websockets first so no new messages reach the publisher
+    shutdown_reactor(reactor, time_until(deadline));
+
+    if let Err(err) = publisher.shutdown(time_until(deadline)) {
+        error!("{}", err);
     }
 
     Ok(())
 }
 
+/// Shuts down the reactor, abandoning it if its connections do not close within `timeout`.
+fn shutdown_reactor(reactor: Reactor, timeout: Duration) {
+    let (done_sender, done_receiver) = mpsc::channel();
+    let spawn_result = thread::Builder::new()
+        .name("ReactorShutdown".into())
+        .spawn(move || {
+            if let Err(err) = reactor.shutdown() {
+                error!(
+                    "Unable to cleanly shutdown application authorization handler reactor: {}",
+                    err
+                );
+            }
+            let _ = done_sender.send(());
+        });
+
+    match spawn_result {
+        Ok(_) => {
+            if done_receiver.recv_timeout(timeout).is_err() {
+                warn!(
+                    "Reactor did not shut down within {:?}, abandoning open connections",
+                    timeout
+                );
+            }
+        }
+        Err(err) => error!("Unable to start reactor shutdown: {}", err),
+    }
+}
+
+fn time_until(deadline: Instant) -> Duration {
+    let now = Instant::now();
+    if now < deadline {
+        deadline - now
+    } else {
+        Duration::from_secs(0)
+    }
+}
+
 fn main() {
     if let Err(e) = run() {
         error!("{}", e);
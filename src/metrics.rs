@@ -0,0 +1,280 @@
+/*
+ * Copyright 2019 Walmart Inc.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * -----------------------------------------------------------------------------
+ */
+
+//! Counters describing the event listener, rendered in the Prometheus text exposition format.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::event_handler::ConnectionStatus;
+use crate::publisher::{ExportSinkSummary, PublisherStats};
+
+#[derive(Default)]
+struct EventTypeMetrics {
+    processed: u64,
+    failed: u64,
+    processing_seconds: f64,
+}
+
+#[derive(Default)]
+struct Inner {
+    invalid_messages: AtomicUsize,
+    splinterd_failovers: AtomicUsize,
+    events: Mutex<BTreeMap<&'static str, EventTypeMetrics>>,
+}
+
+/// A shared handle for recording metrics; clones record into the same counters.
+#[derive(Clone, Default)]
+pub struct Metrics {
+    inner: Arc<Inner>,
+}
+
+impl Metrics {
+    /// Records a message that could not be parsed or did not have the expected content.
+    pub fn invalid_message(&self) {
+        self.inner.invalid_messages.fetch_add(1, Ordering::SeqCst);
+    }
+
+    /// Records a switch to another splinterd endpoint after the active one failed.
+    pub fn splinterd_failover(&self) {
+        self.inner
+            .splinterd_failovers
+            .fetch_add(1, Ordering::SeqCst);
+    }
+
+    /// Records an event of the given type having been processed, and whether it succeeded.
+    pub fn event_processed(&self, event_type: &'static str, elapsed: Duration, succeeded: bool) {
+        let mut events = match self.inner.events.lock() {
+            Ok(events) => events,
+            Err(_) => {
+                error!("Metrics lock was poisoned, event not recorded");
+                return;
+            }
+        };
+        let metrics = events
+            .entry(event_type)
+            .or_insert_with(EventTypeMetrics::default);
+        metrics.processed += 1;
+        if !succeeded {
+            metrics.failed += 1;
+        }
+        metrics.processing_seconds +=
+            elapsed.as_secs() as f64 + f64::from(elapsed.subsec_nanos()) / 1_000_000_000.0;
+    }
+
+    /// Renders every metric in the Prometheus text exposition format.
+    pub fn render(
+        &self,
+        connection_status: &ConnectionStatus,
+        publisher_stats: &PublisherStats,
+        export_sinks: &[ExportSinkSummary],
+    ) -> String {
+        let mut out = String::new();
+
+        write_header(
+            &mut out,
+            "event_listener_splinterd_reconnects_total",
+            "counter",
+            "Reconnection attempts of the admin websockets",
+        );
+        for (circuit_management_type, info) in connection_status.snapshot() {
+            writeln!(
+                out,
+                "event_listener_splinterd_reconnects_total{{circuit_management_type=\"{}\"}} {}",
+                circuit_management_type,
+                info.reconnect_count()
+            )
+            .ok();
+        }
+
+        write_header(
+            &mut out,
+            "event_listener_splinterd_failovers_total",
+            "counter",
+            "Switches to another splinterd endpoint after the active one failed",
+        );
+        writeln!(
+            out,
+            "event_listener_splinterd_failovers_total {}",
+            self.inner.splinterd_failovers.load(Ordering::SeqCst)
+        )
+        .ok();
+
+        write_header(
+            &mut out,
+            "event_listener_invalid_messages_total",
+            "counter",
+            "Messages from splinterd that could not be processed as received",
+        );
+        writeln!(
+            out,
+            "event_listener_invalid_messages_total {}",
+            self.inner.invalid_messages.load(Ordering::SeqCst)
+        )
+        .ok();
+
+        if let Ok(events) = self.inner.events.lock() {
+            write_header(
+                &mut out,
+                "event_listener_events_processed_total",
+                "counter",
+                "Admin events processed, by type",
+            );
+            for (event_type, metrics) in events.iter() {
+                writeln!(
+                    out,
+                    "event_listener_events_processed_total{{type=\"{}\"}} {}",
+                    event_type, metrics.processed
+                )
+                .ok();
+            }
+
+            write_header(
+                &mut out,
+                "event_listener_event_processing_failures_total",
+                "counter",
+                "Admin events that failed to be processed, by type",
+            );
+            for (event_type, metrics) in events.iter() {
+                writeln!(
+                    out,
+                    "event_listener_event_processing_failures_total{{type=\"{}\"}} {}",
+                    event_type, metrics.failed
+                )
+                .ok();
+            }
+
+            write_header(
+                &mut out,
+                "event_listener_event_processing_seconds",
+                "summary",
+                "Time spent processing admin events, by type",
+            );
+            for (event_type, metrics) in events.iter() {
+                writeln!(
+                    out,
+                    "event_listener_event_processing_seconds_sum{{type=\"{}\"}} {}",
+                    event_type, metrics.processing_seconds
+                )
+                .ok();
+                writeln!(
+                    out,
+                    "event_listener_event_processing_seconds_count{{type=\"{}\"}} {}",
+                    event_type, metrics.processed
+                )
+                .ok();
+            }
+        }
+
+        let publisher_metrics = [
+            (
+                "event_listener_queue_length",
+                "gauge",
+                "Messages waiting to be written to the export sinks",
+                publisher_stats.queue_length(),
+            ),
+            (
+                "event_listener_published_total",
+                "counter",
+                "Messages written to every export sink",
+                publisher_stats.published(),
+            ),
+            (
+                "event_listener_publish_failures_total",
+                "counter",
+                "Messages an export sink failed to accept",
+                publisher_stats.failed(),
+            ),
+            (
+                "event_listener_dropped_total",
+                "counter",
+                "Messages discarded because the publisher queue was full",
+                publisher_stats.dropped(),
+            ),
+            (
+                "event_listener_backpressure_events_total",
+                "counter",
+                "Times the publisher queue was found full",
+                publisher_stats.backpressure_events(),
+            ),
+        ];
+        for (name, metric_type, help, value) in publisher_metrics.iter() {
+            write_header(&mut out, name, metric_type, help);
+            writeln!(out, "{} {}", name, value).ok();
+        }
+
+        let sink_metrics: [(&str, &str, &str, fn(&ExportSinkSummary) -> f64); 3] = [
+            (
+                "event_listener_sink_messages_exported_total",
+                "counter",
+                "Messages an export sink accepted, by sink",
+                |sink| sink.messages_exported() as f64,
+            ),
+            (
+                "event_listener_sink_messages_failed_total",
+                "counter",
+                "Messages an export sink failed to accept, by sink",
+                |sink| sink.messages_failed() as f64,
+            ),
+            (
+                "event_listener_sink_last_success_timestamp_seconds",
+                "gauge",
+                "Time of the last batch an export sink accepted, by sink",
+                |sink| sink.last_success_at().unwrap_or_default() as f64,
+            ),
+        ];
+        for (name, metric_type, help, value) in sink_metrics.iter() {
+            write_header(&mut out, name, metric_type, help);
+            for sink in export_sinks {
+                writeln!(out, "{}{{sink=\"{}\"}} {}", name, sink.name(), value(sink)).ok();
+            }
+        }
+
+        write_header(
+            &mut out,
+            "event_listener_sink_write_seconds",
+            "summary",
+            "Time spent writing batches to an export sink, by sink",
+        );
+        for sink in export_sinks {
+            writeln!(
+                out,
+                "event_listener_sink_write_seconds_sum{{sink=\"{}\"}} {}",
+                sink.name(),
+                sink.write_seconds()
+            )
+            .ok();
+            writeln!(
+                out,
+                "event_listener_sink_write_seconds_count{{sink=\"{}\"}} {}",
+                sink.name(),
+                sink.runs()
+            )
+            .ok();
+        }
+
+        out
+    }
+}
+
+fn write_header(out: &mut String, name: &str, metric_type: &str, help: &str) {
+    writeln!(out, "# HELP {} {}", name, help).ok();
+    writeln!(out, "# TYPE {} {}", name, metric_type).ok();
+}
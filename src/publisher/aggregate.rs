@@ -0,0 +1,180 @@
+/*
+ * Copyright 2019 Walmart Inc.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * -----------------------------------------------------------------------------
+ */
+
+//! Aggregation-only export, for deployments that may share activity trends but not the
+//! contents of their circuits.
+
+use std::collections::HashMap;
+use std::mem;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use protobuf::{Message as Msg, RepeatedField};
+
+use super::PublisherError;
+use crate::clock::Clock;
+use crate::proto::pubsub::{ActivityCount, ActivitySummary, Message, Message_MessageType};
+
+#[derive(Default)]
+struct Counts {
+    count: u64,
+    bytes: u64,
+}
+
+struct Period {
+    start: SystemTime,
+    counts: HashMap<Message_MessageType, Counts>,
+}
+
+/// Counts messages per type instead of exporting them, and summarizes the counts once per
+/// period.
+pub struct Aggregator {
+    period: Duration,
+    min_group_size: u64,
+    current: Mutex<Period>,
+    clock: Arc<dyn Clock>,
+}
+
+impl Aggregator {
+    pub fn new(period: Duration, min_group_size: u64, clock: Arc<dyn Clock>) -> Self {
+        Aggregator {
+            period,
+            min_group_size,
+            current: Mutex::new(Period {
+                start: clock.now(),
+                counts: HashMap::new(),
+            }),
+            clock,
+        }
+    }
+
+    pub fn period(&self) -> Duration {
+        self.period
+    }
+
+    /// Adds a message to the counts of the current period.
+    pub fn record(&self, message: &Message) {
+        let mut current = match self.current.lock() {
+            Ok(current) => current,
+            Err(_) => {
+                error!("Aggregator lock was poisoned, message not counted");
+                return;
+            }
+        };
+        let counts = current
+            .counts
+            .entry(message.get_field_type())
+            .or_insert_with(Counts::default);
+        counts.count += 1;
+        counts.bytes += message.get_message().len() as u64;
+    }
+
+    /// Closes the current period and returns its summary, wrapped as a message ready to be
+    /// published.
+    ///
+    /// Message types seen fewer times than the minimum group size are left out of the summary.
+    /// Counts are kept per message type across every circuit, not per circuit, so a type only
+    /// seen on one circuit is still reported once it reaches the minimum group size.
+    pub fn flush(&self) -> Result<Message, PublisherError> {
+        let now = self.clock.now();
+        let period = match self.current.lock() {
+            Ok(mut current) => mem::replace(
+                &mut *current,
+                Period {
+                    start: now,
+                    counts: HashMap::new(),
+                },
+            ),
+            Err(_) => {
+                return Err(PublisherError::SerializationError(
+                    "Aggregator lock was poisoned".to_string(),
+                ))
+            }
+        };
+
+        let mut summary = ActivitySummary::new();
+        summary.set_period_start(seconds_since_epoch(period.start));
+        summary.set_period_end(seconds_since_epoch(now));
+        let mut suppressed_groups = 0;
+        let mut activity_counts = Vec::new();
+        for (message_type, counts) in period.counts {
+            if counts.count < self.min_group_size {
+                suppressed_groups += 1;
+                continue;
+            }
+            let mut activity_count = ActivityCount::new();
+            activity_count.set_field_type(message_type);
+            activity_count.set_count(counts.count);
+            activity_count.set_bytes(counts.bytes);
+            activity_counts.push(activity_count);
+        }
+        summary.set_counts(RepeatedField::from_vec(activity_counts));
+        summary.set_suppressed_groups(suppressed_groups);
+
+        let summary_bytes = summary
+            .write_to_bytes()
+            .map_err(|err| PublisherError::SerializationError(err.to_string()))?;
+        let mut message = Message::new();
+        message.set_field_type(Message_MessageType::ACTIVITY_SUMMARY);
+        message.set_message(summary_bytes);
+        Ok(message)
+    }
+}
+
+fn seconds_since_epoch(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::clock::FixedClock;
+
+    fn message(message_type: Message_MessageType, bytes: &[u8]) -> Message {
+        let mut message = Message::new();
+        message.set_field_type(message_type);
+        message.set_message(bytes.to_vec());
+        message
+    }
+
+    #[test]
+    fn flush_summarizes_the_period_read_from_the_clock() {
+        let now = UNIX_EPOCH + Duration::from_secs(1_600_000_000);
+        let aggregator = Aggregator::new(Duration::from_secs(60), 2, Arc::new(FixedClock(now)));
+        aggregator.record(&message(Message_MessageType::PROPOSAL_VOTE, b"abc"));
+        aggregator.record(&message(Message_MessageType::PROPOSAL_VOTE, b"de"));
+        aggregator.record(&message(Message_MessageType::CIRCUIT_CREATED, b"f"));
+
+        let flushed = aggregator.flush().unwrap();
+        assert_eq!(
+            flushed.get_field_type(),
+            Message_MessageType::ACTIVITY_SUMMARY
+        );
+        let summary: ActivitySummary = protobuf::parse_from_bytes(flushed.get_message()).unwrap();
+        assert_eq!(summary.get_period_start(), 1_600_000_000);
+        assert_eq!(summary.get_period_end(), 1_600_000_000);
+        assert_eq!(summary.get_suppressed_groups(), 1);
+        assert_eq!(summary.get_counts().len(), 1);
+        let count = &summary.get_counts()[0];
+        assert_eq!(count.get_field_type(), Message_MessageType::PROPOSAL_VOTE);
+        assert_eq!(count.get_count(), 2);
+        assert_eq!(count.get_bytes(), 5);
+    }
+}
@@ -0,0 +1,379 @@
+/*
+ * Copyright 2019 Walmart Inc.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * -----------------------------------------------------------------------------
+ */
+
+//! Avro encoding of exported messages, with schemas registered in a Confluent-compatible schema
+//! registry.
+
+use std::collections::HashMap;
+
+use hyper::header::CONTENT_TYPE;
+use hyper::{Body, Request};
+use serde_json::Value;
+
+use super::http::BlockingClient;
+use super::json::{to_json, type_name};
+use super::PublisherError;
+use crate::proto::pubsub::{Message, Message_MessageType};
+
+/// namespace of the Avro record schemas
+const NAMESPACE: &str = "event_listener";
+
+/// first byte of the Confluent wire format, followed by the schema id
+const MAGIC_BYTE: u8 = 0;
+
+const REGISTRY_CONTENT_TYPE: &str = "application/vnd.schemaregistry.v1+json";
+
+/// Encodes the JSON records of exported messages with Avro, in the Confluent wire format.
+///
+/// Every message type has its own record schema, registered under the subject
+/// "<topic>-<record name>" the first time a message of that type is written to a topic, so
+/// topics carrying several message types are supported.
+pub struct AvroEncoder {
+    registry_url: String,
+    client: BlockingClient,
+    /// schema ids, by subject
+    schema_ids: HashMap<String, u32>,
+}
+
+impl AvroEncoder {
+    pub fn new(registry_url: &str) -> Result<Self, PublisherError> {
+        Ok(AvroEncoder {
+            registry_url: registry_url.trim_end_matches('/').to_string(),
+            client: BlockingClient::new()?,
+            schema_ids: HashMap::new(),
+        })
+    }
+
+    /// Encodes a message written to the given topic, registering its schema if needed.
+    pub fn encode(&mut self, topic: &str, message: &Message) -> Result<Vec<u8>, PublisherError> {
+        let message_type = message.get_field_type();
+        let schema = schema(message_type);
+        let subject = format!("{}-{}.{}", topic, NAMESPACE, record_name(message_type));
+        let schema_id = match self.schema_ids.get(&subject) {
+            Some(schema_id) => *schema_id,
+            None => {
+                let schema_id = self.register(&subject, &schema)?;
+                self.schema_ids.insert(subject, schema_id);
+                schema_id
+            }
+        };
+
+        let mut record = to_json(message)?;
+        // decoded state has no fixed shape, so it is carried as JSON text
+        if let Some(decoded) = record.get_mut("decoded") {
+            if !decoded.is_null() {
+                *decoded = Value::String(decoded.to_string());
+            }
+        }
+
+        let mut bytes = vec![MAGIC_BYTE];
+        bytes.extend_from_slice(&schema_id.to_be_bytes());
+        encode_value(&schema, &record, &mut bytes).map_err(|err| {
+            PublisherError::SerializationError(format!(
+                "Unable to encode {} as Avro: {}",
+                type_name(message_type),
+                err
+            ))
+        })?;
+        Ok(bytes)
+    }
+
+    /// Registers the schema under the subject and returns its id; registering a schema the
+    /// subject already has returns the existing id.
+    fn register(&mut self, subject: &str, schema: &Value) -> Result<u32, PublisherError> {
+        let request = Request::post(format!(
+            "{}/subjects/{}/versions",
+            self.registry_url, subject
+        ))
+        .header(CONTENT_TYPE, REGISTRY_CONTENT_TYPE)
+        .body(Body::from(
+            json!({ "schema": schema.to_string() }).to_string(),
+        ))
+        .map_err(|err| PublisherError::SinkError(err.to_string()))?;
+        let (status, body) = self
+            .client
+            .send(request)
+            .map_err(|err| PublisherError::SinkError(format!("schema registry: {}", err)))?;
+        if !status.is_success() {
+            return Err(PublisherError::SinkError(format!(
+                "unable to register the schema of {}: {} {}",
+                subject,
+                status,
+                String::from_utf8_lossy(&body)
+            )));
+        }
+        serde_json::from_slice::<Value>(&body)
+            .ok()
+            .and_then(|response| response["id"].as_u64())
+            .map(|id| id as u32)
+            .ok_or_else(|| {
+                PublisherError::SinkError(format!(
+                    "the schema registry returned no id for {}",
+                    subject
+                ))
+            })
+    }
+}
+
+/// Returns the name of the record schema of a message type, such as "ProposalVote".
+fn record_name(message_type: Message_MessageType) -> String {
+    type_name(message_type)
+        .split('_')
+        .map(|word| {
+            let mut chars = word.chars();
+            match chars.next() {
+                Some(first) => first.to_uppercase().chain(chars).collect::<String>(),
+                None => String::new(),
+            }
+        })
+        .collect()
+}
+
+/// Returns the record schema of the JSON records of a message type.
+fn schema(message_type: Message_MessageType) -> Value {
+    let string = |name: &str| json!({ "name": name, "type": "string" });
+    let long = |name: &str| json!({ "name": name, "type": "long" });
+    // added after the first schemas were registered, so it needs a default
+    let voter_name = json!({ "name": "voter_name", "type": "string", "default": "" });
+    let mut fields = match message_type {
+        Message_MessageType::PROPOSAL_SUBMIT
+        | Message_MessageType::PROPOSAL_READY
+        | Message_MessageType::CIRCUIT_CREATED => vec![
+            string("requester"),
+            string("requester_node_id"),
+            string("circuit_id"),
+        ],
+        Message_MessageType::PROPOSAL_VOTE => vec![
+            string("voter"),
+            string("voter_node_id"),
+            string("circuit_id"),
+            string("vote"),
+            long("remaining_votes"),
+            string("status"),
+            string("circuit_hash"),
+            voter_name.clone(),
+        ],
+        Message_MessageType::PROPOSAL_ACCEPT | Message_MessageType::PROPOSAL_REJECT => vec![
+            string("voter"),
+            string("voter_node_id"),
+            string("circuit_id"),
+            string("circuit_hash"),
+            voter_name,
+        ],
+        Message_MessageType::CIRCUIT_PAYLOAD => vec![
+            string("requester"),
+            string("requester_node_id"),
+            string("circuit_id"),
+            string("data"),
+            string("service_id"),
+            string("address"),
+            json!({ "name": "deleted", "type": "boolean" }),
+            json!({ "name": "decoded", "type": ["null", "string"], "default": null }),
+        ],
+        Message_MessageType::ACTIVITY_SUMMARY => vec![
+            long("period_start"),
+            long("period_end"),
+            json!({
+                "name": "counts",
+                "type": {
+                    "type": "array",
+                    "items": {
+                        "type": "record",
+                        "name": "ActivityCount",
+                        "fields": [string("type"), long("count"), long("bytes")],
+                    },
+                },
+            }),
+            long("suppressed_groups"),
+        ],
+        Message_MessageType::TYPE_UNKNOWN => vec![string("data")],
+    };
+    fields.push(json!({ "name": "schema_version", "type": "int" }));
+    fields.push(string("type"));
+    json!({
+        "type": "record",
+        "name": record_name(message_type),
+        "namespace": NAMESPACE,
+        "fields": fields,
+    })
+}
+
+/// Appends the Avro binary encoding of a JSON value, given its schema.
+///
+/// Only the parts of Avro used by the record schemas above are supported.
+fn encode_value(schema: &Value, value: &Value, out: &mut Vec<u8>) -> Result<(), String> {
+    match schema {
+        Value::String(name) => encode_primitive(name, value, out),
+        // a union: the null branch for null values, the other branch for the rest
+        Value::Array(branches) => {
+            let index = branches
+                .iter()
+                .position(|branch| (branch == "null") == value.is_null())
+                .ok_or_else(|| format!("no branch of {} matches {}", schema, value))?;
+            write_long(index as i64, out);
+            encode_value(&branches[index], value, out)
+        }
+        Value::Object(definition) => match definition.get("type").and_then(Value::as_str) {
+            Some("record") => {
+                let fields = definition
+                    .get("fields")
+                    .and_then(Value::as_array)
+                    .ok_or_else(|| format!("record schema without fields: {}", schema))?;
+                for field in fields {
+                    let name = field["name"].as_str().unwrap_or_default();
+                    encode_value(&field["type"], &value[name], out)
+                        .map_err(|err| format!("{}: {}", name, err))?;
+                }
+                Ok(())
+            }
+            Some("array") => {
+                let items = value
+                    .as_array()
+                    .ok_or_else(|| format!("expected an array, found {}", value))?;
+                // a single block holding every item, followed by the empty block
+                if !items.is_empty() {
+                    write_long(items.len() as i64, out);
+                    for item in items {
+                        encode_value(&definition["items"], item, out)?;
+                    }
+                }
+                write_long(0, out);
+                Ok(())
+            }
+            Some(name) => encode_primitive(name, value, out),
+            None => Err(format!("schema without a type: {}", schema)),
+        },
+        _ => Err(format!("invalid schema: {}", schema)),
+    }
+}
+
+fn encode_primitive(name: &str, value: &Value, out: &mut Vec<u8>) -> Result<(), String> {
+    match name {
+        "null" => Ok(()),
+        "boolean" => {
+            let value = value
+                .as_bool()
+                .ok_or_else(|| format!("expected a boolean, found {}", value))?;
+            out.push(u8::from(value));
+            Ok(())
+        }
+        "int" | "long" => {
+            let value = value
+                .as_i64()
+                .ok_or_else(|| format!("expected an integer, found {}", value))?;
+            write_long(value, out);
+            Ok(())
+        }
+        "string" => {
+            let value = value
+                .as_str()
+                .ok_or_else(|| format!("expected a string, found {}", value))?;
+            write_long(value.len() as i64, out);
+            out.extend_from_slice(value.as_bytes());
+            Ok(())
+        }
+        _ => Err(format!("unsupported type {}", name)),
+    }
+}
+
+/// Appends a zig-zag encoded variable-length integer.
+fn write_long(value: i64, out: &mut Vec<u8>) {
+    let mut zigzag = ((value << 1) ^ (value >> 63)) as u64;
+    while zigzag > 0x7f {
+        out.push((zigzag & 0x7f) as u8 | 0x80);
+        zigzag >>= 7;
+    }
+    out.push(zigzag as u8);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encoded(schema: Value, value: Value) -> Vec<u8> {
+        let mut out = Vec::new();
+        encode_value(&schema, &value, &mut out).unwrap();
+        out
+    }
+
+    #[test]
+    fn write_long_zig_zag_encodes() {
+        let cases: &[(i64, &[u8])] = &[
+            (0, &[0x00]),
+            (-1, &[0x01]),
+            (1, &[0x02]),
+            (-64, &[0x7f]),
+            (63, &[0x7e]),
+            (64, &[0x80, 0x01]),
+            (-65, &[0x81, 0x01]),
+            (8192, &[0x80, 0x80, 0x01]),
+            (
+                i64::min_value(),
+                &[0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x01],
+            ),
+        ];
+        for (value, expected) in cases {
+            let mut out = Vec::new();
+            write_long(*value, &mut out);
+            assert_eq!(&out[..], *expected, "encoding of {}", value);
+        }
+    }
+
+    #[test]
+    fn encode_value_picks_the_union_branch() {
+        let union = json!(["null", "string"]);
+        assert_eq!(encoded(union.clone(), Value::Null), vec![0x00]);
+        assert_eq!(encoded(union, json!("ab")), vec![0x02, 0x04, b'a', b'b']);
+    }
+
+    #[test]
+    fn encode_value_writes_arrays_as_one_block() {
+        let array = json!({ "type": "array", "items": "long" });
+        assert_eq!(
+            encoded(array.clone(), json!([1, -1, 64])),
+            vec![0x06, 0x02, 0x01, 0x80, 0x01, 0x00]
+        );
+        assert_eq!(encoded(array, json!([])), vec![0x00]);
+    }
+
+    #[test]
+    fn encode_value_writes_record_fields_in_order() {
+        let record = json!({
+            "type": "record",
+            "name": "Test",
+            "fields": [
+                { "name": "count", "type": "long" },
+                { "name": "name", "type": "string" },
+                { "name": "deleted", "type": "boolean" },
+            ],
+        });
+        assert_eq!(
+            encoded(
+                record,
+                json!({ "name": "hi", "count": -1, "deleted": true })
+            ),
+            vec![0x01, 0x04, b'h', b'i', 0x01]
+        );
+    }
+
+    #[test]
+    fn encode_value_refuses_mismatched_values() {
+        let mut out = Vec::new();
+        assert!(encode_value(&json!("string"), &json!(5), &mut out).is_err());
+        assert!(encode_value(&json!("long"), &json!("5"), &mut out).is_err());
+    }
+}
@@ -0,0 +1,240 @@
+/*
+ * Copyright 2019 Walmart Inc.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * -----------------------------------------------------------------------------
+ */
+
+//! Bulk-indexes exported messages into Elasticsearch or OpenSearch, for dashboards over circuit
+//! activity.
+
+use std::sync::Arc;
+
+use hyper::header::{AUTHORIZATION, CONTENT_TYPE};
+use hyper::{Body, Method, Request};
+use openssl::base64;
+use serde_json::Value;
+
+use super::http::BlockingClient;
+use super::json::to_json;
+use super::mapping::FieldMapping;
+use super::sink::{ExportSink, QueuedMessage};
+use super::PublisherError;
+use crate::clock::Clock;
+use crate::config::IndexRollover;
+
+/// placeholder in an index name replaced by the current period, according to the rollover
+const DATE_PLACEHOLDER: &str = "{date}";
+
+const SECS_PER_DAY: u64 = 86_400;
+
+/// Where and how an Elasticsearch sink indexes messages
+pub struct ElasticsearchSettings {
+    pub url: String,
+    pub index: String,
+    pub rollover: IndexRollover,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    /// reshapes the records indexed
+    pub fields: FieldMapping,
+}
+
+/// Indexes each batch with a single bulk request, after installing an index template mapping
+/// the fields of the records.
+pub struct ElasticsearchSink {
+    name: String,
+    settings: ElasticsearchSettings,
+    client: BlockingClient,
+    template_installed: bool,
+    clock: Arc<dyn Clock>,
+}
+
+impl ElasticsearchSink {
+    pub fn new(
+        settings: ElasticsearchSettings,
+        clock: Arc<dyn Clock>,
+    ) -> Result<Self, PublisherError> {
+        Ok(ElasticsearchSink {
+            name: format!("Elasticsearch index {}", settings.index),
+            client: BlockingClient::new()?,
+            template_installed: false,
+            settings,
+            clock,
+        })
+    }
+
+    fn request(
+        &self,
+        method: Method,
+        path: &str,
+        content_type: &str,
+        body: Vec<u8>,
+    ) -> Result<Request<Body>, PublisherError> {
+        let mut builder = Request::builder();
+        builder
+            .method(method)
+            .uri(format!("{}/{}", self.settings.url.trim_end_matches('/'), path))
+            .header(CONTENT_TYPE, content_type);
+        if let Some(username) = &self.settings.username {
+            let credentials = format!(
+                "{}:{}",
+                username,
+                self.settings.password.as_ref().map(String::as_str).unwrap_or("")
+            );
+            builder.header(
+                AUTHORIZATION,
+                format!("Basic {}", base64::encode_block(credentials.as_bytes())),
+            );
+        }
+        builder
+            .body(Body::from(body))
+            .map_err(|err| PublisherError::SinkError(err.to_string()))
+    }
+
+    /// Creates or replaces the template applied to every index the sink writes to.
+    fn install_template(&mut self) -> Result<(), PublisherError> {
+        let pattern = self.settings.index.replace(DATE_PLACEHOLDER, "*");
+        let name = self
+            .settings
+            .index
+            .replace(DATE_PLACEHOLDER, "")
+            .trim_matches(|c| c == '-' || c == '.' || c == '_')
+            .to_string();
+        let template = json!({
+            "index_patterns": [pattern],
+            "mappings": {
+                "properties": {
+                    "@timestamp": { "type": "date", "format": "epoch_second" },
+                    "schema_version": { "type": "integer" },
+                    "type": { "type": "keyword" },
+                    "circuit_id": { "type": "keyword" },
+                    "requester": { "type": "keyword" },
+                    "requester_node_id": { "type": "keyword" },
+                    "voter": { "type": "keyword" },
+                    "voter_node_id": { "type": "keyword" },
+                    "voter_name": { "type": "keyword" },
+                    "vote": { "type": "keyword" },
+                    "status": { "type": "keyword" },
+                    "circuit_hash": { "type": "keyword" },
+                    "service_id": { "type": "keyword" },
+                    "address": { "type": "keyword" },
+                    "data": { "type": "text", "index": false },
+                    "decoded": { "type": "object", "enabled": false },
+                }
+            }
+        });
+        let request = self.request(
+            Method::PUT,
+            &format!("_template/{}", name),
+            "application/json",
+            template.to_string().into_bytes(),
+        )?;
+        let (status, body) = self.client.send(request).map_err(PublisherError::SinkError)?;
+        if !status.is_success() {
+            return Err(PublisherError::SinkError(format!(
+                "unable to install index template {}: {} {}",
+                name,
+                status,
+                String::from_utf8_lossy(&body)
+            )));
+        }
+        self.template_installed = true;
+        Ok(())
+    }
+
+    /// Returns the index written to now.
+    fn current_index(&self) -> String {
+        let secs = self.clock.now_secs();
+        let (year, month, day) = civil_date(secs / SECS_PER_DAY);
+        let period = match self.settings.rollover {
+            IndexRollover::None => String::new(),
+            IndexRollover::Monthly => format!("{:04}.{:02}", year, month),
+            IndexRollover::Daily => format!("{:04}.{:02}.{:02}", year, month, day),
+        };
+        self.settings.index.replace(DATE_PLACEHOLDER, &period)
+    }
+}
+
+impl ExportSink for ElasticsearchSink {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn write(&mut self, batch: &[QueuedMessage]) -> Result<(), PublisherError> {
+        if !self.template_installed {
+            self.install_template()?;
+        }
+
+        let index = self.current_index();
+        let timestamp = self.clock.now_secs();
+        let mut body = Vec::new();
+        for queued in batch {
+            let mut record = self.settings.fields.apply(to_json(queued.message())?);
+            record["@timestamp"] = json!(timestamp);
+            body.extend(json!({ "index": { "_index": index } }).to_string().bytes());
+            body.push(b'\n');
+            body.extend(record.to_string().bytes());
+            body.push(b'\n');
+        }
+
+        let request = self.request(Method::POST, "_bulk", "application/x-ndjson", body)?;
+        let (status, body) = self.client.send(request).map_err(PublisherError::SinkError)?;
+        if !status.is_success() {
+            return Err(PublisherError::SinkError(format!(
+                "bulk request failed: {} {}",
+                status,
+                String::from_utf8_lossy(&body)
+            )));
+        }
+        // a successful bulk request may still have failed to index some of the documents
+        let response: Value = serde_json::from_slice(&body)
+            .map_err(|err| PublisherError::SinkError(format!("invalid bulk response: {}", err)))?;
+        if response["errors"].as_bool().unwrap_or(false) {
+            let items = response["items"].as_array().cloned().unwrap_or_default();
+            let failed = items
+                .iter()
+                .filter(|item| !item["index"]["error"].is_null())
+                .collect::<Vec<_>>();
+            return Err(PublisherError::SinkError(format!(
+                "{} of {} documents were not indexed, the first with: {}",
+                failed.len(),
+                items.len(),
+                failed
+                    .first()
+                    .map(|item| item["index"]["error"].to_string())
+                    .unwrap_or_default()
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Converts a number of days since the epoch into a year, month and day of the UTC calendar.
+fn civil_date(days: u64) -> (u64, u64, u64) {
+    // shift the epoch to 0000-03-01 so leap days fall at the end of each 400 year era
+    let days = days + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days % 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + u64::from(month <= 2);
+    (year, month, day)
+}
@@ -0,0 +1,60 @@
+/*
+ * Copyright 2019 Walmart Inc.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * -----------------------------------------------------------------------------
+ */
+
+use std::error::Error;
+use std::fmt;
+
+#[derive(Debug)]
+pub enum PublisherError {
+    StartUpError(String),
+    QueueClosed,
+    QueueFull,
+    KafkaError(String),
+    SinkError(String),
+    SerializationError(String),
+    ShutdownError(String),
+    ShutdownTimeout(usize),
+}
+
+impl Error for PublisherError {}
+
+impl fmt::Display for PublisherError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PublisherError::StartUpError(msg) => {
+                write!(f, "Unable to start publisher workers: {}", msg)
+            }
+            PublisherError::QueueClosed => write!(f, "The publisher queue has been shut down"),
+            PublisherError::QueueFull => {
+                write!(f, "The publisher queue is full, message dropped")
+            }
+            PublisherError::KafkaError(msg) => write!(f, "Unable to write to Kafka: {}", msg),
+            PublisherError::SinkError(msg) => write!(f, "Unable to write to the sink: {}", msg),
+            PublisherError::SerializationError(msg) => {
+                write!(f, "Unable to serialize message: {}", msg)
+            }
+            PublisherError::ShutdownError(msg) => {
+                write!(f, "Unable to shut down publisher: {}", msg)
+            }
+            PublisherError::ShutdownTimeout(remaining) => write!(
+                f,
+                "Publisher did not finish in time, abandoned {} queued messages",
+                remaining
+            ),
+        }
+    }
+}
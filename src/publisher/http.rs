@@ -0,0 +1,84 @@
+/*
+ * Copyright 2019 Walmart Inc.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * -----------------------------------------------------------------------------
+ */
+
+//! A blocking HTTP client for sinks, which run on the publisher worker threads, and for other
+//! callers off the REST API's event loop.
+
+use std::time::Duration;
+
+use futures::{Future, Stream};
+use hyper::client::HttpConnector;
+use hyper::{Body, Client, Request, StatusCode};
+use hyper_openssl::HttpsConnector;
+use openssl::ssl::SslConnectorBuilder;
+use tokio::runtime::Runtime;
+use tokio::timer::Timeout;
+
+use super::PublisherError;
+
+/// time to wait for a complete response
+const HTTP_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// number of threads resolving host names
+const DNS_THREADS: usize = 1;
+
+/// Sends HTTP and HTTPS requests, waiting for each response.
+pub struct BlockingClient {
+    client: Client<HttpsConnector<HttpConnector>>,
+    runtime: Runtime,
+}
+
+impl BlockingClient {
+    pub fn new() -> Result<Self, PublisherError> {
+        let connector = HttpsConnector::new(DNS_THREADS)
+            .map_err(|err| PublisherError::StartUpError(err.to_string()))?;
+        BlockingClient::with_connector(connector)
+    }
+
+    /// Creates a client with the given TLS settings, such as a client certificate.
+    pub fn with_tls(tls: SslConnectorBuilder) -> Result<Self, PublisherError> {
+        let mut http = HttpConnector::new(DNS_THREADS);
+        http.enforce_http(false);
+        let connector = HttpsConnector::with_connector(http, tls)
+            .map_err(|err| PublisherError::StartUpError(err.to_string()))?;
+        BlockingClient::with_connector(connector)
+    }
+
+    fn with_connector(connector: HttpsConnector<HttpConnector>) -> Result<Self, PublisherError> {
+        let runtime = Runtime::new().map_err(|err| {
+            PublisherError::StartUpError(format!("Unable to start HTTP client runtime: {}", err))
+        })?;
+        Ok(BlockingClient {
+            client: Client::builder().build(connector),
+            runtime,
+        })
+    }
+
+    /// Sends the request and returns the status and body of the response.
+    pub fn send(&mut self, request: Request<Body>) -> Result<(StatusCode, Vec<u8>), String> {
+        let response = self.client.request(request).and_then(|response| {
+            let status = response.status();
+            response
+                .into_body()
+                .concat2()
+                .map(move |body| (status, body.to_vec()))
+        });
+        self.runtime
+            .block_on(Timeout::new(response, HTTP_TIMEOUT))
+            .map_err(|err| err.to_string())
+    }
+}
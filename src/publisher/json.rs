@@ -0,0 +1,151 @@
+/*
+ * Copyright 2019 Walmart Inc.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * -----------------------------------------------------------------------------
+ */
+
+//! JSON form of exported messages, for sinks whose consumers do not read protobuf.
+
+use protobuf::Message as Msg;
+use serde_json::Value;
+
+use super::PublisherError;
+use crate::event_handler::to_hex;
+use crate::proto::pubsub::{
+    ActivitySummary, CircuitCreated, CircuitPayload, Message, Message_MessageType,
+    ProposalAccept, ProposalReady, ProposalReject, ProposalSubmit, ProposalVote,
+};
+
+/// Version of the JSON records, raised whenever a field is renamed or removed
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// Returns the name of a message type in JSON records, such as "proposal_vote".
+pub fn type_name(message_type: Message_MessageType) -> String {
+    format!("{:?}", message_type).to_lowercase()
+}
+
+/// Converts an exported message into a JSON record.
+///
+/// Every record has a `schema_version` and a `type`, followed by the fields of the message.
+/// Public keys, hashes and payloads are hex encoded.
+pub fn to_json(message: &Message) -> Result<Value, PublisherError> {
+    let bytes = message.get_message();
+    let mut record = match message.get_field_type() {
+        Message_MessageType::PROPOSAL_SUBMIT => {
+            let submit: ProposalSubmit = parse(bytes)?;
+            json!({
+                "requester": submit.get_requester(),
+                "requester_node_id": submit.get_requester_node_id(),
+                "circuit_id": submit.get_circuit_id(),
+            })
+        }
+        Message_MessageType::PROPOSAL_VOTE => {
+            let vote: ProposalVote = parse(bytes)?;
+            json!({
+                "voter": vote.get_voter(),
+                "voter_node_id": vote.get_voter_node_id(),
+                "circuit_id": vote.get_circuit_id(),
+                "vote": vote.get_vote(),
+                "remaining_votes": vote.get_remaining_votes(),
+                "status": vote.get_status(),
+                "circuit_hash": to_hex(vote.get_circuit_hash()),
+                "voter_name": vote.get_voter_name(),
+            })
+        }
+        Message_MessageType::PROPOSAL_ACCEPT => {
+            let accept: ProposalAccept = parse(bytes)?;
+            json!({
+                "voter": accept.get_voter(),
+                "voter_node_id": accept.get_voter_node_id(),
+                "circuit_id": accept.get_circuit_id(),
+                "circuit_hash": to_hex(accept.get_circuit_hash()),
+                "voter_name": accept.get_voter_name(),
+            })
+        }
+        Message_MessageType::PROPOSAL_REJECT => {
+            let reject: ProposalReject = parse(bytes)?;
+            json!({
+                "voter": reject.get_voter(),
+                "voter_node_id": reject.get_voter_node_id(),
+                "circuit_id": reject.get_circuit_id(),
+                "circuit_hash": to_hex(reject.get_circuit_hash()),
+                "voter_name": reject.get_voter_name(),
+            })
+        }
+        Message_MessageType::PROPOSAL_READY => {
+            let ready: ProposalReady = parse(bytes)?;
+            json!({
+                "requester": ready.get_requester(),
+                "requester_node_id": ready.get_requester_node_id(),
+                "circuit_id": ready.get_circuit_id(),
+            })
+        }
+        Message_MessageType::CIRCUIT_CREATED => {
+            let created: CircuitCreated = parse(bytes)?;
+            json!({
+                "requester": created.get_requester(),
+                "requester_node_id": created.get_requester_node_id(),
+                "circuit_id": created.get_circuit_id(),
+            })
+        }
+        Message_MessageType::CIRCUIT_PAYLOAD => {
+            let payload: CircuitPayload = parse(bytes)?;
+            let decoded = if payload.get_decoded().is_empty() {
+                Value::Null
+            } else {
+                serde_json::from_str(payload.get_decoded())
+                    .map_err(|err| PublisherError::SerializationError(err.to_string()))?
+            };
+            json!({
+                "requester": payload.get_requester(),
+                "requester_node_id": payload.get_requester_node_id(),
+                "circuit_id": payload.get_circuit_id(),
+                "data": to_hex(payload.get_data()),
+                "service_id": payload.get_service_id(),
+                "address": payload.get_address(),
+                "deleted": payload.get_deleted(),
+                "decoded": decoded,
+            })
+        }
+        Message_MessageType::ACTIVITY_SUMMARY => {
+            let summary: ActivitySummary = parse(bytes)?;
+            let counts = summary
+                .get_counts()
+                .iter()
+                .map(|count| {
+                    json!({
+                        "type": type_name(count.get_field_type()),
+                        "count": count.get_count(),
+                        "bytes": count.get_bytes(),
+                    })
+                })
+                .collect::<Vec<_>>();
+            json!({
+                "period_start": summary.get_period_start(),
+                "period_end": summary.get_period_end(),
+                "counts": counts,
+                "suppressed_groups": summary.get_suppressed_groups(),
+            })
+        }
+        Message_MessageType::TYPE_UNKNOWN => json!({ "data": to_hex(bytes) }),
+    };
+    record["schema_version"] = json!(SCHEMA_VERSION);
+    record["type"] = json!(type_name(message.get_field_type()));
+    Ok(record)
+}
+
+fn parse<M: Msg>(bytes: &[u8]) -> Result<M, PublisherError> {
+    protobuf::parse_from_bytes(bytes)
+        .map_err(|err| PublisherError::SerializationError(err.to_string()))
+}
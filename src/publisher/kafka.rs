@@ -0,0 +1,143 @@
+/*
+ * Copyright 2019 Walmart Inc.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * -----------------------------------------------------------------------------
+ */
+
+//! Writes exported messages to Kafka topics.
+
+use std::time::Duration;
+
+use kafka::producer::{Producer, Record, RequiredAcks};
+use protobuf::Message as Msg;
+
+use super::avro::AvroEncoder;
+use super::sink::{topic_for, ExportSink, QueuedMessage};
+use super::PublisherError;
+use crate::config::KafkaAcks;
+
+/// time to wait for the Kafka broker to acknowledge a message
+const KAFKA_ACK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Writes each batch to Kafka in a single produce request.
+///
+/// Records are keyed by circuit id, so the messages of a circuit stay in order on one
+/// partition. Their values are the protobuf messages, or their JSON records encoded with Avro
+/// if an encoder is given. The producer is created on first use and again after a failed
+/// request.
+pub struct KafkaSink {
+    name: String,
+    brokers: Vec<String>,
+    topic: String,
+    required_acks: KafkaAcks,
+    avro: Option<AvroEncoder>,
+    producer: Option<Producer>,
+}
+
+impl KafkaSink {
+    pub fn new(
+        brokers: &[String],
+        topic: &str,
+        required_acks: KafkaAcks,
+        avro: Option<AvroEncoder>,
+    ) -> Self {
+        KafkaSink {
+            name: format!("Kafka topic {}", topic),
+            brokers: brokers.to_vec(),
+            topic: topic.to_string(),
+            required_acks,
+            avro,
+            producer: None,
+        }
+    }
+
+    fn encode(&mut self, topic: &str, queued: &QueuedMessage) -> Result<Vec<u8>, PublisherError> {
+        match &mut self.avro {
+            Some(avro) => avro.encode(topic, queued.message()),
+            None => queued
+                .message()
+                .write_to_bytes()
+                .map_err(|err| PublisherError::SerializationError(err.to_string())),
+        }
+    }
+}
+
+impl ExportSink for KafkaSink {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn write(&mut self, batch: &[QueuedMessage]) -> Result<(), PublisherError> {
+        let topics = batch
+            .iter()
+            .map(|queued| topic_for(&self.topic, queued))
+            .collect::<Vec<_>>();
+        let values = batch
+            .iter()
+            .zip(&topics)
+            .map(|(queued, topic)| self.encode(topic, queued))
+            .collect::<Result<Vec<_>, _>>()?;
+        let records = batch
+            .iter()
+            .zip(&topics)
+            .zip(values)
+            .map(|((queued, topic), value)| {
+                Record::from_key_value(topic, queued.circuit_id(), value)
+            })
+            .collect::<Vec<_>>();
+
+        let mut producer = match self.producer.take() {
+            Some(producer) => producer,
+            None => Producer::from_hosts(self.brokers.clone())
+                .with_ack_timeout(KAFKA_ACK_TIMEOUT)
+                .with_required_acks(match self.required_acks {
+                    KafkaAcks::None => RequiredAcks::None,
+                    KafkaAcks::One => RequiredAcks::One,
+                    KafkaAcks::All => RequiredAcks::All,
+                })
+                .create()
+                .map_err(|err| PublisherError::KafkaError(err.to_string()))?,
+        };
+
+        let confirms = producer
+            .send_all(&records)
+            .map_err(|err| PublisherError::KafkaError(err.to_string()))?;
+        // the broker may accept the request yet reject it for individual partitions
+        let mut rejections = Vec::new();
+        for confirm in confirms {
+            for partition_confirm in confirm.partition_confirms {
+                match partition_confirm.offset {
+                    Ok(offset) => debug!(
+                        "Kafka topic {} partition {} accepted messages up to offset {}",
+                        confirm.topic, partition_confirm.partition, offset
+                    ),
+                    Err(code) => rejections.push(format!(
+                        "topic {} partition {}: {:?}",
+                        confirm.topic, partition_confirm.partition, code
+                    )),
+                }
+            }
+        }
+        self.producer = Some(producer);
+
+        if rejections.is_empty() {
+            Ok(())
+        } else {
+            Err(PublisherError::KafkaError(format!(
+                "the batch was rejected by {}",
+                rejections.join(", ")
+            )))
+        }
+    }
+}
@@ -0,0 +1,155 @@
+/*
+ * Copyright 2019 Walmart Inc.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * -----------------------------------------------------------------------------
+ */
+
+//! Reshapes JSON records for consumers with a fixed ingestion schema.
+
+use std::collections::BTreeMap;
+
+use serde_json::{Map, Value};
+
+/// Selects and renames the fields of JSON records.
+///
+/// Each output field is given the value found at a path into the record, such as
+/// `decoded.contracts[0].name` or `$.circuit_id`; nested values are thereby flattened into
+/// top-level fields. Fields whose path is missing from a record are null. Without any fields,
+/// records are left whole.
+#[derive(Clone, Default)]
+pub struct FieldMapping {
+    /// output field name and JSON pointer to its value
+    fields: Vec<(String, String)>,
+}
+
+impl FieldMapping {
+    /// Parses a mapping of output field names to paths.
+    pub fn new(fields: &BTreeMap<String, String>) -> Result<Self, String> {
+        fields
+            .iter()
+            .map(|(name, path)| Ok((name.clone(), to_pointer(path)?)))
+            .collect::<Result<Vec<_>, String>>()
+            .map(|fields| FieldMapping { fields })
+    }
+
+    pub fn apply(&self, record: Value) -> Value {
+        if self.fields.is_empty() {
+            return record;
+        }
+        let mapped = self
+            .fields
+            .iter()
+            .map(|(name, pointer)| {
+                (
+                    name.clone(),
+                    record.pointer(pointer).cloned().unwrap_or(Value::Null),
+                )
+            })
+            .collect::<Map<String, Value>>();
+        Value::Object(mapped)
+    }
+}
+
+/// Converts a path of dot-separated keys and bracketed array indices into a JSON pointer.
+fn to_pointer(path: &str) -> Result<String, String> {
+    let trimmed = path.trim_start_matches('$').trim_start_matches('.');
+    if trimmed.is_empty() {
+        return Err(format!("empty field path: {}", path));
+    }
+    let mut pointer = String::new();
+    for key in trimmed.split('.') {
+        let (name, indices) = match key.find('[') {
+            Some(start) => key.split_at(start),
+            None => (key, ""),
+        };
+        if name.is_empty() && indices.is_empty() {
+            return Err(format!("empty key in field path: {}", path));
+        }
+        if !name.is_empty() {
+            pointer.push('/');
+            pointer.push_str(&name.replace('~', "~0").replace('/', "~1"));
+        }
+        for index in indices.split_terminator(']') {
+            let index = Some(index)
+                .filter(|index| index.starts_with('['))
+                .and_then(|index| index[1..].parse::<usize>().ok())
+                .ok_or_else(|| format!("invalid array index in field path: {}", path))?;
+            pointer.push('/');
+            pointer.push_str(&index.to_string());
+        }
+    }
+    Ok(pointer)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mapping(fields: &[(&str, &str)]) -> Result<FieldMapping, String> {
+        FieldMapping::new(
+            &fields
+                .iter()
+                .map(|(name, path)| (name.to_string(), path.to_string()))
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn to_pointer_converts_paths() {
+        assert_eq!(
+            to_pointer("decoded.contracts[0].name").unwrap(),
+            "/decoded/contracts/0/name"
+        );
+        assert_eq!(to_pointer("$.circuit_id").unwrap(), "/circuit_id");
+        assert_eq!(to_pointer("matrix[1][2]").unwrap(), "/matrix/1/2");
+        assert_eq!(to_pointer("a/b.c~d").unwrap(), "/a~1b/c~0d");
+    }
+
+    #[test]
+    fn to_pointer_refuses_invalid_paths() {
+        for path in &["", "$", "$.", "a..b", "a[x]", "a[-1]"] {
+            assert!(to_pointer(path).is_err(), "{}", path);
+        }
+    }
+
+    #[test]
+    fn apply_flattens_and_renames_fields() {
+        let mapping = mapping(&[
+            ("circuit", "circuit_id"),
+            ("second_owner", "decoded.registries[0].owners[1]"),
+            ("missing", "decoded.contracts[3]"),
+        ])
+        .unwrap();
+        let record = json!({
+            "circuit_id": "01234-ABCDE",
+            "type": "circuit_payload",
+            "decoded": { "registries": [{ "owners": ["02aa", "03bb"] }] },
+        });
+        assert_eq!(
+            mapping.apply(record),
+            json!({ "circuit": "01234-ABCDE", "second_owner": "03bb", "missing": null })
+        );
+    }
+
+    #[test]
+    fn apply_without_fields_keeps_the_record() {
+        let record = json!({ "circuit_id": "01234-ABCDE", "type": "proposal_vote" });
+        assert_eq!(mapping(&[]).unwrap().apply(record.clone()), record);
+    }
+
+    #[test]
+    fn new_refuses_invalid_paths() {
+        assert!(mapping(&[("circuit", "circuit_id"), ("bad", "a[x]")]).is_err());
+    }
+}
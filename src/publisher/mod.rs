@@ -0,0 +1,463 @@
+/*
+ * Copyright 2019 Walmart Inc.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * -----------------------------------------------------------------------------
+ */
+
+//! Decouples reading events from the websockets from writing them to the export sinks.
+//!
+//! Messages are placed on bounded queues and written by a pool of worker threads, each holding
+//! its own instance of every sink, such as a Kafka producer. A slow sink fills the queues
+//! instead of stalling the websocket thread until splinterd drops the connection. Bursts of
+//! messages, such as those after a reconnect, are coalesced into one write per batch.
+//!
+//! Every worker has its own queue and the messages of a circuit always go to the same one, so
+//! circuits are written concurrently while the messages of each circuit stay in order.
+
+mod aggregate;
+mod avro;
+mod elasticsearch;
+mod error;
+mod http;
+mod json;
+mod kafka;
+mod mapping;
+mod nats;
+mod ndjson;
+mod runs;
+mod sink;
+mod webhook;
+
+pub use error::PublisherError;
+pub use http::BlockingClient;
+pub use runs::{ExportRun, ExportRuns, ExportSinkSummary};
+pub use sink::{check_sink_config, ExportSink};
+pub use webhook::{WebhookDeliveries, WebhookDelivery};
+
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::{
+    channel, sync_channel, Receiver, RecvTimeoutError, Sender, SyncSender, TrySendError,
+};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use splinter::admin::messages::AdminServiceEvent;
+
+use self::aggregate::Aggregator;
+use self::ndjson::NdjsonOutputs;
+use self::sink::{build_sinks, QueuedMessage};
+use crate::clock::Clock;
+use crate::config::{DeploymentConfig, ExportMode, QueueFullPolicy};
+use crate::event_handler::{EventFilter, FailedEvents};
+use crate::proto::pubsub::Message;
+
+/// how often an idle worker checks whether it has been asked to shut down
+const SHUTDOWN_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Counters describing the state of the publisher queue.
+#[derive(Debug, Default)]
+pub struct PublisherStats {
+    queue_length: AtomicUsize,
+    published: AtomicUsize,
+    failed: AtomicUsize,
+    backpressure_events: AtomicUsize,
+    dropped: AtomicUsize,
+}
+
+impl PublisherStats {
+    /// Number of messages waiting to be written
+    pub fn queue_length(&self) -> usize {
+        self.queue_length.load(Ordering::SeqCst)
+    }
+
+    /// Number of messages written to every sink
+    pub fn published(&self) -> usize {
+        self.published.load(Ordering::SeqCst)
+    }
+
+    /// Number of messages a sink failed to accept
+    pub fn failed(&self) -> usize {
+        self.failed.load(Ordering::SeqCst)
+    }
+
+    /// Number of times a caller found the queue full and had to wait
+    pub fn backpressure_events(&self) -> usize {
+        self.backpressure_events.load(Ordering::SeqCst)
+    }
+
+    /// Number of messages discarded because the queue was full
+    pub fn dropped(&self) -> usize {
+        self.dropped.load(Ordering::SeqCst)
+    }
+}
+
+/// Coordinates stopping the worker threads.
+struct Shutdown {
+    requested: Arc<AtomicBool>,
+    workers: usize,
+    finished: Mutex<Receiver<()>>,
+}
+
+/// A handle for queueing messages to be written to the export sinks.
+#[derive(Clone)]
+pub struct Publisher {
+    /// one queue per worker
+    senders: Vec<SyncSender<QueuedMessage>>,
+    queue_full_policy: QueueFullPolicy,
+    stats: Arc<PublisherStats>,
+    aggregator: Option<Arc<Aggregator>>,
+    shutdown: Arc<Shutdown>,
+    webhook_deliveries: WebhookDeliveries,
+    export_runs: ExportRuns,
+}
+
+impl Publisher {
+    /// Starts the worker threads and returns a handle to their queues.
+    ///
+    /// The messages about a circuit are written to the export sink `filter` routes them to, if
+    /// any. The admin events whose messages a sink fails to accept are recorded in
+    /// `failed_events`.
+    pub fn start(
+        config: &DeploymentConfig,
+        filter: &EventFilter,
+        failed_events: FailedEvents,
+        clock: Arc<dyn Clock>,
+    ) -> Result<Publisher, PublisherError> {
+        let mut senders = Vec::with_capacity(config.event_queue_workers());
+        let stats = Arc::new(PublisherStats::default());
+        let shutdown_requested = Arc::new(AtomicBool::new(false));
+        let (finished_sender, finished_receiver) = channel();
+        let webhook_deliveries = WebhookDeliveries::new(config.webhook_delivery_history_size());
+        let export_runs = ExportRuns::new(config.export_run_history_size());
+        let ndjson_outputs = NdjsonOutputs::default();
+
+        for id in 0..config.event_queue_workers() {
+            let (sender, receiver) = sync_channel(config.event_queue_depth());
+            senders.push(sender);
+            let sinks = build_sinks(
+                config,
+                filter,
+                &webhook_deliveries,
+                &failed_events,
+                &ndjson_outputs,
+                &clock,
+            )?;
+            export_runs.register(&sinks);
+            let worker = Worker {
+                receiver,
+                shutdown_requested: shutdown_requested.clone(),
+                finished: finished_sender.clone(),
+                batch_size: config.event_batch_size(),
+                stats: stats.clone(),
+                sinks,
+                runs: export_runs.clone(),
+                failed_events: failed_events.clone(),
+                clock: clock.clone(),
+            };
+            thread::Builder::new()
+                .name(format!("Publisher-{}", id))
+                .spawn(move || worker.run())
+                .map_err(|err| PublisherError::StartUpError(err.to_string()))?;
+        }
+
+        let queue = Publisher {
+            senders,
+            queue_full_policy: config.event_queue_full_policy(),
+            stats,
+            aggregator: None,
+            shutdown: Arc::new(Shutdown {
+                requested: shutdown_requested,
+                workers: config.event_queue_workers(),
+                finished: Mutex::new(finished_receiver),
+            }),
+            webhook_deliveries,
+            export_runs,
+        };
+
+        match config.export_mode() {
+            ExportMode::Records => Ok(queue),
+            ExportMode::Aggregate => {
+                let aggregator = Arc::new(Aggregator::new(
+                    Duration::from_secs(config.aggregation_period_secs()),
+                    config.aggregation_min_group_size(),
+                    clock,
+                ));
+                let flush_aggregator = aggregator.clone();
+                let flush_queue = queue.clone();
+                thread::Builder::new()
+                    .name("Aggregator".into())
+                    .spawn(move || loop {
+                        thread::sleep(flush_aggregator.period());
+                        if flush_queue.shutdown.requested.load(Ordering::SeqCst) {
+                            break;
+                        }
+                        if let Err(err) = flush_aggregator
+                            .flush()
+                            .and_then(|summary| flush_queue.enqueue("", summary, None))
+                        {
+                            error!("Unable to publish activity summary: {}", err);
+                        }
+                    })
+                    .map_err(|err| PublisherError::StartUpError(err.to_string()))?;
+
+                Ok(Publisher {
+                    aggregator: Some(aggregator),
+                    ..queue
+                })
+            }
+        }
+    }
+
+    /// Queues a message about the given circuit to be written to the export sinks.
+    ///
+    /// Messages about the same circuit are written in the order they are published. When the
+    /// queue is full the call either blocks until a worker frees a slot or discards
+    /// the message, depending on the configured policy. In aggregate export mode the message is
+    /// only counted towards the next activity summary.
+    pub fn publish(&self, circuit_id: &str, message: Message) -> Result<(), PublisherError> {
+        if let Some(aggregator) = &self.aggregator {
+            aggregator.record(&message);
+            return Ok(());
+        }
+
+        self.enqueue(circuit_id, message, None)
+    }
+
+    /// Queues a message made from an admin event, like `publish`.
+    ///
+    /// If a sink fails to accept the message, the event is recorded as failed so it can be
+    /// retried.
+    pub fn publish_from_event(
+        &self,
+        circuit_id: &str,
+        message: Message,
+        event: Arc<AdminServiceEvent>,
+    ) -> Result<(), PublisherError> {
+        if let Some(aggregator) = &self.aggregator {
+            aggregator.record(&message);
+            return Ok(());
+        }
+
+        self.enqueue(circuit_id, message, Some(event))
+    }
+
+    fn enqueue(
+        &self,
+        circuit_id: &str,
+        message: Message,
+        source: Option<Arc<AdminServiceEvent>>,
+    ) -> Result<(), PublisherError> {
+        let mut hasher = DefaultHasher::new();
+        circuit_id.hash(&mut hasher);
+        let sender = &self.senders[hasher.finish() as usize % self.senders.len()];
+
+        self.stats.queue_length.fetch_add(1, Ordering::SeqCst);
+        let queued = QueuedMessage::new(circuit_id, message, source);
+        let result = match sender.try_send(queued) {
+            Ok(()) => Ok(()),
+            Err(TrySendError::Full(queued)) => match self.queue_full_policy {
+                QueueFullPolicy::Block => {
+                    self.stats
+                        .backpressure_events
+                        .fetch_add(1, Ordering::SeqCst);
+                    warn!(
+                        "Publisher queue is full ({} messages), waiting for the export sinks",
+                        self.stats.queue_length()
+                    );
+                    sender
+                        .send(queued)
+                        .map_err(|_| PublisherError::QueueClosed)
+                }
+                QueueFullPolicy::Drop => {
+                    self.stats.dropped.fetch_add(1, Ordering::SeqCst);
+                    Err(PublisherError::QueueFull)
+                }
+            },
+            Err(TrySendError::Disconnected(_)) => Err(PublisherError::QueueClosed),
+        };
+        if result.is_err() {
+            self.stats.queue_length.fetch_sub(1, Ordering::SeqCst);
+        }
+        result
+    }
+
+    pub fn stats(&self) -> &PublisherStats {
+        &self.stats
+    }
+
+    /// Returns the most recent deliveries of the webhook sinks, oldest first.
+    pub fn webhook_deliveries(&self) -> Vec<WebhookDelivery> {
+        self.webhook_deliveries.list()
+    }
+
+    /// Returns the totals of every export sink.
+    pub fn export_sinks(&self) -> Vec<ExportSinkSummary> {
+        self.export_runs.summaries()
+    }
+
+    /// Returns the most recent batches written to the export sink with the given id, oldest
+    /// first, or `None` if there is no such sink.
+    pub fn export_runs(&self, sink_id: usize) -> Option<Vec<ExportRun>> {
+        self.export_runs.runs(sink_id)
+    }
+
+    /// Stops the worker threads once they have written the messages already queued.
+    ///
+    /// Waits at most `timeout`. Messages still queued or being written after that are abandoned
+    /// and an error reporting how many were left is returned, so the caller can exit anyway.
+    pub fn shutdown(&self, timeout: Duration) -> Result<(), PublisherError> {
+        let deadline = Instant::now() + timeout;
+
+        if let Some(aggregator) = &self.aggregator {
+            // publish the partial period rather than losing it
+            self.enqueue("", aggregator.flush()?, None)?;
+        }
+
+        self.shutdown.requested.store(true, Ordering::SeqCst);
+        let finished =
+            self.shutdown.finished.lock().map_err(|_| {
+                PublisherError::ShutdownError("shutdown lock was poisoned".to_string())
+            })?;
+        for _ in 0..self.shutdown.workers {
+            let now = Instant::now();
+            let remaining = if now < deadline {
+                deadline - now
+            } else {
+                Duration::from_secs(0)
+            };
+            match finished.recv_timeout(remaining) {
+                Ok(()) => (),
+                Err(RecvTimeoutError::Timeout) => {
+                    return Err(PublisherError::ShutdownTimeout(self.stats.queue_length()))
+                }
+                // every worker has already exited
+                Err(RecvTimeoutError::Disconnected) => break,
+            }
+        }
+        Ok(())
+    }
+}
+
+struct Worker {
+    receiver: Receiver<QueuedMessage>,
+    shutdown_requested: Arc<AtomicBool>,
+    finished: Sender<()>,
+    batch_size: usize,
+    stats: Arc<PublisherStats>,
+    sinks: Vec<Box<dyn ExportSink>>,
+    runs: ExportRuns,
+    failed_events: FailedEvents,
+    clock: Arc<dyn Clock>,
+}
+
+impl Worker {
+    fn run(mut self) {
+        while let Some(batch) = self.next_batch() {
+            self.stats
+                .queue_length
+                .fetch_sub(batch.len(), Ordering::SeqCst);
+
+            if self.send(&batch) {
+                self.stats
+                    .published
+                    .fetch_add(batch.len(), Ordering::SeqCst);
+            } else {
+                self.stats.failed.fetch_add(batch.len(), Ordering::SeqCst);
+            }
+        }
+
+        for sink in &mut self.sinks {
+            if let Err(err) = sink.close() {
+                error!("Unable to close {}: {}", sink.name(), err);
+            }
+        }
+
+        if self.finished.send(()).is_err() {
+            debug!("Publisher shutdown is no longer being waited on");
+        }
+    }
+
+    /// Waits for a message, then takes whatever else is already queued, up to the batch size.
+    ///
+    /// Returns `None` once every publisher handle has been dropped, or once shutdown has been
+    /// requested and the queue is empty.
+    fn next_batch(&self) -> Option<Vec<QueuedMessage>> {
+        let first = loop {
+            match self.receiver.recv_timeout(SHUTDOWN_POLL_INTERVAL) {
+                Ok(message) => break message,
+                Err(RecvTimeoutError::Timeout) => {
+                    if self.shutdown_requested.load(Ordering::SeqCst) {
+                        return None;
+                    }
+                }
+                Err(RecvTimeoutError::Disconnected) => return None,
+            }
+        };
+
+        let mut batch = vec![first];
+        while batch.len() < self.batch_size {
+            match self.receiver.try_recv() {
+                Ok(message) => batch.push(message),
+                Err(_) => break,
+            }
+        }
+        Some(batch)
+    }
+
+    /// Writes a batch of messages to every sink, even if one of them fails.
+    ///
+    /// The admin events of the messages given to a failing sink are recorded as failed. Returns
+    /// true if every sink accepted the batch.
+    fn send(&mut self, batch: &[QueuedMessage]) -> bool {
+        let mut sent = true;
+        // the error of the first sink that failed to accept each message
+        let mut failures: Vec<Option<String>> = vec![None; batch.len()];
+        for (id, sink) in self.sinks.iter_mut().enumerate() {
+            let started_at = self.clock.now();
+            let start = Instant::now();
+            let error = match sink.write(batch).and_then(|()| sink.flush()) {
+                Ok(()) => {
+                    info!("Wrote {} messages to {}", batch.len(), sink.name());
+                    None
+                }
+                Err(err) => {
+                    error!("{}: {}", sink.name(), err);
+                    sent = false;
+                    for (queued, failure) in batch.iter().zip(failures.iter_mut()) {
+                        if failure.is_none() && sink.selects(queued) {
+                            *failure = Some(format!("{}: {}", sink.name(), err));
+                        }
+                    }
+                    Some(err.to_string())
+                }
+            };
+            self.runs
+                .record(id, batch.len(), started_at, start.elapsed(), error);
+        }
+
+        for (queued, failure) in batch.iter().zip(failures) {
+            if let (Some(event), Some(err)) = (queued.source(), failure) {
+                self.failed_events.record_export_failure(
+                    event.clone(),
+                    queued.message().clone(),
+                    &err,
+                );
+            }
+        }
+        sent
+    }
+}
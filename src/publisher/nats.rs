@@ -0,0 +1,358 @@
+/*
+ * Copyright 2019 Walmart Inc.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * -----------------------------------------------------------------------------
+ */
+
+//! Publishes exported messages to a NATS server, for deployments that already run one.
+//!
+//! Only the parts of the NATS client protocol needed to publish are spoken: CONNECT, PUB and
+//! PING, whose PONG confirms the server has processed every message published before it.
+
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpStream;
+use std::time::Duration;
+
+use protobuf::Message as Msg;
+
+use super::sink::{topic_for, ExportSink, QueuedMessage};
+use super::PublisherError;
+
+/// time to wait for the NATS server to accept a connection or respond
+const NATS_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// scheme servers may be given with
+const NATS_SCHEME: &str = "nats://";
+
+struct Connection {
+    reader: BufReader<TcpStream>,
+    writer: TcpStream,
+}
+
+/// Publishes each message, as protobuf, on a subject.
+///
+/// The connection is opened on first use and again after a failure, trying each server in
+/// turn.
+pub struct NatsSink {
+    name: String,
+    servers: Vec<String>,
+    subject: String,
+    auth_token: Option<String>,
+    confirm: bool,
+    /// index of the server to try first
+    next_server: usize,
+    connection: Option<Connection>,
+}
+
+impl NatsSink {
+    pub fn new(
+        servers: &[String],
+        subject: &str,
+        auth_token: Option<String>,
+        confirm: bool,
+    ) -> Self {
+        NatsSink {
+            name: format!("NATS subject {}", subject),
+            servers: servers
+                .iter()
+                .map(|server| server.trim_start_matches(NATS_SCHEME).to_string())
+                .collect(),
+            subject: subject.to_string(),
+            auth_token,
+            confirm,
+            next_server: 0,
+            connection: None,
+        }
+    }
+
+    fn connect(&mut self) -> Result<Connection, PublisherError> {
+        let mut errors = Vec::new();
+        for _ in 0..self.servers.len() {
+            let server = self.servers[self.next_server].clone();
+            self.next_server = (self.next_server + 1) % self.servers.len();
+            match connect_to(&server, self.auth_token.as_ref().map(String::as_str)) {
+                Ok(connection) => {
+                    info!("Connected to NATS server {}", server);
+                    return Ok(connection);
+                }
+                Err(err) => errors.push(format!("{}: {}", server, err)),
+            }
+        }
+        Err(PublisherError::SinkError(format!(
+            "unable to connect to NATS: {}",
+            errors.join(", ")
+        )))
+    }
+}
+
+impl ExportSink for NatsSink {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn write(&mut self, batch: &[QueuedMessage]) -> Result<(), PublisherError> {
+        let mut frames = Vec::new();
+        for queued in batch {
+            let bytes = queued
+                .message()
+                .write_to_bytes()
+                .map_err(|err| PublisherError::SerializationError(err.to_string()))?;
+            frames.extend(
+                format!("PUB {} {}\r\n", topic_for(&self.subject, queued), bytes.len()).bytes(),
+            );
+            frames.extend(bytes);
+            frames.extend(b"\r\n");
+        }
+        if self.confirm {
+            frames.extend(b"PING\r\n");
+        }
+
+        let mut connection = match self.connection.take() {
+            Some(connection) => connection,
+            None => self.connect()?,
+        };
+        connection
+            .writer
+            .write_all(&frames)
+            .map_err(|err| PublisherError::SinkError(err.to_string()))?;
+        if self.confirm {
+            await_pong(&mut connection)?;
+        }
+        self.connection = Some(connection);
+        Ok(())
+    }
+}
+
+/// Opens a connection and introduces the client to the server.
+fn connect_to(server: &str, auth_token: Option<&str>) -> Result<Connection, String> {
+    let stream = TcpStream::connect(server).map_err(|err| err.to_string())?;
+    stream
+        .set_read_timeout(Some(NATS_TIMEOUT))
+        .and_then(|()| stream.set_write_timeout(Some(NATS_TIMEOUT)))
+        .map_err(|err| err.to_string())?;
+    let mut connection = Connection {
+        reader: BufReader::new(stream.try_clone().map_err(|err| err.to_string())?),
+        writer: stream,
+    };
+
+    // the server starts by describing itself
+    let info = read_line(&mut connection).map_err(|err| err.to_string())?;
+    if !info.starts_with("INFO") {
+        return Err(format!("unexpected greeting: {}", info));
+    }
+    let mut options = json!({
+        "verbose": false,
+        "pedantic": false,
+        "name": env!("CARGO_PKG_NAME"),
+        "version": env!("CARGO_PKG_VERSION"),
+        "lang": "rust",
+    });
+    if let Some(auth_token) = auth_token {
+        options["auth_token"] = json!(auth_token);
+    }
+    connection
+        .writer
+        .write_all(format!("CONNECT {}\r\nPING\r\n", options).as_bytes())
+        .map_err(|err| err.to_string())?;
+    await_pong(&mut connection).map_err(|err| err.to_string())?;
+    Ok(connection)
+}
+
+/// Reads until the server answers a PING, failing if it reports an error first.
+fn await_pong(connection: &mut Connection) -> Result<(), PublisherError> {
+    loop {
+        let line = read_line(connection)?;
+        if line == "PONG" {
+            return Ok(());
+        } else if line == "PING" {
+            // the server checks the client is alive
+            connection
+                .writer
+                .write_all(b"PONG\r\n")
+                .map_err(|err| PublisherError::SinkError(err.to_string()))?;
+        } else if line.starts_with("-ERR") {
+            return Err(PublisherError::SinkError(format!(
+                "NATS server reported {}",
+                line
+            )));
+        }
+    }
+}
+
+fn read_line(connection: &mut Connection) -> Result<String, PublisherError> {
+    let mut line = String::new();
+    match connection.reader.read_line(&mut line) {
+        Ok(0) => Err(PublisherError::SinkError(
+            "NATS server closed the connection".to_string(),
+        )),
+        Ok(_) => Ok(line.trim_end().to_string()),
+        Err(err) => Err(PublisherError::SinkError(err.to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::io::Read;
+    use std::net::TcpListener;
+    use std::sync::mpsc::{channel, Receiver};
+    use std::thread;
+
+    use protobuf::Message as Msg;
+    use serde_json::Value;
+
+    use crate::proto::pubsub::{Message, Message_MessageType, ProposalSubmit};
+
+    /// Serves one connection, answering each PING with the next reply until they run out.
+    ///
+    /// Returns the server's address and a receiver of everything the client wrote, sent once
+    /// the client closes the connection.
+    fn nats_server(replies: &'static [&'static str]) -> (String, Receiver<Vec<u8>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let (sender, receiver) = channel();
+        thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            stream
+                .write_all(b"INFO {\"max_payload\":1048576}\r\n")
+                .unwrap();
+            let mut replies = replies.iter();
+            let mut received = Vec::new();
+            loop {
+                let mut line = String::new();
+                if reader.read_line(&mut line).unwrap_or(0) == 0 {
+                    break;
+                }
+                received.extend(line.bytes());
+                if line.starts_with("PUB ") {
+                    let size: usize = line.trim_end().rsplit(' ').next().unwrap().parse().unwrap();
+                    let mut payload = vec![0; size + 2];
+                    reader.read_exact(&mut payload).unwrap();
+                    received.extend(payload);
+                } else if line == "PING\r\n" {
+                    if let Some(reply) = replies.next() {
+                        stream.write_all(reply.as_bytes()).unwrap();
+                    }
+                }
+            }
+            sender.send(received).unwrap();
+        });
+        (address, receiver)
+    }
+
+    fn submit(circuit_id: &str) -> QueuedMessage {
+        let mut submit = ProposalSubmit::new();
+        submit.set_circuit_id(circuit_id.to_string());
+        let mut message = Message::new();
+        message.set_field_type(Message_MessageType::PROPOSAL_SUBMIT);
+        message.set_message(submit.write_to_bytes().unwrap());
+        QueuedMessage::new(circuit_id, message, None)
+    }
+
+    /// Splits the CONNECT line and PING that open a connection from what follows them.
+    fn connect_options(received: &[u8]) -> (Value, &[u8]) {
+        let end = received
+            .windows(2)
+            .position(|bytes| bytes == b"\r\n")
+            .unwrap();
+        let line = std::str::from_utf8(&received[..end]).unwrap();
+        assert!(line.starts_with("CONNECT "), "unexpected line: {}", line);
+        let rest = &received[end + 2..];
+        assert!(rest.starts_with(b"PING\r\n"));
+        (
+            serde_json::from_str(&line["CONNECT ".len()..]).unwrap(),
+            &rest[b"PING\r\n".len()..],
+        )
+    }
+
+    #[test]
+    fn publishes_each_message_and_confirms_the_batch() {
+        let (address, received) = nats_server(&["PONG\r\n", "PONG\r\n"]);
+        let mut sink = NatsSink::new(
+            &[format!("nats://{}", address)],
+            "events.{type}",
+            Some("token".to_string()),
+            true,
+        );
+        let batch = vec![submit("01234-ABCDE"), submit("56789-FGHIJ")];
+
+        sink.write(&batch).unwrap();
+        drop(sink);
+
+        let received = received.recv().unwrap();
+        let (options, published) = connect_options(&received);
+        assert_eq!(options["auth_token"], "token");
+        assert_eq!(options["verbose"], false);
+        let mut expected = Vec::new();
+        for queued in &batch {
+            let bytes = queued.message().write_to_bytes().unwrap();
+            expected.extend(format!("PUB events.proposal_submit {}\r\n", bytes.len()).bytes());
+            expected.extend(bytes);
+            expected.extend(b"\r\n");
+        }
+        expected.extend(b"PING\r\n");
+        assert_eq!(published, &expected[..]);
+    }
+
+    #[test]
+    fn publishes_without_confirming_when_not_asked_to() {
+        let (address, received) = nats_server(&["PONG\r\n"]);
+        let mut sink = NatsSink::new(&[address], "events", None, false);
+        let batch = vec![submit("01234-ABCDE")];
+
+        sink.write(&batch).unwrap();
+        drop(sink);
+
+        let received = received.recv().unwrap();
+        let (options, published) = connect_options(&received);
+        assert_eq!(options.get("auth_token"), None);
+        let bytes = batch[0].message().write_to_bytes().unwrap();
+        let mut expected = format!("PUB events {}\r\n", bytes.len()).into_bytes();
+        expected.extend(bytes);
+        expected.extend(b"\r\n");
+        assert_eq!(published, &expected[..]);
+    }
+
+    #[test]
+    fn reports_an_error_instead_of_a_pong() {
+        let (address, received) =
+            nats_server(&["PONG\r\n", "-ERR 'Maximum Payload Violation'\r\n"]);
+        let mut sink = NatsSink::new(&[address], "events", None, true);
+
+        match sink.write(&[submit("01234-ABCDE")]) {
+            Err(PublisherError::SinkError(err)) => {
+                assert!(err.contains("Maximum Payload Violation"), "{}", err)
+            }
+            result => panic!("expected a sink error, got {:?}", result),
+        }
+        // the connection is dropped, to be opened again by the next write
+        assert!(sink.connection.is_none());
+        received.recv().unwrap();
+    }
+
+    #[test]
+    fn reports_an_error_on_connecting() {
+        let (address, _received) = nats_server(&["-ERR 'Authorization Violation'\r\n"]);
+        let mut sink = NatsSink::new(&[address], "events", Some("wrong".to_string()), false);
+
+        match sink.write(&[submit("01234-ABCDE")]) {
+            Err(PublisherError::SinkError(err)) => {
+                assert!(err.contains("Authorization Violation"), "{}", err)
+            }
+            result => panic!("expected a sink error, got {:?}", result),
+        }
+    }
+}
@@ -0,0 +1,344 @@
+/*
+ * Copyright 2019 Walmart Inc.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * -----------------------------------------------------------------------------
+ */
+
+//! Writes exported messages as JSON Lines, one record per line, for jq or log pipelines.
+
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::sync::{Arc, Mutex};
+
+use flate2::write::GzEncoder;
+
+use super::json::to_json;
+use super::mapping::FieldMapping;
+use super::sink::{ExportSink, QueuedMessage};
+use super::PublisherError;
+use crate::config::FileCompression;
+
+/// path that selects standard output instead of a file
+const STDOUT_PATH: &str = "-";
+
+/// level zstd compresses at unless configured otherwise
+const DEFAULT_ZSTD_LEVEL: i32 = 3;
+
+/// How each batch is compressed before it is written.
+///
+/// Every batch is compressed on its own, as a gzip member or zstd frame; a file of several is
+/// still read as a whole by zcat or zstdcat.
+#[derive(Clone, Copy)]
+pub enum Compression {
+    None,
+    Gzip(flate2::Compression),
+    Zstd(i32),
+}
+
+impl Compression {
+    pub fn new(compression: FileCompression, level: Option<u32>) -> Result<Self, PublisherError> {
+        match (compression, level) {
+            (FileCompression::None, _) => Ok(Compression::None),
+            (FileCompression::Gzip, None) => Ok(Compression::Gzip(flate2::Compression::default())),
+            (FileCompression::Gzip, Some(level)) if level <= 9 => {
+                Ok(Compression::Gzip(flate2::Compression::new(level)))
+            }
+            (FileCompression::Zstd, None) => Ok(Compression::Zstd(DEFAULT_ZSTD_LEVEL)),
+            (FileCompression::Zstd, Some(level)) if (1..=21).contains(&level) => {
+                Ok(Compression::Zstd(level as i32))
+            }
+            (_, Some(level)) => Err(PublisherError::StartUpError(format!(
+                "Invalid {:?} compression level {}",
+                compression, level
+            ))),
+        }
+    }
+
+    /// extension of the files written with this compression
+    fn extension(self) -> &'static str {
+        match self {
+            Compression::None => "",
+            Compression::Gzip(_) => ".gz",
+            Compression::Zstd(_) => ".zst",
+        }
+    }
+
+    fn compress(self, data: Vec<u8>) -> io::Result<Vec<u8>> {
+        match self {
+            Compression::None => Ok(data),
+            Compression::Gzip(level) => {
+                let mut encoder = GzEncoder::new(Vec::new(), level);
+                encoder.write_all(&data)?;
+                encoder.finish()
+            }
+            Compression::Zstd(level) => zstd::stream::encode_all(&data[..], level),
+        }
+    }
+}
+
+/// Appends each message as a JSON record on its own line.
+///
+/// The instances of every publisher worker writing to the same path share one output, which is
+/// locked while a batch is written, so batches never interleave.
+pub struct NdjsonSink {
+    name: String,
+    output: Arc<Mutex<Output>>,
+    fields: FieldMapping,
+    compression: Compression,
+    buffer: Vec<u8>,
+}
+
+enum Output {
+    Stdout,
+    File(File),
+}
+
+impl Output {
+    fn write_batch(&mut self, data: &[u8]) -> io::Result<()> {
+        match self {
+            Output::Stdout => {
+                let stdout = io::stdout();
+                let mut stdout = stdout.lock();
+                stdout.write_all(data).and_then(|()| stdout.flush())
+            }
+            Output::File(file) => file.write_all(data),
+        }
+    }
+}
+
+/// The outputs opened by ndjson sinks, by path, so every publisher worker's sink for a path
+/// writes through the same one. Clones share the same outputs.
+#[derive(Clone, Default)]
+pub struct NdjsonOutputs {
+    outputs: Arc<Mutex<HashMap<String, Arc<Mutex<Output>>>>>,
+}
+
+impl NdjsonOutputs {
+    /// Returns the output for the path, opening it for appending if it is not open yet.
+    fn open(&self, path: &str) -> Result<Arc<Mutex<Output>>, PublisherError> {
+        let mut outputs = self.outputs.lock().map_err(|_| {
+            PublisherError::StartUpError("ndjson outputs lock was poisoned".to_string())
+        })?;
+        if let Some(output) = outputs.get(path) {
+            return Ok(output.clone());
+        }
+        let output = if path == STDOUT_PATH {
+            Output::Stdout
+        } else {
+            Output::File(
+                OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(path)
+                    .map_err(|err| {
+                        PublisherError::StartUpError(format!("Unable to open {}: {}", path, err))
+                    })?,
+            )
+        };
+        let output = Arc::new(Mutex::new(output));
+        outputs.insert(path.to_string(), output.clone());
+        Ok(output)
+    }
+}
+
+impl NdjsonSink {
+    /// Opens the file at the given path for appending, creating it if needed, or shares the
+    /// output already opened for it in `outputs`; "-" writes to standard output. Records are
+    /// reshaped by the given field mapping. A compressed file is given the extension of its
+    /// compression if its path does not already end with it.
+    pub fn open(
+        path: &str,
+        fields: FieldMapping,
+        compression: Compression,
+        outputs: &NdjsonOutputs,
+    ) -> Result<Self, PublisherError> {
+        let mut path = path.to_string();
+        if path != STDOUT_PATH && !path.ends_with(compression.extension()) {
+            path.push_str(compression.extension());
+        }
+        Ok(NdjsonSink {
+            name: format!("JSON Lines file {}", path),
+            output: outputs.open(&path)?,
+            fields,
+            compression,
+            buffer: Vec::new(),
+        })
+    }
+}
+
+impl ExportSink for NdjsonSink {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn write(&mut self, batch: &[QueuedMessage]) -> Result<(), PublisherError> {
+        let mut lines = Vec::new();
+        for queued in batch {
+            let record = self.fields.apply(to_json(queued.message())?);
+            serde_json::to_writer(&mut lines, &record)
+                .map_err(|err| PublisherError::SerializationError(err.to_string()))?;
+            lines.push(b'\n');
+        }
+        self.buffer.extend(lines);
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), PublisherError> {
+        if self.buffer.is_empty() {
+            return Ok(());
+        }
+        // a failed write may have been partial, so the batch is not retried into the file
+        let data = self
+            .compression
+            .compress(self.buffer.split_off(0))
+            .map_err(|err| PublisherError::SinkError(err.to_string()))?;
+        let mut output = self
+            .output
+            .lock()
+            .map_err(|_| PublisherError::SinkError("output lock was poisoned".to_string()))?;
+        output
+            .write_batch(&data)
+            .map_err(|err| PublisherError::SinkError(err.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::fs;
+    use std::io::Read;
+    use std::path::PathBuf;
+
+    use protobuf::Message as Msg;
+    use uuid::Uuid;
+
+    use crate::proto::pubsub::{Message, Message_MessageType, ProposalSubmit};
+
+    fn temp_path(extension: &str) -> PathBuf {
+        std::env::temp_dir().join(format!(
+            "ndjson-{}{}",
+            Uuid::new_v4().to_simple(),
+            extension
+        ))
+    }
+
+    fn submit(circuit_id: &str) -> QueuedMessage {
+        let mut submit = ProposalSubmit::new();
+        submit.set_circuit_id(circuit_id.to_string());
+        let mut message = Message::new();
+        message.set_field_type(Message_MessageType::PROPOSAL_SUBMIT);
+        message.set_message(submit.write_to_bytes().unwrap());
+        QueuedMessage::new(circuit_id, message, None)
+    }
+
+    /// Writes one batch of `count` messages about the circuit through each sink.
+    fn write_batches(sinks: &mut [NdjsonSink], count: usize) {
+        for (id, sink) in sinks.iter_mut().enumerate() {
+            let batch = (0..count)
+                .map(|_| submit(&format!("circuit-{}", id)))
+                .collect::<Vec<_>>();
+            sink.write(&batch).unwrap();
+            sink.flush().unwrap();
+        }
+    }
+
+    /// Returns the circuit id of every line, checking that each is a whole record.
+    fn circuit_ids(lines: &str) -> Vec<String> {
+        lines
+            .lines()
+            .map(|line| {
+                let record: serde_json::Value = serde_json::from_str(line).unwrap();
+                record["circuit_id"].as_str().unwrap().to_string()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn sinks_for_the_same_path_share_one_output() {
+        let path = temp_path(".ndjson");
+        let outputs = NdjsonOutputs::default();
+        let open = || {
+            NdjsonSink::open(
+                path.to_str().unwrap(),
+                FieldMapping::default(),
+                Compression::None,
+                &outputs,
+            )
+            .unwrap()
+        };
+        let mut sinks = vec![open(), open()];
+        assert!(Arc::ptr_eq(&sinks[0].output, &sinks[1].output));
+
+        write_batches(&mut sinks, 3);
+        let lines = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(
+            circuit_ids(&lines),
+            vec![
+                "circuit-0",
+                "circuit-0",
+                "circuit-0",
+                "circuit-1",
+                "circuit-1",
+                "circuit-1"
+            ]
+        );
+    }
+
+    /// Writes two batches through sinks of two workers sharing the compressed path, and returns
+    /// the path of the file written.
+    fn write_compressed(compression: Compression) -> PathBuf {
+        let path = temp_path(compression.extension());
+        let outputs = NdjsonOutputs::default();
+        let open = || {
+            NdjsonSink::open(
+                path.to_str().unwrap(),
+                FieldMapping::default(),
+                compression,
+                &outputs,
+            )
+            .unwrap()
+        };
+        let mut sinks = vec![open(), open()];
+        write_batches(&mut sinks, 2);
+        path
+    }
+
+    #[test]
+    fn gzip_file_of_two_batches_decompresses_whole() {
+        let path = write_compressed(Compression::Gzip(flate2::Compression::default()));
+        let mut lines = String::new();
+        flate2::read::MultiGzDecoder::new(fs::File::open(&path).unwrap())
+            .read_to_string(&mut lines)
+            .unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(
+            circuit_ids(&lines),
+            vec!["circuit-0", "circuit-0", "circuit-1", "circuit-1"]
+        );
+    }
+
+    #[test]
+    fn zstd_file_of_two_batches_decompresses_whole() {
+        let path = write_compressed(Compression::Zstd(DEFAULT_ZSTD_LEVEL));
+        let data = zstd::stream::decode_all(fs::File::open(&path).unwrap()).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(
+            circuit_ids(&String::from_utf8(data).unwrap()),
+            vec!["circuit-0", "circuit-0", "circuit-1", "circuit-1"]
+        );
+    }
+}
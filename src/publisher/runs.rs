@@ -0,0 +1,228 @@
+/*
+ * Copyright 2019 Walmart Inc.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * -----------------------------------------------------------------------------
+ */
+
+//! A record of the batches written to each export sink, to spot stalled or failing sinks.
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use super::sink::ExportSink;
+
+/// The outcome of writing one batch to an export sink
+#[derive(Debug, Clone, Serialize)]
+pub struct ExportRun {
+    id: u64,
+    messages: usize,
+    succeeded: bool,
+    /// reason the sink failed to accept the batch
+    error: Option<String>,
+    duration_millis: u64,
+    /// Time the batch started being written, in seconds since the epoch
+    started_at: u64,
+}
+
+/// The totals of an export sink, over every publisher worker
+#[derive(Debug, Clone, Serialize)]
+pub struct ExportSinkSummary {
+    /// position of the sink in the configuration, the primary Kafka topic being 0
+    id: usize,
+    name: String,
+    runs: u64,
+    failed_runs: u64,
+    messages_exported: u64,
+    messages_failed: u64,
+    write_seconds: f64,
+    /// Time of the last batch the sink accepted, in seconds since the epoch
+    last_success_at: Option<u64>,
+    last_error: Option<String>,
+}
+
+impl ExportSinkSummary {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn runs(&self) -> u64 {
+        self.runs
+    }
+
+    pub fn messages_exported(&self) -> u64 {
+        self.messages_exported
+    }
+
+    pub fn messages_failed(&self) -> u64 {
+        self.messages_failed
+    }
+
+    pub fn write_seconds(&self) -> f64 {
+        self.write_seconds
+    }
+
+    pub fn last_success_at(&self) -> Option<u64> {
+        self.last_success_at
+    }
+}
+
+struct SinkHistory {
+    summary: ExportSinkSummary,
+    next_run_id: u64,
+    /// runs, oldest first
+    runs: VecDeque<ExportRun>,
+}
+
+struct Runs {
+    sinks: Vec<SinkHistory>,
+    capacity: usize,
+}
+
+/// Keeps the totals and most recent runs of every export sink.
+///
+/// Every publisher worker has its own instance of each sink; the runs of the instances at the
+/// same position are recorded together. Clones share the same runs.
+#[derive(Clone)]
+pub struct ExportRuns {
+    inner: Arc<Mutex<Runs>>,
+}
+
+impl ExportRuns {
+    /// Creates a store keeping up to `capacity` runs per sink; a zero capacity keeps none, but
+    /// the totals are still counted.
+    pub fn new(capacity: usize) -> Self {
+        ExportRuns {
+            inner: Arc::new(Mutex::new(Runs {
+                sinks: Vec::new(),
+                capacity,
+            })),
+        }
+    }
+
+    /// Adds the sinks not known yet, by position.
+    pub fn register(&self, sinks: &[Box<dyn ExportSink>]) {
+        match self.inner.lock() {
+            Ok(mut inner) => {
+                for (id, sink) in sinks.iter().enumerate().skip(inner.sinks.len()) {
+                    inner.sinks.push(SinkHistory {
+                        summary: ExportSinkSummary {
+                            id,
+                            name: sink.name().to_string(),
+                            runs: 0,
+                            failed_runs: 0,
+                            messages_exported: 0,
+                            messages_failed: 0,
+                            write_seconds: 0.0,
+                            last_success_at: None,
+                            last_error: None,
+                        },
+                        next_run_id: 0,
+                        runs: VecDeque::new(),
+                    });
+                }
+            }
+            Err(_) => error!("Export runs lock was poisoned"),
+        }
+    }
+
+    /// Returns the totals of every sink.
+    pub fn summaries(&self) -> Vec<ExportSinkSummary> {
+        match self.inner.lock() {
+            Ok(inner) => inner
+                .sinks
+                .iter()
+                .map(|sink| sink.summary.clone())
+                .collect(),
+            Err(_) => {
+                error!("Export runs lock was poisoned");
+                Vec::new()
+            }
+        }
+    }
+
+    /// Returns the runs of the sink with the given id, oldest first, or `None` if there is no
+    /// such sink.
+    pub fn runs(&self, sink_id: usize) -> Option<Vec<ExportRun>> {
+        match self.inner.lock() {
+            Ok(inner) => inner
+                .sinks
+                .get(sink_id)
+                .map(|sink| sink.runs.iter().cloned().collect()),
+            Err(_) => {
+                error!("Export runs lock was poisoned");
+                None
+            }
+        }
+    }
+
+    /// Records a batch of the given size having been written to the sink with the given id.
+    pub fn record(
+        &self,
+        sink_id: usize,
+        messages: usize,
+        started_at: SystemTime,
+        duration: Duration,
+        error: Option<String>,
+    ) {
+        let mut inner = match self.inner.lock() {
+            Ok(inner) => inner,
+            Err(_) => {
+                error!("Export runs lock was poisoned, run not recorded");
+                return;
+            }
+        };
+        let capacity = inner.capacity;
+        let sink = match inner.sinks.get_mut(sink_id) {
+            Some(sink) => sink,
+            None => return,
+        };
+        let started_at = started_at
+            .duration_since(UNIX_EPOCH)
+            .map(|since_epoch| since_epoch.as_secs())
+            .unwrap_or_default();
+
+        let summary = &mut sink.summary;
+        summary.runs += 1;
+        summary.write_seconds +=
+            duration.as_secs() as f64 + f64::from(duration.subsec_nanos()) / 1_000_000_000.0;
+        match &error {
+            None => {
+                summary.messages_exported += messages as u64;
+                summary.last_success_at = Some(started_at);
+            }
+            Some(error) => {
+                summary.failed_runs += 1;
+                summary.messages_failed += messages as u64;
+                summary.last_error = Some(error.clone());
+            }
+        }
+
+        if capacity == 0 {
+            return;
+        }
+        if sink.runs.len() == capacity {
+            sink.runs.pop_front();
+        }
+        sink.runs.push_back(ExportRun {
+            id: sink.next_run_id,
+            messages,
+            succeeded: error.is_none(),
+            error,
+            duration_millis: duration.as_secs() * 1000 + u64::from(duration.subsec_millis()),
+            started_at,
+        });
+        sink.next_run_id += 1;
+    }
+}
@@ -0,0 +1,339 @@
+/*
+ * Copyright 2019 Walmart Inc.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * -----------------------------------------------------------------------------
+ */
+
+//! The destinations exported messages are written to.
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use protobuf::ProtobufEnum;
+use splinter::admin::messages::AdminServiceEvent;
+
+use super::avro::AvroEncoder;
+use super::elasticsearch::{ElasticsearchSettings, ElasticsearchSink};
+use super::json::type_name;
+use super::kafka::KafkaSink;
+use super::mapping::FieldMapping;
+use super::nats::NatsSink;
+use super::ndjson::{Compression, NdjsonOutputs, NdjsonSink};
+use super::webhook::{WebhookDeliveries, WebhookSettings, WebhookSink};
+use super::PublisherError;
+use crate::clock::Clock;
+use crate::config::{DeploymentConfig, ExportSinkConfig, KafkaAcks, MessageFilter, SinkConfig};
+use crate::event_handler::{EventFilter, FailedEvents};
+use crate::proto::pubsub::{Message, Message_MessageType};
+
+/// A message waiting to be exported, with the id of the circuit it concerns.
+#[derive(Clone)]
+pub struct QueuedMessage {
+    circuit_id: String,
+    message: Message,
+    source: Option<Arc<AdminServiceEvent>>,
+}
+
+impl QueuedMessage {
+    pub fn new(circuit_id: &str, message: Message, source: Option<Arc<AdminServiceEvent>>) -> Self {
+        QueuedMessage {
+            circuit_id: circuit_id.to_string(),
+            message,
+            source,
+        }
+    }
+
+    /// Empty for messages about no particular circuit, such as activity summaries.
+    pub fn circuit_id(&self) -> &str {
+        &self.circuit_id
+    }
+
+    pub fn message(&self) -> &Message {
+        &self.message
+    }
+
+    /// The admin event the message was made from, if any.
+    pub fn source(&self) -> Option<&AdminServiceEvent> {
+        self.source.as_ref().map(AsRef::as_ref)
+    }
+}
+
+/// placeholder in a topic or subject replaced by the type of each message, such as
+/// "proposal_vote"
+const TYPE_PLACEHOLDER: &str = "{type}";
+
+/// Returns the topic a message is written to, given the configured topic name.
+pub fn topic_for(topic: &str, queued: &QueuedMessage) -> String {
+    topic.replace(
+        TYPE_PLACEHOLDER,
+        &type_name(queued.message().get_field_type()),
+    )
+}
+
+/// A destination for exported messages.
+///
+/// Every publisher worker holds its own instance of each sink, so a sink is only used from one
+/// thread and is given the messages of a circuit in order. Instances writing to a resource they
+/// cannot each open on their own, such as a file, share it between workers.
+pub trait ExportSink: Send {
+    /// Names the sink in logs.
+    fn name(&self) -> &str;
+
+    /// Writes a batch of messages; the sink may buffer them until it is flushed.
+    fn write(&mut self, batch: &[QueuedMessage]) -> Result<(), PublisherError>;
+
+    /// Returns true if the sink writes the message when given it, rather than leaving it out.
+    fn selects(&self, _queued: &QueuedMessage) -> bool {
+        true
+    }
+
+    /// Makes the messages written so far durable.
+    fn flush(&mut self) -> Result<(), PublisherError> {
+        Ok(())
+    }
+
+    /// Flushes the sink and releases its resources; the sink is not used again.
+    fn close(&mut self) -> Result<(), PublisherError> {
+        self.flush()
+    }
+}
+
+/// Creates one instance of every configured sink: the kafka_topic on kafka_url first, then each
+/// of export_sinks. Webhook sinks record their deliveries in `deliveries`, and the admin events
+/// whose messages they fail to deliver in `failed_events`; ndjson sinks share the files opened
+/// in `ndjson_outputs`. The messages about a circuit that `filter` routes to an export sink are
+/// only passed to that sink, as decided by the rules in force when the message is written.
+pub fn build_sinks(
+    config: &DeploymentConfig,
+    filter: &EventFilter,
+    deliveries: &WebhookDeliveries,
+    failed_events: &FailedEvents,
+    ndjson_outputs: &NdjsonOutputs,
+    clock: &Arc<dyn Clock>,
+) -> Result<Vec<Box<dyn ExportSink>>, PublisherError> {
+    let mut sinks: Vec<Box<dyn ExportSink>> = vec![Box::new(FilteredSink::new(
+        None,
+        filter.clone(),
+        MessageFilter::default(),
+        Box::new(KafkaSink::new(
+            &[config.kafka_url().to_string()],
+            config.kafka_topic(),
+            KafkaAcks::default(),
+            None,
+        )),
+    )?)];
+    for sink_config in config.export_sinks() {
+        let sink = build_sink(
+            sink_config.sink(),
+            deliveries,
+            failed_events,
+            ndjson_outputs,
+            clock,
+        )?;
+        sinks.push(Box::new(FilteredSink::new(
+            sink_config.name().map(ToOwned::to_owned),
+            filter.clone(),
+            sink_config.filter().clone(),
+            sink,
+        )?));
+    }
+    Ok(sinks)
+}
+
+/// Passes a sink only the messages its filter selects, leaving out those the event filter's
+/// current rules route to another sink.
+struct FilteredSink {
+    /// the name messages are routed to the sink by
+    route_name: Option<String>,
+    event_filter: EventFilter,
+    filter: MessageFilter,
+    sink: Box<dyn ExportSink>,
+}
+
+impl FilteredSink {
+    fn new(
+        route_name: Option<String>,
+        event_filter: EventFilter,
+        filter: MessageFilter,
+        sink: Box<dyn ExportSink>,
+    ) -> Result<Self, PublisherError> {
+        if let Some(unknown) = unknown_event_type(&filter) {
+            return Err(PublisherError::StartUpError(format!(
+                "Unknown event type {} in the filter of {}",
+                unknown,
+                sink.name()
+            )));
+        }
+        Ok(FilteredSink {
+            route_name,
+            event_filter,
+            filter,
+            sink,
+        })
+    }
+}
+
+impl ExportSink for FilteredSink {
+    fn name(&self) -> &str {
+        self.sink.name()
+    }
+
+    fn selects(&self, queued: &QueuedMessage) -> bool {
+        let route = self.event_filter.route_of(queued.circuit_id());
+        (route.is_none() || route == self.route_name)
+            && self.filter.matches(
+                &type_name(queued.message().get_field_type()),
+                queued.circuit_id(),
+            )
+    }
+
+    fn write(&mut self, batch: &[QueuedMessage]) -> Result<(), PublisherError> {
+        if batch.iter().all(|queued| self.selects(queued)) {
+            return self.sink.write(batch);
+        }
+        let selected = batch
+            .iter()
+            .filter(|queued| self.selects(queued))
+            .cloned()
+            .collect::<Vec<_>>();
+        if selected.is_empty() {
+            return Ok(());
+        }
+        self.sink.write(&selected)
+    }
+
+    fn flush(&mut self) -> Result<(), PublisherError> {
+        self.sink.flush()
+    }
+
+    fn close(&mut self) -> Result<(), PublisherError> {
+        self.sink.close()
+    }
+}
+
+/// Checks the configuration of an export sink without connecting to it: the event types it
+/// selects must exist and its field paths must be valid.
+pub fn check_sink_config(sink_config: &ExportSinkConfig) -> Result<(), String> {
+    if let Some(unknown) = unknown_event_type(sink_config.filter()) {
+        return Err(format!("Unknown event type {}", unknown));
+    }
+    match sink_config.sink() {
+        SinkConfig::Elasticsearch { fields, .. }
+        | SinkConfig::Ndjson { fields, .. }
+        | SinkConfig::Webhook { fields, .. } => FieldMapping::new(fields).map(|_| ()),
+        SinkConfig::Kafka { .. } | SinkConfig::Nats { .. } => Ok(()),
+    }
+}
+
+/// Returns the first of the filter's event types that is not the type of any message.
+fn unknown_event_type(filter: &MessageFilter) -> Option<&String> {
+    let known_types = Message_MessageType::values()
+        .iter()
+        .map(|message_type| type_name(*message_type))
+        .collect::<Vec<_>>();
+    filter
+        .event_types()
+        .iter()
+        .find(|event_type| !known_types.contains(event_type))
+}
+
+fn build_sink(
+    config: &SinkConfig,
+    deliveries: &WebhookDeliveries,
+    failed_events: &FailedEvents,
+    ndjson_outputs: &NdjsonOutputs,
+    clock: &Arc<dyn Clock>,
+) -> Result<Box<dyn ExportSink>, PublisherError> {
+    match config {
+        SinkConfig::Kafka {
+            brokers,
+            topic,
+            required_acks,
+            schema_registry_url,
+        } => Ok(Box::new(KafkaSink::new(
+            brokers,
+            topic,
+            *required_acks,
+            schema_registry_url
+                .as_ref()
+                .map(|url| AvroEncoder::new(url))
+                .transpose()?,
+        ))),
+        SinkConfig::Elasticsearch {
+            url,
+            index,
+            rollover,
+            username,
+            password,
+            fields,
+        } => Ok(Box::new(ElasticsearchSink::new(
+            ElasticsearchSettings {
+                url: url.clone(),
+                index: index.clone(),
+                rollover: *rollover,
+                username: username.clone(),
+                password: password.clone(),
+                fields: field_mapping(fields)?,
+            },
+            clock.clone(),
+        )?)),
+        SinkConfig::Ndjson {
+            path,
+            fields,
+            compression,
+            compression_level,
+        } => Ok(Box::new(NdjsonSink::open(
+            path,
+            field_mapping(fields)?,
+            Compression::new(*compression, *compression_level)?,
+            ndjson_outputs,
+        )?)),
+        SinkConfig::Nats {
+            servers,
+            subject,
+            auth_token,
+            confirm,
+        } => Ok(Box::new(NatsSink::new(
+            servers,
+            subject,
+            auth_token.clone(),
+            *confirm,
+        ))),
+        SinkConfig::Webhook {
+            url,
+            secret,
+            retry_limit,
+            retry_backoff_millis,
+            queue_depth,
+            fields,
+        } => Ok(Box::new(WebhookSink::new(
+            WebhookSettings {
+                url: url.clone(),
+                secret: secret.clone(),
+                retry_limit: *retry_limit,
+                retry_backoff: Duration::from_millis(*retry_backoff_millis),
+                queue_depth: *queue_depth,
+                fields: field_mapping(fields)?,
+            },
+            deliveries.clone(),
+            failed_events.clone(),
+            clock.clone(),
+        )?)),
+    }
+}
+
+fn field_mapping(fields: &BTreeMap<String, String>) -> Result<FieldMapping, PublisherError> {
+    FieldMapping::new(fields).map_err(PublisherError::StartUpError)
+}
@@ -0,0 +1,408 @@
+/*
+ * Copyright 2019 Walmart Inc.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * -----------------------------------------------------------------------------
+ */
+
+//! Posts exported messages to webhook endpoints, signed so receivers can authenticate them.
+
+use std::collections::VecDeque;
+use std::sync::mpsc::{sync_channel, Receiver, SyncSender, TrySendError};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use crypto::hmac::Hmac;
+use crypto::mac::Mac;
+use crypto::sha2::Sha256;
+use hyper::header::CONTENT_TYPE;
+use hyper::{Body, Request, Uri};
+
+use super::http::BlockingClient;
+use super::json::{to_json, type_name};
+use super::mapping::FieldMapping;
+use super::sink::{ExportSink, QueuedMessage};
+use super::PublisherError;
+use crate::clock::Clock;
+use crate::event_handler::{to_hex, FailedEvents};
+
+/// header carrying the hex HMAC-SHA256 of the timestamp, a "." and the request body, prefixed
+/// with "sha256="
+pub const SIGNATURE_HEADER: &str = "X-Signature-256";
+
+/// header carrying the time the request was signed, in seconds since the epoch, so receivers
+/// can refuse replayed requests
+pub const TIMESTAMP_HEADER: &str = "X-Signature-Timestamp";
+
+/// Caps the exponential backoff between delivery retries at 2^10 times the base delay
+const MAX_BACKOFF_EXPONENT: u32 = 10;
+
+/// The outcome of posting one message to a webhook endpoint
+#[derive(Debug, Clone, Serialize)]
+pub struct WebhookDelivery {
+    id: u64,
+    url: String,
+    event_type: String,
+    circuit_id: String,
+    delivered: bool,
+    attempts: u32,
+    /// status of the last response, if the endpoint responded
+    status: Option<u16>,
+    /// reason the last attempt failed
+    error: Option<String>,
+    /// Time of the last attempt, in seconds since the epoch
+    attempted_at: u64,
+}
+
+struct Deliveries {
+    next_id: u64,
+    /// deliveries, oldest first
+    deliveries: VecDeque<WebhookDelivery>,
+    capacity: usize,
+}
+
+/// Keeps the most recent webhook deliveries of every webhook sink.
+///
+/// Clones share the same deliveries.
+#[derive(Clone)]
+pub struct WebhookDeliveries {
+    inner: Arc<Mutex<Deliveries>>,
+}
+
+impl WebhookDeliveries {
+    /// Creates a store keeping up to `capacity` deliveries; a zero capacity keeps none.
+    pub fn new(capacity: usize) -> Self {
+        WebhookDeliveries {
+            inner: Arc::new(Mutex::new(Deliveries {
+                next_id: 0,
+                deliveries: VecDeque::new(),
+                capacity,
+            })),
+        }
+    }
+
+    /// Returns the deliveries, oldest first.
+    pub fn list(&self) -> Vec<WebhookDelivery> {
+        match self.inner.lock() {
+            Ok(inner) => inner.deliveries.iter().cloned().collect(),
+            Err(_) => {
+                error!("Webhook deliveries lock was poisoned");
+                Vec::new()
+            }
+        }
+    }
+
+    fn record(&self, mut delivery: WebhookDelivery) {
+        match self.inner.lock() {
+            Ok(mut inner) => {
+                if inner.capacity == 0 {
+                    return;
+                }
+                delivery.id = inner.next_id;
+                inner.next_id += 1;
+                if inner.deliveries.len() == inner.capacity {
+                    inner.deliveries.pop_front();
+                }
+                inner.deliveries.push_back(delivery);
+            }
+            Err(_) => error!("Webhook deliveries lock was poisoned"),
+        }
+    }
+}
+
+/// Where and how a webhook sink delivers messages
+pub struct WebhookSettings {
+    pub url: String,
+    pub secret: String,
+    pub retry_limit: u32,
+    /// delay before the first retry, doubled on each attempt up to 2^10 times
+    pub retry_backoff: Duration,
+    /// number of messages that may wait to be delivered
+    pub queue_depth: usize,
+    /// reshapes the records posted
+    pub fields: FieldMapping,
+}
+
+/// Posts each message as a JSON record, retrying failed deliveries.
+///
+/// Messages are handed to a delivery thread of the sink's own, which makes the retries, so an
+/// unreachable endpoint does not hold up the publisher worker and the other sinks. Once the
+/// delivery queue is full, further messages fail without being posted.
+pub struct WebhookSink {
+    name: String,
+    url: String,
+    sender: Option<SyncSender<QueuedMessage>>,
+    deliverer: Option<JoinHandle<()>>,
+    deliveries: WebhookDeliveries,
+    clock: Arc<dyn Clock>,
+}
+
+impl WebhookSink {
+    pub fn new(
+        settings: WebhookSettings,
+        deliveries: WebhookDeliveries,
+        failed_events: FailedEvents,
+        clock: Arc<dyn Clock>,
+    ) -> Result<Self, PublisherError> {
+        let uri = settings.url.parse::<Uri>().map_err(|err| {
+            PublisherError::StartUpError(format!("Invalid webhook URL {}: {}", settings.url, err))
+        })?;
+        let name = format!("webhook {}", settings.url);
+        let url = settings.url.clone();
+        let (sender, receiver) = sync_channel(settings.queue_depth);
+        let deliverer = Deliverer {
+            name: name.clone(),
+            uri,
+            client: BlockingClient::new()?,
+            deliveries: deliveries.clone(),
+            failed_events,
+            settings,
+            clock: clock.clone(),
+        };
+        let deliverer = thread::Builder::new()
+            .name("Webhook".into())
+            .spawn(move || deliverer.run(receiver))
+            .map_err(|err| PublisherError::StartUpError(err.to_string()))?;
+        Ok(WebhookSink {
+            name,
+            url,
+            sender: Some(sender),
+            deliverer: Some(deliverer),
+            deliveries,
+            clock,
+        })
+    }
+
+    /// Records a message that could not be handed to the delivery thread.
+    fn not_queued(&self, queued: &QueuedMessage, error: &str) {
+        self.deliveries.record(WebhookDelivery {
+            id: 0,
+            url: self.url.clone(),
+            event_type: type_name(queued.message().get_field_type()),
+            circuit_id: queued.circuit_id().to_string(),
+            delivered: false,
+            attempts: 0,
+            status: None,
+            error: Some(error.to_string()),
+            attempted_at: self.clock.now_secs(),
+        });
+    }
+}
+
+impl ExportSink for WebhookSink {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Queues every message for delivery, even after one of them cannot be queued.
+    ///
+    /// Fails only for the messages left out because the delivery queue is full; the outcome of
+    /// each delivery is recorded in the webhook deliveries.
+    fn write(&mut self, batch: &[QueuedMessage]) -> Result<(), PublisherError> {
+        let sender = match &self.sender {
+            Some(sender) => sender,
+            None => return Err(PublisherError::SinkError("the sink is closed".to_string())),
+        };
+        let mut failures = 0;
+        let mut last_error = "";
+        for queued in batch {
+            let error = match sender.try_send(queued.clone()) {
+                Ok(()) => continue,
+                Err(TrySendError::Full(_)) => "the delivery queue is full",
+                Err(TrySendError::Disconnected(_)) => "the delivery thread has stopped",
+            };
+            self.not_queued(queued, error);
+            failures += 1;
+            last_error = error;
+        }
+        if failures == 0 {
+            Ok(())
+        } else {
+            Err(PublisherError::SinkError(format!(
+                "{} messages not delivered: {}",
+                failures, last_error
+            )))
+        }
+    }
+
+    /// Waits for the messages already queued to be delivered or to fail.
+    fn close(&mut self) -> Result<(), PublisherError> {
+        // the delivery thread stops once the queue is empty and the sender is dropped
+        self.sender.take();
+        match self.deliverer.take() {
+            Some(deliverer) => deliverer
+                .join()
+                .map_err(|_| PublisherError::SinkError("the delivery thread panicked".to_string())),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Delivers the messages queued by a webhook sink, one at a time, in order.
+struct Deliverer {
+    name: String,
+    uri: Uri,
+    settings: WebhookSettings,
+    client: BlockingClient,
+    deliveries: WebhookDeliveries,
+    failed_events: FailedEvents,
+    clock: Arc<dyn Clock>,
+}
+
+impl Deliverer {
+    fn run(mut self, receiver: Receiver<QueuedMessage>) {
+        for queued in receiver {
+            let event_type = type_name(queued.message().get_field_type());
+            if let Err(err) = self.deliver(&queued, event_type) {
+                error!("{}: {}", self.name, err);
+                // the worker has already counted the message as published, so the event is
+                // recorded here to be retried
+                if let Some(event) = queued.source() {
+                    self.failed_events.record_export_failure(
+                        event.clone(),
+                        queued.message().clone(),
+                        &format!("{}: {}", self.name, err),
+                    );
+                }
+            }
+        }
+    }
+
+    /// Signs and posts the body once, returning the response status if it is a success.
+    fn post(&mut self, body: &[u8]) -> Result<u16, (Option<u16>, String)> {
+        let timestamp = self.clock.now_secs();
+        let signature = sign(&self.settings.secret, timestamp, body);
+        let request = Request::post(self.uri.clone())
+            .header(CONTENT_TYPE, "application/json")
+            .header(SIGNATURE_HEADER, format!("sha256={}", signature))
+            .header(TIMESTAMP_HEADER, timestamp.to_string())
+            .body(Body::from(body.to_vec()))
+            .map_err(|err| (None, err.to_string()))?;
+        let (status, _) = self.client.send(request).map_err(|err| (None, err))?;
+        if status.is_success() {
+            Ok(status.as_u16())
+        } else {
+            Err((Some(status.as_u16()), format!("responded with {}", status)))
+        }
+    }
+
+    /// Posts a message, retrying with exponential backoff, and records the outcome.
+    fn deliver(&mut self, queued: &QueuedMessage, event_type: String) -> Result<(), String> {
+        let record = to_json(queued.message()).map_err(|err| err.to_string())?;
+        let record = self.settings.fields.apply(record);
+        let body = serde_json::to_vec(&record).map_err(|err| err.to_string())?;
+
+        let mut attempts = 0;
+        let result = loop {
+            attempts += 1;
+            let err = match self.post(&body) {
+                Ok(status) => break Ok(status),
+                Err(err) => err,
+            };
+            if attempts > self.settings.retry_limit {
+                break Err(err);
+            }
+            let backoff =
+                self.settings.retry_backoff * 2u32.pow((attempts - 1).min(MAX_BACKOFF_EXPONENT));
+            warn!(
+                "Delivery to {} failed, retrying in {:?}: {}",
+                self.name, backoff, err.1
+            );
+            thread::sleep(backoff);
+        };
+
+        let (status, error) = match &result {
+            Ok(status) => (Some(*status), None),
+            Err((status, err)) => (*status, Some(err.clone())),
+        };
+        self.deliveries.record(WebhookDelivery {
+            id: 0,
+            url: self.settings.url.clone(),
+            event_type,
+            circuit_id: queued.circuit_id().to_string(),
+            delivered: result.is_ok(),
+            attempts,
+            status,
+            error,
+            attempted_at: self.clock.now_secs(),
+        });
+        result.map(|_| ()).map_err(|(_, err)| err)
+    }
+}
+
+/// Returns the hex HMAC-SHA256 of the timestamp, a "." and the body, keyed with the webhook's
+/// secret.
+fn sign(secret: &str, timestamp: u64, body: &[u8]) -> String {
+    let mut hmac = Hmac::new(Sha256::new(), secret.as_bytes());
+    hmac.input(timestamp.to_string().as_bytes());
+    hmac.input(b".");
+    hmac.input(body);
+    to_hex(hmac.result().code())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::time::Instant;
+
+    use crate::clock::SystemClock;
+    use crate::proto::pubsub::Message;
+
+    #[test]
+    fn sign_covers_the_timestamp_and_the_body() {
+        assert_eq!(
+            sign("secret", 1_600_000_000, br#"{"type":"proposal_vote"}"#),
+            "0da292a81b78f7fa9a4063d579397b559447754fa819db2abbeaf1d14f0cab80"
+        );
+    }
+
+    #[test]
+    fn sign_depends_on_the_timestamp() {
+        let body = br#"{"type":"proposal_vote"}"#;
+        assert_ne!(
+            sign("secret", 1_600_000_000, body),
+            sign("secret", 1_600_000_001, body)
+        );
+    }
+
+    #[test]
+    fn write_queues_without_waiting_for_retries() {
+        let deliveries = WebhookDeliveries::new(10);
+        let mut sink = WebhookSink::new(
+            WebhookSettings {
+                // nothing listens on the discard port, so every delivery is retried
+                url: "http://127.0.0.1:9/events".to_string(),
+                secret: "secret".to_string(),
+                retry_limit: 3,
+                retry_backoff: Duration::from_secs(60),
+                queue_depth: 1,
+                fields: FieldMapping::default(),
+            },
+            deliveries.clone(),
+            FailedEvents::new(10, Arc::new(SystemClock)),
+            Arc::new(SystemClock),
+        )
+        .unwrap();
+        let batch = vec![QueuedMessage::new("circuit", Message::new(), None); 3];
+
+        let start = Instant::now();
+        assert!(sink.write(&batch).is_err());
+        assert!(start.elapsed() < Duration::from_secs(60));
+        assert!(deliveries
+            .list()
+            .iter()
+            .any(|delivery| !delivery.delivered && delivery.attempts == 0));
+    }
+}
@@ -0,0 +1,200 @@
+/*
+ * Copyright 2019 Walmart Inc.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * -----------------------------------------------------------------------------
+ */
+
+//! API key authentication for programmatic clients of the REST API.
+
+use std::collections::BTreeMap;
+use std::sync::{Arc, RwLock};
+
+use actix_web::dev::Payload;
+use actix_web::{error, web, Error, FromRequest, HttpRequest};
+use crypto::digest::Digest;
+use crypto::sha2::Sha256;
+use uuid::Uuid;
+
+use crate::config::{ApiKeyConfig, Role};
+use crate::rest_api::AppState;
+
+pub const API_KEY_HEADER: &str = "X-Api-Key";
+
+/// name reported for requests when API keys are not enabled
+const ANONYMOUS: &str = "anonymous";
+
+struct StoredKey {
+    /// SHA-256 hash of the key
+    hash: String,
+    role: Role,
+}
+
+/// The API keys accepted by the REST API, by name.
+///
+/// Authentication is enabled when keys are configured. Keys created or revoked at runtime last
+/// until the event listener restarts. Clones share the same keys.
+#[derive(Clone)]
+pub struct ApiKeyStore {
+    enabled: bool,
+    keys: Arc<RwLock<BTreeMap<String, StoredKey>>>,
+}
+
+impl ApiKeyStore {
+    pub fn new(api_keys: &[ApiKeyConfig]) -> Self {
+        let keys = api_keys
+            .iter()
+            .map(|key| {
+                (
+                    key.name().to_string(),
+                    StoredKey {
+                        hash: key.key_sha256().to_lowercase(),
+                        role: key.role(),
+                    },
+                )
+            })
+            .collect();
+        ApiKeyStore {
+            enabled: !api_keys.is_empty(),
+            keys: Arc::new(RwLock::new(keys)),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Returns the name and role of the given key, if it is accepted.
+    pub fn authenticate(&self, key: &str) -> Option<(String, Role)> {
+        let hash = sha256(key);
+        self.keys.read().ok().and_then(|keys| {
+            keys.iter()
+                .find(|(_, stored)| stored.hash == hash)
+                .map(|(name, stored)| (name.clone(), stored.role))
+        })
+    }
+
+    /// Returns the name and role of every key.
+    pub fn roles(&self) -> BTreeMap<String, Role> {
+        self.keys
+            .read()
+            .map(|keys| {
+                keys.iter()
+                    .map(|(name, stored)| (name.clone(), stored.role))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Generates a new key with the given name and role, replacing any key with that name, and
+    /// returns it. Only its hash is kept.
+    pub fn create(&self, name: &str, role: Role) -> Result<String, String> {
+        let key = format!(
+            "{}{}",
+            Uuid::new_v4().to_simple(),
+            Uuid::new_v4().to_simple()
+        );
+        let mut keys = self
+            .keys
+            .write()
+            .map_err(|_| "API key lock was poisoned".to_string())?;
+        keys.insert(
+            name.to_string(),
+            StoredKey {
+                hash: sha256(&key),
+                role,
+            },
+        );
+        Ok(key)
+    }
+
+    /// Revokes the key with the given name; returns false if there is none.
+    pub fn revoke(&self, name: &str) -> Result<bool, String> {
+        let mut keys = self
+            .keys
+            .write()
+            .map_err(|_| "API key lock was poisoned".to_string())?;
+        Ok(keys.remove(name).is_some())
+    }
+}
+
+fn sha256(key: &str) -> String {
+    let mut sha = Sha256::new();
+    sha.input_str(key);
+    sha.result_str()
+}
+
+/// The client authenticated by the X-Api-Key header of a request.
+///
+/// Taking it as a handler argument rejects requests without a valid key with 401, unless API
+/// keys are not enabled, in which case every request is treated as coming from an admin.
+pub struct ApiKey {
+    name: String,
+    role: Role,
+    authenticated: bool,
+}
+
+impl ApiKey {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns false if API keys are not enabled and the client is anonymous.
+    pub fn is_authenticated(&self) -> bool {
+        self.authenticated
+    }
+
+    /// Fails with 403 unless the client has at least the given role.
+    pub fn require(&self, role: Role) -> Result<(), Error> {
+        if self.role >= role {
+            Ok(())
+        } else {
+            Err(error::ErrorForbidden(format!(
+                "This request requires the {:?} role",
+                role
+            )))
+        }
+    }
+}
+
+impl FromRequest for ApiKey {
+    type Config = ();
+    type Error = Error;
+    type Future = Result<Self, Error>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let state = web::Data::<AppState>::extract(req)?;
+        let store = &state.api_key_store;
+        if !store.is_enabled() {
+            return Ok(ApiKey {
+                name: ANONYMOUS.to_string(),
+                role: Role::Admin,
+                authenticated: false,
+            });
+        }
+
+        let key = req
+            .headers()
+            .get(API_KEY_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .ok_or_else(|| error::ErrorUnauthorized("Missing X-Api-Key header"))?;
+        store
+            .authenticate(key)
+            .map(|(name, role)| ApiKey {
+                name,
+                role,
+                authenticated: true,
+            })
+            .ok_or_else(|| error::ErrorUnauthorized("Invalid API key"))
+    }
+}
@@ -0,0 +1,135 @@
+/*
+ * Copyright 2019 Walmart Inc.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * -----------------------------------------------------------------------------
+ */
+
+//! Batch statuses that can no longer change, kept to answer repeated polls.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+
+use serde_json::Value;
+
+/// a circuit id and the id of a batch submitted to it
+type BatchKey = (String, String);
+
+#[derive(Default)]
+struct Statuses {
+    by_id: HashMap<BatchKey, Value>,
+    /// circuit and batch ids in the order their statuses were stored, oldest first
+    order: VecDeque<BatchKey>,
+}
+
+/// Keeps the statuses of committed and invalid batches, forgetting the oldest beyond capacity.
+///
+/// Statuses are kept per circuit, so a batch id only answers polls on the circuit whose scabbard
+/// service reported it. Pending statuses are never kept. Clones share the same statuses.
+#[derive(Clone)]
+pub struct BatchStatusCache {
+    capacity: usize,
+    statuses: Arc<Mutex<Statuses>>,
+}
+
+impl BatchStatusCache {
+    /// Creates a cache of at most `capacity` statuses; a zero capacity disables it.
+    pub fn new(capacity: usize) -> Self {
+        BatchStatusCache {
+            capacity,
+            statuses: Arc::new(Mutex::new(Statuses::default())),
+        }
+    }
+
+    /// Returns the final status of the batch on the circuit, if it is known.
+    pub fn get(&self, circuit_id: &str, batch_id: &str) -> Option<Value> {
+        let key = (circuit_id.to_string(), batch_id.to_string());
+        match self.statuses.lock() {
+            Ok(statuses) => statuses.by_id.get(&key).cloned(),
+            Err(_) => {
+                error!("Batch status cache lock was poisoned");
+                None
+            }
+        }
+    }
+
+    /// Stores a batch status as reported by the circuit's scabbard service, if it is final.
+    pub fn store(&self, circuit_id: &str, status: &Value) {
+        let key = match status["id"].as_str() {
+            Some(batch_id) if self.capacity > 0 && is_final(status) => {
+                (circuit_id.to_string(), batch_id.to_string())
+            }
+            _ => return,
+        };
+        match self.statuses.lock() {
+            Ok(mut statuses) => {
+                if statuses.by_id.insert(key.clone(), status.clone()).is_none() {
+                    statuses.order.push_back(key);
+                }
+                while statuses.order.len() > self.capacity {
+                    if let Some(oldest) = statuses.order.pop_front() {
+                        statuses.by_id.remove(&oldest);
+                    }
+                }
+            }
+            Err(_) => error!("Batch status cache lock was poisoned"),
+        }
+    }
+}
+
+/// Returns true if scabbard reports the batch as committed or invalid.
+fn is_final(status: &Value) -> bool {
+    match status["status"]["statusType"].as_str() {
+        Some("Committed") | Some("Invalid") => true,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn committed(batch_id: &str) -> Value {
+        json!({ "id": batch_id, "status": { "statusType": "Committed" } })
+    }
+
+    #[test]
+    fn statuses_are_kept_per_circuit() {
+        let cache = BatchStatusCache::new(10);
+        cache.store("circuit-a", &committed("abc123"));
+
+        assert_eq!(cache.get("circuit-a", "abc123"), Some(committed("abc123")));
+        assert_eq!(cache.get("circuit-b", "abc123"), None);
+    }
+
+    #[test]
+    fn pending_statuses_are_not_kept() {
+        let cache = BatchStatusCache::new(10);
+        cache.store(
+            "circuit-a",
+            &json!({ "id": "abc123", "status": { "statusType": "Pending" } }),
+        );
+
+        assert_eq!(cache.get("circuit-a", "abc123"), None);
+    }
+
+    #[test]
+    fn oldest_status_is_forgotten_beyond_capacity() {
+        let cache = BatchStatusCache::new(1);
+        cache.store("circuit-a", &committed("abc123"));
+        cache.store("circuit-b", &committed("abc123"));
+
+        assert_eq!(cache.get("circuit-a", "abc123"), None);
+        assert!(cache.get("circuit-b", "abc123").is_some());
+    }
+}
@@ -0,0 +1,84 @@
+/*
+ * Copyright 2019 Walmart Inc.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * -----------------------------------------------------------------------------
+ */
+
+//! Choice of the REST API responses worth compressing; the compression itself is done by
+//! actix-web's Compress middleware.
+
+use std::sync::Arc;
+
+use actix_web::dev::{BodyEncoding, BodySize, MessageBody, Service, ServiceRequest, ServiceResponse};
+use actix_web::http::{header, ContentEncoding};
+use actix_web::Error;
+use futures::future::Future;
+
+use crate::config::CompressionConfig;
+
+/// Exempts responses from compression unless their content type is allowed and their body is
+/// large enough. Streamed responses, such as Server-Sent Events, are never compressed.
+#[derive(Clone)]
+pub struct CompressionPolicy {
+    min_size: u64,
+    content_types: Arc<Vec<String>>,
+}
+
+impl CompressionPolicy {
+    pub fn new(config: &CompressionConfig) -> Self {
+        CompressionPolicy {
+            min_size: config.min_size_bytes(),
+            content_types: Arc::new(config.content_types().to_vec()),
+        }
+    }
+
+    /// Handles a request on its way to the wrapped service.
+    pub fn handle<S>(
+        &self,
+        req: ServiceRequest,
+        srv: &mut S,
+    ) -> Box<dyn Future<Item = ServiceResponse, Error = Error>>
+    where
+        S: Service<Request = ServiceRequest, Response = ServiceResponse, Error = Error>,
+        S::Future: 'static,
+    {
+        let policy = self.clone();
+        Box::new(srv.call(req).map(move |mut res| {
+            if !policy.should_compress(&res) {
+                res.response_mut().encoding(ContentEncoding::Identity);
+            }
+            res
+        }))
+    }
+
+    fn should_compress(&self, res: &ServiceResponse) -> bool {
+        let size = match res.response().body().size() {
+            BodySize::Sized(size) => size as u64,
+            BodySize::Sized64(size) => size,
+            _ => return false,
+        };
+        let content_type = res
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.split(';').next())
+            .map(str::trim)
+            .unwrap_or_default();
+        size >= self.min_size
+            && self
+                .content_types
+                .iter()
+                .any(|allowed| allowed.eq_ignore_ascii_case(content_type))
+    }
+}
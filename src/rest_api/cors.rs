@@ -0,0 +1,115 @@
+/*
+ * Copyright 2019 Walmart Inc.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * -----------------------------------------------------------------------------
+ */
+
+//! Cross-origin resource sharing, so browser UIs served from another origin can call the REST
+//! API without a reverse proxy.
+
+use actix_web::dev::{Service, ServiceRequest, ServiceResponse};
+use actix_web::http::header::{self, HeaderValue};
+use actix_web::http::Method;
+use actix_web::{Error, HttpResponse};
+use futures::future::{self, Future};
+
+use crate::config::CorsConfig;
+
+const ANY_ORIGIN: &str = "*";
+
+/// Adds CORS headers to responses for allowed origins and answers preflight requests.
+#[derive(Clone)]
+pub struct CorsPolicy {
+    allowed_origins: Vec<String>,
+    allowed_methods: String,
+    allowed_headers: String,
+    max_age: String,
+}
+
+impl CorsPolicy {
+    pub fn new(config: &CorsConfig) -> Self {
+        CorsPolicy {
+            allowed_origins: config.allowed_origins().to_vec(),
+            allowed_methods: config.allowed_methods().join(", "),
+            allowed_headers: config.allowed_headers().join(", "),
+            max_age: config.max_age_secs().to_string(),
+        }
+    }
+
+    /// Handles a request on its way to the wrapped service.
+    pub fn handle<S>(
+        &self,
+        req: ServiceRequest,
+        srv: &mut S,
+    ) -> Box<dyn Future<Item = ServiceResponse, Error = Error>>
+    where
+        S: Service<Request = ServiceRequest, Response = ServiceResponse, Error = Error>,
+        S::Future: 'static,
+    {
+        let origin = match self.allowed_origin(&req) {
+            Some(origin) => origin,
+            None => return Box::new(srv.call(req)),
+        };
+
+        if req.method() == Method::OPTIONS
+            && req
+                .headers()
+                .contains_key(header::ACCESS_CONTROL_REQUEST_METHOD)
+        {
+            let response = HttpResponse::NoContent()
+                .header(header::ACCESS_CONTROL_ALLOW_ORIGIN, origin)
+                .header(
+                    header::ACCESS_CONTROL_ALLOW_METHODS,
+                    self.allowed_methods.as_str(),
+                )
+                .header(
+                    header::ACCESS_CONTROL_ALLOW_HEADERS,
+                    self.allowed_headers.as_str(),
+                )
+                .header(header::ACCESS_CONTROL_MAX_AGE, self.max_age.as_str())
+                .header(header::VARY, "Origin")
+                .finish();
+            return Box::new(future::ok(req.into_response(response)));
+        }
+
+        Box::new(srv.call(req).map(move |mut res| {
+            let headers = res.headers_mut();
+            headers.insert(header::ACCESS_CONTROL_ALLOW_ORIGIN, origin);
+            headers.insert(header::VARY, HeaderValue::from_static("Origin"));
+            res
+        }))
+    }
+
+    /// Returns the value of the Access-Control-Allow-Origin header for the request, if its
+    /// origin is allowed.
+    fn allowed_origin(&self, req: &ServiceRequest) -> Option<HeaderValue> {
+        let origin = req.headers().get(header::ORIGIN)?;
+        let origin_str = origin.to_str().ok()?;
+        if self
+            .allowed_origins
+            .iter()
+            .any(|allowed| allowed == ANY_ORIGIN)
+        {
+            Some(HeaderValue::from_static(ANY_ORIGIN))
+        } else if self
+            .allowed_origins
+            .iter()
+            .any(|allowed| allowed == origin_str)
+        {
+            Some(origin.clone())
+        } else {
+            None
+        }
+    }
+}
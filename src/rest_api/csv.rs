@@ -0,0 +1,120 @@
+/*
+ * Copyright 2019 Walmart Inc.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * -----------------------------------------------------------------------------
+ */
+
+//! CSV rendering of list responses, for clients sending `Accept: text/csv`.
+
+use actix_web::http::header;
+use actix_web::{HttpRequest, HttpResponse};
+
+/// Returns true if the client prefers CSV to JSON.
+pub fn accepts_csv(req: &HttpRequest) -> bool {
+    req.headers()
+        .get(header::ACCEPT)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.split(',').next())
+        .and_then(|media_type| media_type.split(';').next())
+        .map(|media_type| media_type.trim().eq_ignore_ascii_case("text/csv"))
+        .unwrap_or(false)
+}
+
+/// Responds with a CSV table of the given header row and rows, quoted as RFC 4180 requires.
+pub fn csv_response<I>(columns: &[&str], rows: I) -> HttpResponse
+where
+    I: IntoIterator<Item = Vec<String>>,
+{
+    let mut body = String::new();
+    write_row(&mut body, columns.iter().cloned());
+    for row in rows {
+        write_row(&mut body, row.iter().map(String::as_str));
+    }
+    HttpResponse::Ok()
+        .content_type("text/csv; charset=utf-8")
+        .body(body)
+}
+
+fn write_row<'a, I>(body: &mut String, fields: I)
+where
+    I: Iterator<Item = &'a str>,
+{
+    for (i, field) in fields.enumerate() {
+        if i > 0 {
+            body.push(',');
+        }
+        if field.contains(|c| c == ',' || c == '"' || c == '\r' || c == '\n') {
+            body.push('"');
+            body.push_str(&field.replace('"', "\"\""));
+            body.push('"');
+        } else {
+            body.push_str(field);
+        }
+    }
+    body.push_str("\r\n");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Parses one RFC 4180 record terminated by CRLF.
+    fn parse_row(record: &str) -> Vec<String> {
+        let mut fields = vec![String::new()];
+        let mut quoted = false;
+        let mut chars = record.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '"' if quoted && chars.peek() == Some(&'"') => {
+                    chars.next();
+                    fields.last_mut().unwrap().push('"');
+                }
+                '"' => quoted = !quoted,
+                ',' if !quoted => fields.push(String::new()),
+                '\r' if !quoted => {
+                    assert_eq!(chars.next(), Some('\n'));
+                    assert_eq!(chars.next(), None);
+                }
+                c => fields.last_mut().unwrap().push(c),
+            }
+        }
+        fields
+    }
+
+    fn row(fields: &[&str]) -> String {
+        let mut body = String::new();
+        write_row(&mut body, fields.iter().cloned());
+        body
+    }
+
+    #[test]
+    fn write_row_leaves_plain_fields_unquoted() {
+        assert_eq!(row(&["a", "b c", ""]), "a,b c,\r\n");
+    }
+
+    #[test]
+    fn write_row_quotes_fields_with_quotes_and_commas() {
+        assert_eq!(
+            row(&["say \"hi\", then leave", "x"]),
+            "\"say \"\"hi\"\", then leave\",x\r\n"
+        );
+        assert_eq!(row(&["two\r\nlines"]), "\"two\r\nlines\"\r\n");
+    }
+
+    #[test]
+    fn write_row_round_trips() {
+        let fields = ["plain", "a,b", "\"quoted\"", "\",\"", "line\nbreak", ""];
+        assert_eq!(parse_row(&row(&fields)), fields);
+    }
+}
@@ -0,0 +1,116 @@
+/*
+ * Copyright 2019 Walmart Inc.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * -----------------------------------------------------------------------------
+ */
+
+//! Conditional GET support, so polling clients do not download unchanged responses again.
+
+use actix_web::http::header;
+use actix_web::{HttpRequest, HttpResponse};
+use crypto::digest::Digest;
+use crypto::sha2::Sha256;
+use serde_json::Value;
+
+/// Responds with `body` as JSON under a weak ETag, or with 304 Not Modified if the client's
+/// If-None-Match header already names it.
+pub fn json_with_etag(req: &HttpRequest, body: &Value) -> HttpResponse {
+    let serialized = body.to_string();
+    let etag = {
+        let mut sha = Sha256::new();
+        sha.input_str(&serialized);
+        format!("W/\"{}\"", &sha.result_str()[..32])
+    };
+
+    let not_modified = req
+        .headers()
+        .get(header::IF_NONE_MATCH)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| {
+            value
+                .split(',')
+                .map(str::trim)
+                .any(|tag| tag == "*" || tag == etag || tag == &etag[2..])
+        })
+        .unwrap_or(false);
+
+    if not_modified {
+        HttpResponse::NotModified()
+            .header(header::ETAG, etag)
+            .finish()
+    } else {
+        HttpResponse::Ok()
+            .header(header::ETAG, etag)
+            .content_type("application/json")
+            .body(serialized)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use actix_web::http::StatusCode;
+    use actix_web::test::TestRequest;
+
+    const ETAG: &str = "W/\"015abd7f5cc57a2dd94b7590f04ad808\"";
+
+    fn respond(if_none_match: Option<&str>) -> HttpResponse {
+        let req = match if_none_match {
+            Some(value) => TestRequest::with_header(header::IF_NONE_MATCH, value),
+            None => TestRequest::default(),
+        };
+        json_with_etag(&req.to_http_request(), &json!({ "a": 1 }))
+    }
+
+    fn etag_of(response: &HttpResponse) -> &str {
+        response
+            .headers()
+            .get(header::ETAG)
+            .and_then(|value| value.to_str().ok())
+            .unwrap()
+    }
+
+    #[test]
+    fn etag_is_a_weak_hash_of_the_body() {
+        let response = respond(None);
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(etag_of(&response), ETAG);
+    }
+
+    #[test]
+    fn matching_etags_are_not_modified() {
+        for if_none_match in &[
+            ETAG,
+            "\"015abd7f5cc57a2dd94b7590f04ad808\"",
+            "W/\"other\", W/\"015abd7f5cc57a2dd94b7590f04ad808\"",
+            "*",
+        ] {
+            let response = respond(Some(if_none_match));
+            assert_eq!(
+                response.status(),
+                StatusCode::NOT_MODIFIED,
+                "{}",
+                if_none_match
+            );
+            assert_eq!(etag_of(&response), ETAG);
+        }
+    }
+
+    #[test]
+    fn other_etags_are_answered_in_full() {
+        let response = respond(Some("W/\"other\""));
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+}
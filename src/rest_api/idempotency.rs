@@ -0,0 +1,304 @@
+/*
+ * Copyright 2019 Walmart Inc.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * -----------------------------------------------------------------------------
+ */
+//! Responses remembered by idempotency key, so that retried submissions are not repeated.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use actix_web::http::StatusCode;
+use crypto::digest::Digest;
+use crypto::sha2::Sha256;
+use serde_json::Value;
+
+/// header carrying the client's idempotency key
+pub const IDEMPOTENCY_KEY_HEADER: &str = "Idempotency-Key";
+
+/// number of idempotency keys remembered; the oldest are forgotten first
+const MAX_KEYS: usize = 10_000;
+
+/// The outcome of reserving an idempotency key for a request.
+pub enum Reservation {
+    /// The key is new; the request should be handled and its response completed.
+    Reserved(ReservedKey),
+    /// A request with the key was already handled, with this response.
+    Replay(StatusCode, Value),
+    /// A request with the key is still being handled.
+    InProgress,
+    /// The key was already used for a different payload.
+    Mismatch,
+}
+
+/// An idempotency key reserved for a request being handled.
+///
+/// Dropping it before its response is completed releases the key, so a request abandoned
+/// halfway, such as when the client disconnects, can be retried.
+pub struct ReservedKey {
+    cache: IdempotencyCache,
+    key: String,
+    reserved_at: Instant,
+    completed: bool,
+}
+
+impl ReservedKey {
+    /// Records the response to the request; a server error releases the key instead.
+    pub fn complete(mut self, status: StatusCode, body: &Value) {
+        self.completed = true;
+        if status.is_server_error() {
+            self.cache.release(&self.key, self.reserved_at);
+        } else {
+            self.cache
+                .record(&self.key, self.reserved_at, status, body.clone());
+        }
+    }
+}
+
+impl Drop for ReservedKey {
+    fn drop(&mut self) {
+        if !self.completed {
+            self.cache.release(&self.key, self.reserved_at);
+        }
+    }
+}
+
+struct Entry {
+    reserved_at: Instant,
+    payload_hash: String,
+    response: Option<(StatusCode, Value)>,
+}
+
+struct Entries {
+    by_key: HashMap<String, Entry>,
+    /// keys in the order they were reserved, to expire them
+    order: VecDeque<(Instant, String)>,
+}
+
+impl Entries {
+    /// Removes the entry of `key` if it is still the one reserved at `reserved_at`, as the key
+    /// may have been released and reserved again since.
+    fn remove(&mut self, key: &str, reserved_at: Instant) {
+        if self
+            .by_key
+            .get(key)
+            .map(|entry| entry.reserved_at == reserved_at)
+            .unwrap_or(false)
+        {
+            self.by_key.remove(key);
+        }
+    }
+}
+
+/// Remembers the response to each request carrying an idempotency key for a period of time, up
+/// to the most recent 10000 keys.
+///
+/// Server errors are forgotten so the request can be retried. Clones share the same entries.
+#[derive(Clone)]
+pub struct IdempotencyCache {
+    ttl: Duration,
+    entries: Arc<Mutex<Entries>>,
+}
+
+impl IdempotencyCache {
+    /// Creates a cache keeping responses for `ttl`; a zero ttl disables it.
+    pub fn new(ttl: Duration) -> Self {
+        IdempotencyCache {
+            ttl,
+            entries: Arc::new(Mutex::new(Entries {
+                by_key: HashMap::new(),
+                order: VecDeque::new(),
+            })),
+        }
+    }
+
+    /// Reserves `key` for a request with the given payload, unless it has been used before.
+    pub fn reserve(&self, key: &str, payload: &[u8]) -> Reservation {
+        let now = Instant::now();
+        if self.ttl == Duration::from_secs(0) {
+            return self.reserved(key, now);
+        }
+        let mut entries = match self.entries.lock() {
+            Ok(entries) => entries,
+            Err(_) => {
+                error!("Idempotency cache lock was poisoned, ignoring idempotency key");
+                return self.reserved(key, now);
+            }
+        };
+
+        while let Some((reserved_at, _)) = entries.order.front() {
+            if now.duration_since(*reserved_at) < self.ttl && entries.order.len() < MAX_KEYS {
+                break;
+            }
+            if let Some((reserved_at, oldest)) = entries.order.pop_front() {
+                entries.remove(&oldest, reserved_at);
+            }
+        }
+
+        let payload_hash = {
+            let mut sha = Sha256::new();
+            sha.input(payload);
+            sha.result_str()
+        };
+        if let Some(entry) = entries.by_key.get(key) {
+            return if entry.payload_hash != payload_hash {
+                Reservation::Mismatch
+            } else if let Some((status, body)) = &entry.response {
+                Reservation::Replay(*status, body.clone())
+            } else {
+                Reservation::InProgress
+            };
+        }
+
+        entries.by_key.insert(
+            key.to_string(),
+            Entry {
+                reserved_at: now,
+                payload_hash,
+                response: None,
+            },
+        );
+        entries.order.push_back((now, key.to_string()));
+        self.reserved(key, now)
+    }
+
+    fn reserved(&self, key: &str, reserved_at: Instant) -> Reservation {
+        Reservation::Reserved(ReservedKey {
+            cache: self.clone(),
+            key: key.to_string(),
+            reserved_at,
+            completed: false,
+        })
+    }
+
+    fn record(&self, key: &str, reserved_at: Instant, status: StatusCode, body: Value) {
+        if self.ttl == Duration::from_secs(0) {
+            return;
+        }
+        match self.entries.lock() {
+            Ok(mut entries) => {
+                if let Some(entry) = entries.by_key.get_mut(key) {
+                    if entry.reserved_at == reserved_at {
+                        entry.response = Some((status, body));
+                    }
+                }
+            }
+            Err(_) => error!("Idempotency cache lock was poisoned, not recording response"),
+        }
+    }
+
+    fn release(&self, key: &str, reserved_at: Instant) {
+        if self.ttl == Duration::from_secs(0) {
+            return;
+        }
+        match self.entries.lock() {
+            Ok(mut entries) => entries.remove(key, reserved_at),
+            Err(_) => error!("Idempotency cache lock was poisoned, not releasing key"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cache() -> IdempotencyCache {
+        IdempotencyCache::new(Duration::from_secs(60))
+    }
+
+    fn reserved(reservation: Reservation) -> ReservedKey {
+        match reservation {
+            Reservation::Reserved(reserved) => reserved,
+            _ => panic!("the key was not reserved"),
+        }
+    }
+
+    fn is_reserved(reservation: Reservation) -> bool {
+        match reservation {
+            Reservation::Reserved(_) => true,
+            _ => false,
+        }
+    }
+
+    #[test]
+    fn completed_response_is_replayed() {
+        let cache = cache();
+        reserved(cache.reserve("client:key", b"payload"))
+            .complete(StatusCode::ACCEPTED, &json!({ "submission_id": "abc" }));
+
+        match cache.reserve("client:key", b"payload") {
+            Reservation::Replay(status, body) => {
+                assert_eq!(status, StatusCode::ACCEPTED);
+                assert_eq!(body, json!({ "submission_id": "abc" }));
+            }
+            _ => panic!("the response was not replayed"),
+        }
+    }
+
+    #[test]
+    fn key_used_for_another_payload_is_a_mismatch() {
+        let cache = cache();
+        reserved(cache.reserve("client:key", b"payload"))
+            .complete(StatusCode::ACCEPTED, &json!({}));
+
+        match cache.reserve("client:key", b"other payload") {
+            Reservation::Mismatch => (),
+            _ => panic!("the payloads were not told apart"),
+        }
+    }
+
+    #[test]
+    fn key_being_handled_is_in_progress() {
+        let cache = cache();
+        let _reserved = reserved(cache.reserve("client:key", b"payload"));
+
+        match cache.reserve("client:key", b"payload") {
+            Reservation::InProgress => (),
+            _ => panic!("the key was not in progress"),
+        }
+    }
+
+    #[test]
+    fn server_error_releases_the_key() {
+        let cache = cache();
+        reserved(cache.reserve("client:key", b"payload"))
+            .complete(StatusCode::SERVICE_UNAVAILABLE, &json!({}));
+
+        assert!(is_reserved(cache.reserve("client:key", b"payload")));
+    }
+
+    #[test]
+    fn dropped_reservation_releases_the_key() {
+        let cache = cache();
+        drop(reserved(cache.reserve("client:key", b"payload")));
+
+        assert!(is_reserved(cache.reserve("client:key", b"payload")));
+    }
+
+    #[test]
+    fn oldest_key_is_forgotten_beyond_capacity() {
+        let cache = cache();
+        for id in 0..=MAX_KEYS {
+            reserved(cache.reserve(&format!("client:{}", id), b"payload"))
+                .complete(StatusCode::ACCEPTED, &json!({}));
+        }
+
+        assert!(is_reserved(cache.reserve("client:0", b"payload")));
+        match cache.reserve(&format!("client:{}", MAX_KEYS), b"payload") {
+            Reservation::Replay(..) => (),
+            _ => panic!("the newest key was forgotten"),
+        }
+    }
+}
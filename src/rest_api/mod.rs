@@ -0,0 +1,420 @@
+/*
+ * Copyright 2019 Walmart Inc.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * -----------------------------------------------------------------------------
+ */
+
+mod auth;
+mod batch_status_cache;
+mod compression;
+mod cors;
+mod csv;
+mod error;
+mod etag;
+mod idempotency;
+mod node_cache;
+mod rate_limit;
+mod request_id;
+mod routes;
+mod splinterd;
+mod submissions;
+mod tls;
+
+pub use error::RestApiServerError;
+
+use std::fs;
+use std::os::unix::fs::FileTypeExt;
+use std::sync::{mpsc, Arc};
+use std::thread;
+use std::time::Duration;
+
+use actix_web::{client::Client, middleware, web, App, HttpServer};
+
+use crate::authorization::TokenProvider;
+use crate::broadcast::Broadcaster;
+use crate::clock::Clock;
+use crate::config::EventListenerConfig;
+use crate::event_handler::{
+    ConnectionStatus, ContractInventory, EventFilter, EventReprocessor, ServiceRoster,
+};
+use crate::key_registry::KeyRegistry;
+use crate::metrics::Metrics;
+use crate::publisher::Publisher;
+use crate::signer::Signer;
+
+use self::auth::ApiKeyStore;
+use self::batch_status_cache::BatchStatusCache;
+use self::compression::CompressionPolicy;
+use self::cors::CorsPolicy;
+use self::idempotency::IdempotencyCache;
+use self::node_cache::NodeCache;
+use self::rate_limit::RateLimiter;
+use self::submissions::SubmissionTracker;
+use self::tls::HttpsPort;
+
+/// one line per request, as key=value pairs
+const ACCESS_LOG_FORMAT: &str = "request_id=%{X-Request-Id}o remote_addr=%a request=\"%r\" \
+                                 status=%s bytes=%b latency_secs=%T";
+
+/// The handles the REST API shares with the rest of the event listener.
+#[derive(Clone)]
+pub struct RestApiResources {
+    pub config: EventListenerConfig,
+    /// the identity of the splinterd node the event listener serves
+    pub node_id: String,
+    pub token_provider: Option<TokenProvider>,
+    pub connection_status: ConnectionStatus,
+    pub filter: EventFilter,
+    pub metrics: Metrics,
+    pub publisher: Publisher,
+    pub broadcaster: Broadcaster,
+    pub reprocessor: EventReprocessor,
+    pub roster: ServiceRoster,
+    pub contracts: ContractInventory,
+    pub keys: KeyRegistry,
+    pub signer: Option<Arc<dyn Signer>>,
+    pub clock: Arc<dyn Clock>,
+}
+
+/// The state every handler is given, built for each worker thread.
+pub struct AppState {
+    pub client: Client,
+    pub config: EventListenerConfig,
+    pub node_id: String,
+    pub token_provider: Option<TokenProvider>,
+    pub connection_status: ConnectionStatus,
+    pub filter: EventFilter,
+    pub metrics: Metrics,
+    pub publisher: Publisher,
+    pub broadcaster: Broadcaster,
+    pub reprocessor: EventReprocessor,
+    pub roster: ServiceRoster,
+    pub contracts: ContractInventory,
+    pub keys: KeyRegistry,
+    pub signer: Option<Arc<dyn Signer>>,
+    pub api_key_store: ApiKeyStore,
+    pub rate_limiter: RateLimiter,
+    pub idempotency_cache: IdempotencyCache,
+    pub node_cache: NodeCache,
+    pub submission_tracker: SubmissionTracker,
+    pub batch_status_cache: BatchStatusCache,
+}
+
+pub struct RestApiShutdownHandle {
+    do_shutdown: Box<dyn Fn() -> Result<(), RestApiServerError> + Send>,
+}
+
+impl RestApiShutdownHandle {
+    pub fn shutdown(&self) -> Result<(), RestApiServerError> {
+        (*self.do_shutdown)()
+    }
+}
+
+/// Starts the REST API on its own thread.
+pub fn run(
+    resources: RestApiResources,
+) -> Result<
+    (
+        RestApiShutdownHandle,
+        thread::JoinHandle<Result<(), RestApiServerError>>,
+    ),
+    RestApiServerError,
+> {
+    let config = resources.config.clone();
+    let bind_url = config.rest_api_endpoint().to_owned();
+    let api_key_store = ApiKeyStore::new(config.deployment_config().api_keys());
+    let cors_policy = CorsPolicy::new(config.deployment_config().cors());
+    let compression_policy = CompressionPolicy::new(config.deployment_config().compression());
+    let rate_limiter = RateLimiter::new(
+        config.deployment_config().submit_rate_limit_per_minute(),
+        config.deployment_config().submit_rate_limit_burst(),
+    );
+    let idempotency_cache = IdempotencyCache::new(Duration::from_secs(
+        config.deployment_config().idempotency_key_ttl_secs(),
+    ));
+    let node_cache = NodeCache::new(Duration::from_secs(
+        config.deployment_config().node_cache_ttl_secs(),
+    ));
+    if !api_key_store.is_enabled() {
+        warn!("No API keys are configured, the REST API accepts changes from any client");
+    }
+    let submission_tracker = SubmissionTracker::new(resources.clock.clone());
+    let batch_status_cache =
+        BatchStatusCache::new(config.deployment_config().batch_status_cache_size());
+    let tls_config = config.deployment_config().rest_api_tls();
+    let tls_acceptor = match tls_config {
+        Some(tls_config) => Some(tls::acceptor(tls_config)?),
+        None => None,
+    };
+    let redirect = match tls_config.and_then(|tls_config| tls_config.redirect_bind()) {
+        Some(redirect_bind) => {
+            let https_port = bind_url
+                .rsplit(':')
+                .next()
+                .and_then(|port| port.parse().ok())
+                .ok_or_else(|| {
+                    RestApiServerError::StartUpError(format!(
+                        "Unable to find the port to redirect to in {}",
+                        bind_url
+                    ))
+                })?;
+            Some((redirect_bind.to_string(), HttpsPort(https_port)))
+        }
+        None => None,
+    };
+    let tcp_enabled = config.deployment_config().rest_api_tcp_enabled();
+    let unix_socket = config
+        .deployment_config()
+        .rest_api_unix_socket()
+        .map(ToOwned::to_owned);
+    let (tx, rx) = mpsc::channel();
+
+    let join_handle = thread::Builder::new()
+        .name("EventListenerRestApi".into())
+        .spawn(move || {
+            let sys = actix::System::new("EventListener-Rest-API");
+            actix::spawn(submission_tracker.track(resources.broadcaster.subscribe()));
+
+            let server = HttpServer::new(move || {
+                let resources = resources.clone();
+                App::new()
+                    .data(AppState {
+                        client: Client::default(),
+                        config: resources.config,
+                        node_id: resources.node_id,
+                        token_provider: resources.token_provider,
+                        connection_status: resources.connection_status,
+                        filter: resources.filter,
+                        metrics: resources.metrics,
+                        publisher: resources.publisher,
+                        broadcaster: resources.broadcaster,
+                        reprocessor: resources.reprocessor,
+                        roster: resources.roster,
+                        contracts: resources.contracts,
+                        keys: resources.keys,
+                        signer: resources.signer,
+                        api_key_store: api_key_store.clone(),
+                        rate_limiter: rate_limiter.clone(),
+                        idempotency_cache: idempotency_cache.clone(),
+                        node_cache: node_cache.clone(),
+                        submission_tracker: submission_tracker.clone(),
+                        batch_status_cache: batch_status_cache.clone(),
+                    })
+                    .wrap_fn({
+                        let cors_policy = cors_policy.clone();
+                        move |req, srv| cors_policy.handle(req, srv)
+                    })
+                    .wrap_fn({
+                        let compression_policy = compression_policy.clone();
+                        move |req, srv| compression_policy.handle(req, srv)
+                    })
+                    .wrap(middleware::Compress::default())
+                    .wrap_fn(request_id::assign_request_id)
+                    .wrap(middleware::Logger::new(ACCESS_LOG_FORMAT))
+                    .service(
+                        web::resource("/health/live").route(web::get().to(routes::fetch_liveness)),
+                    )
+                    .service(
+                        web::resource("/health/ready")
+                            .route(web::get().to(routes::fetch_readiness)),
+                    )
+                    .service(
+                        web::resource("/health/splinterd")
+                            .route(web::get().to(routes::fetch_splinterd_health)),
+                    )
+                    .service(web::resource("/metrics").route(web::get().to(routes::fetch_metrics)))
+                    .service(
+                        web::resource("/filters")
+                            .route(web::get().to(routes::fetch_filters))
+                            .route(web::put().to(routes::replace_filters)),
+                    )
+                    .service(
+                        web::resource("/admin/failed-events")
+                            .route(web::get().to(routes::list_failed_events)),
+                    )
+                    .service(
+                        web::resource("/admin/failed-events/{id}/retry")
+                            .route(web::post().to_async(routes::retry_failed_event)),
+                    )
+                    .service(
+                        web::resource("/admin/proposals/{circuit_id}/resync")
+                            .route(web::post().to_async(routes::resync_proposal)),
+                    )
+                    .service(
+                        web::resource("/admin/validate-config")
+                            .route(web::post().to_async(routes::validate_config)),
+                    )
+                    .service(
+                        web::resource("/admin/webhook-deliveries")
+                            .route(web::get().to(routes::list_webhook_deliveries)),
+                    )
+                    .service(
+                        web::resource("/api-keys")
+                            .route(web::get().to(routes::list_api_keys))
+                            .route(web::post().to(routes::create_api_key)),
+                    )
+                    .service(
+                        web::resource("/api-keys/{name}")
+                            .route(web::delete().to(routes::revoke_api_key)),
+                    )
+                    .service(
+                        web::resource("/circuits/{circuit_id}/batches")
+                            .data(web::PayloadConfig::new(routes::MAX_BATCH_LIST_SIZE))
+                            .route(web::post().to_async(routes::submit_circuit_batches)),
+                    )
+                    .service(
+                        web::resource("/circuits/{circuit_id}/contracts")
+                            .route(web::get().to(routes::list_circuit_contracts)),
+                    )
+                    .service(
+                        web::resource("/circuits/{circuit_id}/batch_statuses")
+                            .route(web::get().to_async(routes::list_batch_statuses)),
+                    )
+                    .service(
+                        web::resource("/export/sinks")
+                            .route(web::get().to(routes::list_export_sinks)),
+                    )
+                    .service(
+                        web::resource("/export/sinks/{id}/runs")
+                            .route(web::get().to(routes::list_export_runs)),
+                    )
+                    .service(
+                        web::resource("/keys")
+                            .route(web::get().to(routes::list_keys))
+                            .route(web::post().to(routes::register_key)),
+                    )
+                    .service(
+                        web::resource("/keys/{public_key}")
+                            .route(web::get().to(routes::fetch_key))
+                            .route(web::put().to(routes::update_key)),
+                    )
+                    .service(
+                        web::resource("/keys/{public_key}/deactivate")
+                            .route(web::post().to(routes::deactivate_key)),
+                    )
+                    .service(web::resource("/nodes").route(web::get().to_async(routes::list_nodes)))
+                    .service(
+                        web::resource("/proposals/{circuit_id}/votes")
+                            .route(web::get().to_async(routes::list_proposal_votes)),
+                    )
+                    .service(
+                        web::resource("/proposals/{circuit_id}/vote")
+                            .route(web::post().to_async(routes::vote_on_proposal)),
+                    )
+                    .service(
+                        web::resource("/proposals/{circuit_id}/vote/payload")
+                            .route(web::post().to_async(routes::build_vote_payload)),
+                    )
+                    .service(
+                        web::resource("/sabre/contracts")
+                            .data(
+                                web::JsonConfig::default().limit(routes::MAX_CONTRACT_UPLOAD_SIZE),
+                            )
+                            .route(web::post().to_async(routes::upload_contract)),
+                    )
+                    .service(
+                        web::resource("/sabre/namespaces")
+                            .route(web::post().to_async(routes::create_namespace)),
+                    )
+                    .service(
+                        web::resource("/sabre/namespaces/{namespace}")
+                            .route(web::put().to_async(routes::update_namespace_owners)),
+                    )
+                    .service(
+                        web::resource("/sabre/namespaces/{namespace}/permissions")
+                            .route(web::post().to_async(routes::grant_namespace_permission)),
+                    )
+                    .service(
+                        web::resource("/submit")
+                            .route(web::post().to_async(routes::submit_signed_payload)),
+                    )
+                    .service(
+                        web::resource("/submissions/{id}")
+                            .route(web::get().to(routes::fetch_submission)),
+                    )
+                    .service(web::resource("/ws/subscribe").route(web::get().to(routes::subscribe)))
+                    .service(
+                        web::resource("/events/stream").route(web::get().to(routes::stream_events)),
+                    )
+                    .service(
+                        web::resource("/openapi.json").route(web::get().to(routes::fetch_openapi)),
+                    )
+            });
+            let server = match (tcp_enabled, tls_acceptor) {
+                (false, _) => server,
+                (true, Some(tls_acceptor)) => server.bind_ssl(bind_url, tls_acceptor)?,
+                (true, None) => server.bind(bind_url)?,
+            };
+            let server = match unix_socket {
+                Some(unix_socket) => {
+                    remove_stale_socket(&unix_socket)?;
+                    server.bind_uds(unix_socket)?
+                }
+                None => server,
+            };
+            let addr = server.disable_signals().system_exit().start();
+
+            let redirect_addr = match redirect {
+                Some((redirect_bind, https_port)) => Some(
+                    HttpServer::new(move || {
+                        App::new()
+                            .data(https_port)
+                            .default_service(web::route().to(tls::redirect_to_https))
+                    })
+                    .bind(redirect_bind)?
+                    .disable_signals()
+                    .start(),
+                ),
+                None => None,
+            };
+
+            tx.send((addr, redirect_addr)).map_err(|err| {
+                RestApiServerError::StartUpError(format!("Unable to send Server Addr: {}", err))
+            })?;
+            sys.run()?;
+
+            info!("Rest API terminating");
+
+            Ok(())
+        })?;
+
+    let (addr, redirect_addr) = rx.recv().map_err(|err| {
+        RestApiServerError::StartUpError(format!("Unable to receive Server Addr: {}", err))
+    })?;
+
+    let do_shutdown = Box::new(move || {
+        debug!("Shutting down Rest API");
+        addr.stop(true);
+        if let Some(redirect_addr) = &redirect_addr {
+            redirect_addr.stop(true);
+        }
+        debug!("Graceful signal sent to Rest API");
+
+        Ok(())
+    });
+
+    Ok((RestApiShutdownHandle { do_shutdown }, join_handle))
+}
+
+/// Removes a socket left at `path` by an earlier run, which would prevent binding it again.
+fn remove_stale_socket(path: &str) -> Result<(), RestApiServerError> {
+    match fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.file_type().is_socket() => Ok(fs::remove_file(path)?),
+        Ok(_) => Err(RestApiServerError::StartUpError(format!(
+            "{} exists and is not a socket",
+            path
+        ))),
+        Err(_) => Ok(()),
+    }
+}
@@ -0,0 +1,66 @@
+/*
+ * Copyright 2019 Walmart Inc.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * -----------------------------------------------------------------------------
+ */
+
+//! An in-memory copy of splinterd's node registry.
+
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use splinter::node_registry::Node;
+
+/// Keeps the nodes last fetched from splinterd for a period of time.
+///
+/// Clones share the same copy.
+#[derive(Clone)]
+pub struct NodeCache {
+    ttl: Duration,
+    nodes: Arc<Mutex<Option<(Instant, Vec<Node>)>>>,
+}
+
+impl NodeCache {
+    /// Creates a cache keeping nodes for `ttl`; a zero ttl disables it.
+    pub fn new(ttl: Duration) -> Self {
+        NodeCache {
+            ttl,
+            nodes: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// Returns the cached nodes, unless they are missing or stale.
+    pub fn get(&self) -> Option<Vec<Node>> {
+        match self.nodes.lock() {
+            Ok(nodes) => nodes
+                .as_ref()
+                .filter(|(fetched_at, _)| fetched_at.elapsed() < self.ttl)
+                .map(|(_, nodes)| nodes.clone()),
+            Err(_) => {
+                error!("Node cache lock was poisoned");
+                None
+            }
+        }
+    }
+
+    pub fn store(&self, nodes: Vec<Node>) {
+        if self.ttl == Duration::from_secs(0) {
+            return;
+        }
+        match self.nodes.lock() {
+            Ok(mut cached) => *cached = Some((Instant::now(), nodes)),
+            Err(_) => error!("Node cache lock was poisoned"),
+        }
+    }
+}
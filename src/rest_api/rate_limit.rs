@@ -0,0 +1,174 @@
+/*
+ * Copyright 2019 Walmart Inc.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * -----------------------------------------------------------------------------
+ */
+
+//! Token bucket rate limiting of expensive routes, per client.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use actix_web::http::header;
+use actix_web::{HttpRequest, HttpResponse};
+
+use super::auth::ApiKey;
+
+/// number of buckets above which those of idle clients are discarded
+const MAX_IDLE_BUCKETS: usize = 10_000;
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+/// Allows each client a burst of requests, refilled at a steady rate.
+///
+/// Clones share the same buckets.
+#[derive(Clone)]
+pub struct RateLimiter {
+    /// maximum number of tokens in a bucket; zero disables the limiter
+    capacity: f64,
+    /// tokens added to a bucket per second
+    refill_per_sec: f64,
+    buckets: Arc<Mutex<HashMap<String, Bucket>>>,
+}
+
+impl RateLimiter {
+    pub fn new(per_minute: u64, burst: u64) -> Self {
+        RateLimiter {
+            capacity: if per_minute == 0 {
+                0.0
+            } else {
+                burst.max(1) as f64
+            },
+            refill_per_sec: per_minute as f64 / 60.0,
+            buckets: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Takes a token from the client's bucket, or returns how long the client has to wait for
+    /// the next one.
+    pub fn check(&self, client: &str) -> Result<(), Duration> {
+        if self.capacity <= 0.0 {
+            return Ok(());
+        }
+        let mut buckets = match self.buckets.lock() {
+            Ok(buckets) => buckets,
+            Err(_) => {
+                error!("Rate limiter lock was poisoned, not limiting");
+                return Ok(());
+            }
+        };
+
+        let now = Instant::now();
+        if buckets.len() > MAX_IDLE_BUCKETS {
+            let (capacity, refill_per_sec) = (self.capacity, self.refill_per_sec);
+            buckets.retain(|_, bucket| {
+                bucket.tokens + elapsed_secs(bucket.updated, now) * refill_per_sec < capacity
+            });
+        }
+
+        let bucket = buckets.entry(client.to_string()).or_insert(Bucket {
+            tokens: self.capacity,
+            updated: now,
+        });
+        bucket.tokens = (bucket.tokens + elapsed_secs(bucket.updated, now) * self.refill_per_sec)
+            .min(self.capacity);
+        bucket.updated = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            let wait = (1.0 - bucket.tokens) / self.refill_per_sec;
+            Err(Duration::from_millis((wait * 1000.0).ceil() as u64))
+        }
+    }
+}
+
+fn elapsed_secs(since: Instant, now: Instant) -> f64 {
+    let elapsed = now.duration_since(since);
+    elapsed.as_secs() as f64 + f64::from(elapsed.subsec_nanos()) / 1_000_000_000.0
+}
+
+/// Takes a token for the client of a request to an expensive route, identified by its API key
+/// or else its address.
+///
+/// Returns the id of the client, or the 429 response telling it when to retry.
+pub fn limit(
+    rate_limiter: &RateLimiter,
+    req: &HttpRequest,
+    api_key: &ApiKey,
+) -> Result<String, HttpResponse> {
+    let client_id = if api_key.is_authenticated() {
+        format!("key:{}", api_key.name())
+    } else {
+        req.peer_addr()
+            .map(|addr| format!("address:{}", addr.ip()))
+            .unwrap_or_else(|| "address:unknown".to_string())
+    };
+    match rate_limiter.check(&client_id) {
+        Ok(()) => Ok(client_id),
+        Err(retry_after) => {
+            // round up so the client does not retry too early
+            let retry_after_secs =
+                retry_after.as_secs() + u64::from(retry_after.subsec_nanos() > 0);
+            Err(HttpResponse::TooManyRequests()
+                .header(header::RETRY_AFTER, retry_after_secs.to_string())
+                .json(json!({ "message": "Too many requests, retry later" })))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_allows_a_burst_then_asks_to_wait() {
+        // one token a second, in bursts of two
+        let limiter = RateLimiter::new(60, 2);
+        assert!(limiter.check("key:ci").is_ok());
+        assert!(limiter.check("key:ci").is_ok());
+        let wait = limiter.check("key:ci").unwrap_err();
+        assert!(wait <= Duration::from_secs(1));
+        assert!(wait > Duration::from_millis(900));
+    }
+
+    #[test]
+    fn check_keeps_a_bucket_per_client() {
+        let limiter = RateLimiter::new(60, 1);
+        assert!(limiter.check("key:ci").is_ok());
+        assert!(limiter.check("key:ci").is_err());
+        assert!(limiter.check("key:ops").is_ok());
+        assert!(limiter.clone().check("key:ops").is_err());
+    }
+
+    #[test]
+    fn check_allows_everything_when_disabled() {
+        let limiter = RateLimiter::new(0, 1);
+        for _ in 0..100 {
+            assert!(limiter.check("key:ci").is_ok());
+        }
+    }
+
+    #[test]
+    fn check_allows_at_least_one_request_with_no_burst() {
+        let limiter = RateLimiter::new(60, 0);
+        assert!(limiter.check("key:ci").is_ok());
+        assert!(limiter.check("key:ci").is_err());
+    }
+}
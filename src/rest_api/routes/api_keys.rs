@@ -0,0 +1,95 @@
+/*
+ * Copyright 2019 Walmart Inc.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * -----------------------------------------------------------------------------
+ */
+
+use actix_web::{web, Error, HttpResponse};
+
+use crate::config::Role;
+use crate::rest_api::auth::ApiKey;
+use crate::rest_api::AppState;
+
+#[derive(Deserialize)]
+pub struct NewApiKey {
+    name: String,
+    #[serde(default)]
+    role: Role,
+}
+
+/// Lists the accepted API keys with their roles.
+pub fn list_api_keys(api_key: ApiKey, state: web::Data<AppState>) -> Result<HttpResponse, Error> {
+    if !state.api_key_store.is_enabled() {
+        return Ok(not_enabled());
+    }
+    api_key.require(Role::Admin)?;
+    Ok(HttpResponse::Ok().json(json!({ "keys": state.api_key_store.roles() })))
+}
+
+/// Creates an API key and returns it; it cannot be retrieved again.
+pub fn create_api_key(
+    api_key: ApiKey,
+    state: web::Data<AppState>,
+    new_key: web::Json<NewApiKey>,
+) -> Result<HttpResponse, Error> {
+    if !state.api_key_store.is_enabled() {
+        return Ok(not_enabled());
+    }
+    api_key.require(Role::Admin)?;
+    match state.api_key_store.create(&new_key.name, new_key.role) {
+        Ok(key) => {
+            info!("API key {} created by {}", new_key.name, api_key.name());
+            Ok(HttpResponse::Created().json(json!({
+                "name": new_key.name,
+                "role": new_key.role,
+                "key": key,
+            })))
+        }
+        Err(err) => {
+            error!("Unable to create API key: {}", err);
+            Ok(HttpResponse::InternalServerError()
+                .json(json!({ "message": "Unable to create key" })))
+        }
+    }
+}
+
+/// Revokes the API key with the given name.
+pub fn revoke_api_key(
+    api_key: ApiKey,
+    state: web::Data<AppState>,
+    name: web::Path<String>,
+) -> Result<HttpResponse, Error> {
+    if !state.api_key_store.is_enabled() {
+        return Ok(not_enabled());
+    }
+    api_key.require(Role::Admin)?;
+    match state.api_key_store.revoke(&name) {
+        Ok(true) => {
+            info!("API key {} revoked by {}", name, api_key.name());
+            Ok(HttpResponse::Ok().json(json!({ "message": "Key revoked" })))
+        }
+        Ok(false) => Ok(HttpResponse::NotFound().json(json!({ "message": "No such key" }))),
+        Err(err) => {
+            error!("Unable to revoke API key: {}", err);
+            Ok(HttpResponse::InternalServerError()
+                .json(json!({ "message": "Unable to revoke key" })))
+        }
+    }
+}
+
+fn not_enabled() -> HttpResponse {
+    HttpResponse::NotFound().json(json!({
+        "message": "API keys are not enabled; configure api_keys to enable them",
+    }))
+}
@@ -0,0 +1,245 @@
+/*
+ * Copyright 2019 Walmart Inc.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * -----------------------------------------------------------------------------
+ */
+
+use actix_web::{web, Error, HttpRequest, HttpResponse};
+use futures::future::{self, Future};
+use sawtooth_sdk::messages::batch::BatchList;
+use serde_json::Value;
+
+use crate::config::Role;
+use crate::rest_api::auth::ApiKey;
+use crate::rest_api::rate_limit;
+use crate::rest_api::request_id::RequestId;
+use crate::rest_api::splinterd;
+use crate::rest_api::AppState;
+
+use super::sabre::find_service;
+
+/// largest batch list accepted, in bytes
+pub const MAX_BATCH_LIST_SIZE: usize = 16 * 1024 * 1024;
+/// longest a client may ask scabbard to wait for batches to be committed, in seconds
+const MAX_WAIT_SECS: u64 = 300;
+
+#[derive(Deserialize)]
+pub struct BatchStatusQuery {
+    /// comma separated batch ids
+    ids: String,
+    /// seconds scabbard waits for the batches to be committed before answering
+    wait: Option<u64>,
+}
+
+/// Forwards a signed BatchList to this node's scabbard service on the circuit, so applications
+/// do not need network access to splinterd.
+///
+/// Responds with the ids of the batches, whose statuses can be followed with
+/// GET /circuits/{circuit_id}/batch_statuses.
+pub fn submit_circuit_batches(
+    req: HttpRequest,
+    request_id: RequestId,
+    api_key: ApiKey,
+    circuit_id: web::Path<String>,
+    state: web::Data<AppState>,
+    batch_list: web::Bytes,
+) -> Box<dyn Future<Item = HttpResponse, Error = Error>> {
+    if let Err(err) = api_key.require(Role::Member) {
+        return Box::new(future::err(err));
+    }
+    if let Err(response) = rate_limit::limit(&state.rate_limiter, &req, &api_key) {
+        return Box::new(future::ok(response));
+    }
+    let service = match find_service(&state.roster, &circuit_id, None) {
+        Ok(service) => service,
+        Err(response) => return Box::new(future::ok(response)),
+    };
+    let batch_ids = match protobuf::parse_from_bytes::<BatchList>(&batch_list) {
+        Ok(parsed) if !parsed.get_batches().is_empty() => parsed
+            .get_batches()
+            .iter()
+            .map(|batch| batch.get_header_signature().to_string())
+            .collect::<Vec<_>>(),
+        Ok(_) => {
+            return Box::new(future::ok(HttpResponse::BadRequest().json(json!({
+                "message": "The BatchList holds no batches",
+            }))))
+        }
+        Err(err) => {
+            return Box::new(future::ok(HttpResponse::BadRequest().json(json!({
+                "message": format!("Invalid BatchList: {}", err),
+            }))))
+        }
+    };
+
+    debug!(
+        "Forwarding {} batches to circuit {} for {}",
+        batch_ids.len(),
+        service.circuit_id(),
+        api_key.name()
+    );
+    Box::new(
+        splinterd::submit_batches(
+            &state.client,
+            &state.config,
+            state.token_provider.as_ref(),
+            &request_id,
+            service.circuit_id(),
+            service.service_id(),
+            batch_list.to_vec(),
+        )
+        .then(move |result| match result {
+            Ok(()) => Ok(HttpResponse::Accepted().json(json!({
+                "batch_ids": batch_ids,
+                "link": format!(
+                    "/circuits/{}/batch_statuses?ids={}",
+                    service.circuit_id(),
+                    batch_ids.join(",")
+                ),
+            }))),
+            Err(err) => {
+                error!(
+                    "Request {}: unable to forward batches: {}",
+                    request_id.as_str(),
+                    err
+                );
+                Ok(err.to_response())
+            }
+        }),
+    )
+}
+
+/// Lists the Sabre contracts deployed on the circuit, as seen in its contract registry since the
+/// event listener started.
+pub fn list_circuit_contracts(
+    api_key: ApiKey,
+    circuit_id: web::Path<String>,
+    state: web::Data<AppState>,
+) -> Result<HttpResponse, Error> {
+    api_key.require(Role::ReadOnly)?;
+    if let Err(response) = find_service(&state.roster, &circuit_id, None) {
+        return Ok(response);
+    }
+    Ok(HttpResponse::Ok().json(json!({ "data": state.contracts.list(&circuit_id) })))
+}
+
+/// Returns the statuses of batches submitted to this node's scabbard service on the circuit, in
+/// the order of `ids`.
+///
+/// Statuses of committed and invalid batches are remembered, so only those still pending are
+/// asked of scabbard, which waits up to `wait` seconds for them to be committed.
+pub fn list_batch_statuses(
+    request_id: RequestId,
+    api_key: ApiKey,
+    circuit_id: web::Path<String>,
+    query: web::Query<BatchStatusQuery>,
+    state: web::Data<AppState>,
+) -> Box<dyn Future<Item = HttpResponse, Error = Error>> {
+    if let Err(err) = api_key.require(Role::ReadOnly) {
+        return Box::new(future::err(err));
+    }
+    let service = match find_service(&state.roster, &circuit_id, None) {
+        Ok(service) => service,
+        Err(response) => return Box::new(future::ok(response)),
+    };
+    let ids = query
+        .ids
+        .split(',')
+        .filter(|id| !id.is_empty())
+        .map(ToOwned::to_owned)
+        .collect::<Vec<_>>();
+    if ids.is_empty()
+        || !ids
+            .iter()
+            .all(|id| id.chars().all(|c| c.is_ascii_hexdigit()))
+    {
+        return Box::new(future::ok(HttpResponse::BadRequest().json(json!({
+            "message": "ids must be a comma separated list of batch ids",
+        }))));
+    }
+
+    let pending = ids
+        .iter()
+        .filter(|id| state.batch_status_cache.get(&circuit_id, id).is_none())
+        .cloned()
+        .collect::<Vec<_>>();
+    if pending.is_empty() {
+        return Box::new(future::ok(statuses_response(
+            &circuit_id,
+            &ids,
+            &state.batch_status_cache,
+            Vec::new(),
+        )));
+    }
+    let mut path = format!(
+        "/scabbard/{}/{}/batch_statuses?ids={}",
+        service.circuit_id(),
+        service.service_id(),
+        pending.join(",")
+    );
+    if let Some(wait) = query.wait {
+        path.push_str(&format!("&wait={}", wait.min(MAX_WAIT_SECS)));
+    }
+
+    Box::new(
+        splinterd::get_json(
+            &state.client,
+            &state.config,
+            state.token_provider.as_ref(),
+            &request_id,
+            &path,
+        )
+        .then(move |result| match result {
+            Ok(Value::Array(statuses)) => {
+                let cache = &state.batch_status_cache;
+                statuses
+                    .iter()
+                    .for_each(|status| cache.store(&circuit_id, status));
+                Ok(statuses_response(&circuit_id, &ids, cache, statuses))
+            }
+            Ok(_) => Ok(HttpResponse::BadGateway().json(json!({
+                "message": "scabbard responded with an invalid batch status list",
+            }))),
+            Err(err) => {
+                error!(
+                    "Request {}: unable to fetch batch statuses: {}",
+                    request_id.as_str(),
+                    err
+                );
+                Ok(err.to_response())
+            }
+        }),
+    )
+}
+
+/// Builds the response listing the status of each batch, from the cache or else those fetched.
+fn statuses_response(
+    circuit_id: &str,
+    ids: &[String],
+    cache: &BatchStatusCache,
+    fetched: Vec<Value>,
+) -> HttpResponse {
+    let data = ids
+        .iter()
+        .filter_map(|id| {
+            cache.get(circuit_id, id).or_else(|| {
+                fetched
+                    .iter()
+                    .find(|status| status["id"].as_str() == Some(id.as_str()))
+                    .cloned()
+            })
+        })
+        .collect::<Vec<_>>();
+    HttpResponse::Ok().json(json!({ "data": data }))
+}
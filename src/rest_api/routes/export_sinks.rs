@@ -0,0 +1,44 @@
+/*
+ * Copyright 2019 Walmart Inc.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * -----------------------------------------------------------------------------
+ */
+
+use actix_web::{web, Error, HttpResponse};
+
+use crate::config::Role;
+use crate::rest_api::auth::ApiKey;
+use crate::rest_api::AppState;
+
+/// Lists the export sinks with the totals of the batches written to them.
+pub fn list_export_sinks(
+    api_key: ApiKey,
+    state: web::Data<AppState>,
+) -> Result<HttpResponse, Error> {
+    api_key.require(Role::ReadOnly)?;
+    Ok(HttpResponse::Ok().json(json!({ "data": state.publisher.export_sinks() })))
+}
+
+/// Lists the most recent batches written to an export sink, oldest first.
+pub fn list_export_runs(
+    api_key: ApiKey,
+    state: web::Data<AppState>,
+    sink_id: web::Path<usize>,
+) -> Result<HttpResponse, Error> {
+    api_key.require(Role::ReadOnly)?;
+    match state.publisher.export_runs(*sink_id) {
+        Some(runs) => Ok(HttpResponse::Ok().json(json!({ "data": runs }))),
+        None => Ok(HttpResponse::NotFound().json(json!({ "message": "No such export sink" }))),
+    }
+}
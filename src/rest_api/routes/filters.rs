@@ -0,0 +1,60 @@
+/*
+ * Copyright 2019 Walmart Inc.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * -----------------------------------------------------------------------------
+ */
+
+use actix_web::{web, Error, HttpRequest, HttpResponse};
+
+use crate::config::{check_filter_rules, FilterRule, Role};
+use crate::rest_api::auth::ApiKey;
+use crate::rest_api::etag::json_with_etag;
+use crate::rest_api::AppState;
+
+/// Lists the admin event filter rules in effect.
+pub fn fetch_filters(
+    req: HttpRequest,
+    api_key: ApiKey,
+    state: web::Data<AppState>,
+) -> Result<HttpResponse, Error> {
+    api_key.require(Role::ReadOnly)?;
+    Ok(json_with_etag(
+        &req,
+        &json!({
+            "default": state.filter.default_action(),
+            "rules": state.filter.rules(),
+        }),
+    ))
+}
+
+/// Replaces the admin event filter rules until the event listener restarts.
+pub fn replace_filters(
+    api_key: ApiKey,
+    state: web::Data<AppState>,
+    rules: web::Json<Vec<FilterRule>>,
+) -> Result<HttpResponse, Error> {
+    api_key.require(Role::Admin)?;
+    let rules = rules.into_inner();
+    if let Err(err) = check_filter_rules(&rules, state.config.deployment_config().export_sinks()) {
+        return Ok(HttpResponse::BadRequest().json(json!({
+            "message": format!("Invalid rules, missing {}", err),
+        })));
+    }
+    state.filter.set_rules(rules);
+    info!("Admin event filter rules replaced by {}", api_key.name());
+    Ok(HttpResponse::Ok().json(json!({
+        "default": state.filter.default_action(),
+        "rules": state.filter.rules(),
+    })))
+}
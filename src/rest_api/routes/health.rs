@@ -0,0 +1,56 @@
+/*
+ * Copyright 2019 Walmart Inc.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * -----------------------------------------------------------------------------
+ */
+
+use actix_web::{web, HttpResponse};
+
+use crate::rest_api::AppState;
+
+/// Reports that the process is up, for use as a liveness probe.
+pub fn fetch_liveness() -> HttpResponse {
+    HttpResponse::Ok().json(json!({
+        "status": "live",
+        "version": env!("CARGO_PKG_VERSION"),
+    }))
+}
+
+/// Reports whether admin events are being received, for use as a readiness probe.
+///
+/// Responds with 503 unless every admin websocket is connected to splinterd.
+pub fn fetch_readiness(state: web::Data<AppState>) -> HttpResponse {
+    let connection_status = &state.connection_status;
+    if connection_status.is_live() {
+        HttpResponse::Ok().json(json!({ "status": "ready" }))
+    } else {
+        HttpResponse::ServiceUnavailable().json(json!({
+            "status": "not_ready",
+            "splinterd": connection_status.snapshot(),
+        }))
+    }
+}
+
+/// Reports the state of the admin websockets.
+///
+/// Responds with 503 unless every connection is up.
+pub fn fetch_splinterd_health(state: web::Data<AppState>) -> HttpResponse {
+    let connection_status = &state.connection_status;
+    let connections = connection_status.snapshot();
+    if connection_status.is_live() {
+        HttpResponse::Ok().json(connections)
+    } else {
+        HttpResponse::ServiceUnavailable().json(connections)
+    }
+}
@@ -0,0 +1,188 @@
+/*
+ * Copyright 2019 Walmart Inc.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * -----------------------------------------------------------------------------
+ */
+
+use actix_web::{web, Error, HttpResponse};
+
+use crate::config::Role;
+use crate::key_registry::{validate_public_key, RegisteredKey};
+use crate::rest_api::auth::ApiKey;
+use crate::rest_api::AppState;
+
+#[derive(Deserialize)]
+pub struct KeyRegistration {
+    public_key: String,
+    name: String,
+    node_id: Option<String>,
+    organization: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct KeyUpdate {
+    name: String,
+    node_id: Option<String>,
+    organization: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct KeyDeactivation {
+    reason: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct KeyQuery {
+    node_id: Option<String>,
+    organization: Option<String>,
+    active: Option<bool>,
+}
+
+/// Lists the registered keys, optionally only those of a node or organization, or only the
+/// active or deactivated ones.
+pub fn list_keys(
+    api_key: ApiKey,
+    state: web::Data<AppState>,
+    query: web::Query<KeyQuery>,
+) -> Result<HttpResponse, Error> {
+    api_key.require(Role::ReadOnly)?;
+    let node_id = query.node_id.as_ref().map(String::as_str);
+    let organization = query.organization.as_ref().map(String::as_str);
+    let keys = state
+        .keys
+        .list()
+        .into_iter()
+        .filter(|key| node_id.map_or(true, |node_id| key.node_id() == Some(node_id)))
+        .filter(|key| {
+            organization.map_or(true, |organization| {
+                key.organization() == Some(organization)
+            })
+        })
+        .filter(|key| {
+            query
+                .active
+                .map_or(true, |active| key.is_active() == active)
+        })
+        .collect::<Vec<RegisteredKey>>();
+    Ok(HttpResponse::Ok().json(json!({ "data": keys })))
+}
+
+/// Returns a registered key, active or not.
+pub fn fetch_key(
+    api_key: ApiKey,
+    state: web::Data<AppState>,
+    public_key: web::Path<String>,
+) -> Result<HttpResponse, Error> {
+    api_key.require(Role::ReadOnly)?;
+    match state.keys.get(&public_key) {
+        Some(key) => Ok(HttpResponse::Ok().json(key)),
+        None => Ok(not_found()),
+    }
+}
+
+/// Registers a user's public key, with the name shown for them and the node and organization
+/// they belong to.
+pub fn register_key(
+    api_key: ApiKey,
+    state: web::Data<AppState>,
+    registration: web::Json<KeyRegistration>,
+) -> Result<HttpResponse, Error> {
+    api_key.require(Role::Admin)?;
+    if let Err(err) = validate_public_key(&registration.public_key) {
+        return Ok(HttpResponse::BadRequest().json(json!({
+            "message": format!("Invalid public key: {}", err),
+        })));
+    }
+    match state.keys.register(
+        &registration.public_key,
+        &registration.name,
+        registration.node_id.as_ref().map(String::as_str),
+        registration.organization.as_ref().map(String::as_str),
+    ) {
+        Ok(true) => {
+            info!(
+                "Key {} of {} registered by {}",
+                registration.public_key,
+                registration.name,
+                api_key.name()
+            );
+            Ok(HttpResponse::Created().json(state.keys.get(&registration.public_key)))
+        }
+        Ok(false) => Ok(HttpResponse::Conflict().json(json!({
+            "message": "The key is already registered",
+        }))),
+        Err(err) => {
+            error!("Unable to register key: {}", err);
+            Ok(HttpResponse::InternalServerError()
+                .json(json!({ "message": "Unable to register key" })))
+        }
+    }
+}
+
+/// Replaces the name, node and organization of a registered key.
+pub fn update_key(
+    api_key: ApiKey,
+    state: web::Data<AppState>,
+    public_key: web::Path<String>,
+    update: web::Json<KeyUpdate>,
+) -> Result<HttpResponse, Error> {
+    api_key.require(Role::Admin)?;
+    match state.keys.update(
+        &public_key,
+        &update.name,
+        update.node_id.as_ref().map(String::as_str),
+        update.organization.as_ref().map(String::as_str),
+    ) {
+        Ok(Some(key)) => {
+            info!("Key {} updated by {}", public_key, api_key.name());
+            Ok(HttpResponse::Ok().json(key))
+        }
+        Ok(None) => Ok(not_found()),
+        Err(err) => {
+            error!("Unable to update key: {}", err);
+            Ok(HttpResponse::InternalServerError()
+                .json(json!({ "message": "Unable to update key" })))
+        }
+    }
+}
+
+/// Deactivates a registered key, such as one that was compromised; votes signed with it are
+/// refused from then on. The key stays listed and cannot be registered again.
+pub fn deactivate_key(
+    api_key: ApiKey,
+    state: web::Data<AppState>,
+    public_key: web::Path<String>,
+    deactivation: Option<web::Json<KeyDeactivation>>,
+) -> Result<HttpResponse, Error> {
+    api_key.require(Role::Admin)?;
+    let reason = deactivation
+        .as_ref()
+        .and_then(|deactivation| deactivation.reason.as_ref().map(String::as_str));
+    match state.keys.deactivate(&public_key, reason) {
+        Ok(Some(key)) => {
+            warn!("Key {} deactivated by {}", public_key, api_key.name());
+            Ok(HttpResponse::Ok().json(key))
+        }
+        Ok(None) => Ok(not_found()),
+        Err(err) => {
+            error!("Unable to deactivate key: {}", err);
+            Ok(HttpResponse::InternalServerError()
+                .json(json!({ "message": "Unable to deactivate key" })))
+        }
+    }
+}
+
+fn not_found() -> HttpResponse {
+    HttpResponse::NotFound().json(json!({ "message": "No such key" }))
+}
@@ -0,0 +1,54 @@
+/*
+ * Copyright 2019 Walmart Inc.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * -----------------------------------------------------------------------------
+ */
+
+mod api_keys;
+mod circuits;
+mod export_sinks;
+mod filters;
+mod health;
+mod keys;
+mod metrics;
+mod nodes;
+mod proposals;
+mod reprocess;
+mod sabre;
+mod openapi;
+mod stream;
+mod submissions;
+mod submit;
+mod subscribe;
+mod validation;
+mod webhooks;
+
+pub use api_keys::*;
+pub use circuits::*;
+pub use export_sinks::*;
+pub use filters::*;
+pub use health::*;
+pub use keys::*;
+pub use metrics::*;
+pub use nodes::*;
+pub use proposals::*;
+pub use reprocess::*;
+pub use sabre::*;
+pub use openapi::*;
+pub use stream::*;
+pub use submissions::*;
+pub use submit::*;
+pub use subscribe::*;
+pub use validation::*;
+pub use webhooks::*;
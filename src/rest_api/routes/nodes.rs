@@ -0,0 +1,108 @@
+/*
+ * Copyright 2019 Walmart Inc.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * -----------------------------------------------------------------------------
+ */
+
+use std::collections::{BTreeSet, HashMap};
+
+use actix_web::{web, Error, HttpRequest, HttpResponse};
+use futures::future::{self, Future};
+use splinter::node_registry::Node;
+
+use crate::config::Role;
+use crate::rest_api::auth::ApiKey;
+use crate::rest_api::csv::{accepts_csv, csv_response};
+use crate::rest_api::etag::json_with_etag;
+use crate::rest_api::request_id::RequestId;
+use crate::rest_api::splinterd;
+use crate::rest_api::AppState;
+
+/// Lists the nodes in splinterd's node registry, cached for node_cache_ttl_secs.
+///
+/// Each query parameter filters the nodes by the metadata entry of the same name, keeping those
+/// whose value contains the parameter regardless of case; for example `?organization=acme`.
+/// Clients sending `Accept: text/csv` receive a row per node, with a column per metadata entry.
+pub fn list_nodes(
+    req: HttpRequest,
+    request_id: RequestId,
+    api_key: ApiKey,
+    filters: web::Query<HashMap<String, String>>,
+    state: web::Data<AppState>,
+) -> Box<dyn Future<Item = HttpResponse, Error = Error>> {
+    if let Err(err) = api_key.require(Role::ReadOnly) {
+        return Box::new(future::err(err));
+    }
+    let filters = filters.into_inner();
+
+    Box::new(
+        splinterd::fetch_nodes(
+            &state.client,
+            &state.config,
+            state.token_provider.as_ref(),
+            &request_id,
+            &state.node_cache,
+        )
+        .then(move |nodes| match nodes {
+            Ok(nodes) => Ok(nodes_response(&req, nodes, &filters)),
+            Err(err) => {
+                error!(
+                    "Request {}: unable to list splinterd's nodes: {}",
+                    request_id.as_str(),
+                    err
+                );
+                Ok(err.to_response())
+            }
+        }),
+    )
+}
+
+fn nodes_response(
+    req: &HttpRequest,
+    nodes: Vec<Node>,
+    filters: &HashMap<String, String>,
+) -> HttpResponse {
+    let nodes = nodes
+        .into_iter()
+        .filter(|node| {
+            filters.iter().all(|(key, filter)| {
+                node.metadata
+                    .get(key)
+                    .map(|value| value.to_lowercase().contains(&filter.to_lowercase()))
+                    .unwrap_or(false)
+            })
+        })
+        .collect::<Vec<_>>();
+    if accepts_csv(req) {
+        let metadata_keys = nodes
+            .iter()
+            .flat_map(|node| node.metadata.keys())
+            .collect::<BTreeSet<_>>();
+        let mut columns = vec!["identity"];
+        columns.extend(metadata_keys.iter().map(|key| key.as_str()));
+        return csv_response(
+            &columns,
+            nodes.iter().map(|node| {
+                let mut row = vec![node.identity.clone()];
+                row.extend(
+                    metadata_keys
+                        .iter()
+                        .map(|key| node.metadata.get(*key).cloned().unwrap_or_default()),
+                );
+                row
+            }),
+        );
+    }
+    json_with_etag(req, &json!({ "data": nodes }))
+}
@@ -0,0 +1,103 @@
+/*
+ * Copyright 2019 Walmart Inc.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * -----------------------------------------------------------------------------
+ */
+
+use actix_web::HttpResponse;
+
+const OPENAPI_DOCUMENT: &str = include_str!("../openapi.json");
+
+/// Serves the OpenAPI 3 document describing the REST API.
+///
+/// The document is maintained by hand in `src/rest_api/openapi.json`; a test checks that it
+/// describes exactly the routes registered in `src/rest_api/mod.rs`.
+pub fn fetch_openapi() -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type("application/json")
+        .body(OPENAPI_DOCUMENT)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::collections::BTreeSet;
+
+    use serde_json::Value;
+
+    /// the source that registers the routes with the server
+    const ROUTE_TABLE: &str = include_str!("../mod.rs");
+
+    const METHODS: [&str; 4] = ["get", "post", "put", "delete"];
+
+    /// Returns the method and path of every route registered in the route table.
+    fn registered_routes() -> BTreeSet<(String, String)> {
+        let mut routes = BTreeSet::new();
+        for resource in ROUTE_TABLE.split("web::resource(\"").skip(1) {
+            let path = &resource[..resource.find('"').unwrap()];
+            for method in &METHODS {
+                if resource.contains(&format!("web::{}()", method)) {
+                    routes.insert((method.to_string(), path.to_string()));
+                }
+            }
+        }
+        routes
+    }
+
+    /// Returns the method and path of every operation in the OpenAPI document.
+    fn documented_routes() -> BTreeSet<(String, String)> {
+        let document: Value = serde_json::from_str(OPENAPI_DOCUMENT).unwrap();
+        let mut routes = BTreeSet::new();
+        for (path, operations) in document["paths"].as_object().unwrap() {
+            for method in operations.as_object().unwrap().keys() {
+                routes.insert((method.to_string(), path.to_string()));
+            }
+        }
+        routes
+    }
+
+    #[test]
+    fn route_table_is_read() {
+        let routes = registered_routes();
+        assert!(routes.contains(&("get".to_string(), "/openapi.json".to_string())));
+        assert!(routes.contains(&("post".to_string(), "/submit".to_string())));
+    }
+
+    #[test]
+    fn every_route_is_documented() {
+        let undocumented = registered_routes()
+            .difference(&documented_routes())
+            .cloned()
+            .collect::<Vec<_>>();
+        assert!(
+            undocumented.is_empty(),
+            "missing from openapi.json: {:?}",
+            undocumented
+        );
+    }
+
+    #[test]
+    fn every_documented_route_is_served() {
+        let unserved = documented_routes()
+            .difference(&registered_routes())
+            .cloned()
+            .collect::<Vec<_>>();
+        assert!(
+            unserved.is_empty(),
+            "not registered in the route table: {:?}",
+            unserved
+        );
+    }
+}
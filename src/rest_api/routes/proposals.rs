@@ -0,0 +1,483 @@
+/*
+ * Copyright 2019 Walmart Inc.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * -----------------------------------------------------------------------------
+ */
+
+use std::collections::HashMap;
+
+use actix_web::error::BlockingError;
+use actix_web::http::StatusCode;
+use actix_web::{web, Error, HttpRequest, HttpResponse};
+use crypto::digest::Digest;
+use crypto::sha2::Sha512;
+use futures::future::{self, Either, Future};
+use protobuf::Message;
+use serde_json::Value;
+use splinter::protos::admin::{
+    CircuitManagementPayload, CircuitManagementPayload_Action, CircuitManagementPayload_Header,
+    CircuitProposalVote, CircuitProposalVote_Vote,
+};
+use uuid::Uuid;
+
+use crate::config::Role;
+use crate::event_handler::{from_hex, to_hex};
+use crate::key_registry::RegisteredKey;
+use crate::rest_api::auth::ApiKey;
+use crate::rest_api::csv::{accepts_csv, csv_response};
+use crate::rest_api::etag::json_with_etag;
+use crate::rest_api::rate_limit;
+use crate::rest_api::request_id::RequestId;
+use crate::rest_api::splinterd;
+use crate::rest_api::submissions::ExpectedEvent;
+use crate::rest_api::AppState;
+use crate::signer::{sign_circuit_management_payload, Signer, SignerError};
+
+use super::submit::{admin_submit_request, send_payload};
+
+/// default number of votes in a page
+const DEFAULT_LIMIT: usize = 100;
+/// largest number of votes in a page
+const MAX_LIMIT: usize = 1000;
+/// fields of a vote, in the order of the CSV columns
+const VOTE_COLUMNS: [&str; 5] = [
+    "voter_public_key",
+    "voter_name",
+    "voter_node_id",
+    "organization",
+    "vote",
+];
+
+#[derive(Deserialize)]
+pub struct Paging {
+    #[serde(default)]
+    offset: usize,
+    #[serde(default = "default_limit")]
+    limit: usize,
+}
+
+fn default_limit() -> usize {
+    DEFAULT_LIMIT
+}
+
+#[derive(Clone, Copy, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VoteChoice {
+    Accept,
+    Reject,
+}
+
+#[derive(Deserialize)]
+pub struct ProposalVote {
+    vote: VoteChoice,
+}
+
+#[derive(Deserialize)]
+pub struct VotePayloadRequest {
+    vote: VoteChoice,
+    /// public key the client signs the vote with, as hex
+    requester: String,
+}
+
+/// Lists the votes recorded on a circuit proposal, with the organization of each voter's node
+/// looked up in the node registry, and the name registered for each voter's key.
+///
+/// A voter whose node has no organization is given the organization registered for their key.
+///
+/// The proposal is identified by its circuit id; its votes are paged with `offset` and `limit`.
+/// Clients sending `Accept: text/csv` receive the page as CSV.
+pub fn list_proposal_votes(
+    req: HttpRequest,
+    request_id: RequestId,
+    api_key: ApiKey,
+    circuit_id: web::Path<String>,
+    paging: web::Query<Paging>,
+    state: web::Data<AppState>,
+) -> Box<dyn Future<Item = HttpResponse, Error = Error>> {
+    if let Err(err) = api_key.require(Role::ReadOnly) {
+        return Box::new(future::err(err));
+    }
+    let offset = paging.offset;
+    let limit = paging.limit.min(MAX_LIMIT);
+    let token_provider = state.token_provider.as_ref();
+
+    let proposal = splinterd::get_json(
+        &state.client,
+        &state.config,
+        token_provider,
+        &request_id,
+        &format!("/admin/proposals/{}", circuit_id),
+    );
+    let nodes = splinterd::fetch_nodes(
+        &state.client,
+        &state.config,
+        token_provider,
+        &request_id,
+        &state.node_cache,
+    );
+    let keys = state.keys.clone();
+    Box::new(proposal.join(nodes).then(move |result| match result {
+        Ok((proposal, nodes)) => {
+            let organizations = nodes
+                .into_iter()
+                .filter_map(|node| {
+                    let organization = node.metadata.get("organization").cloned()?;
+                    Some((node.identity, organization))
+                })
+                .collect::<HashMap<_, _>>();
+            let votes = proposal["votes"]
+                .as_array()
+                .map(Vec::as_slice)
+                .unwrap_or_default();
+            let page = votes
+                .iter()
+                .skip(offset)
+                .take(limit)
+                .map(|vote| {
+                    let voter_node_id = vote["voter_node_id"].as_str().unwrap_or_default();
+                    let key = keys.get(vote["public_key"].as_str().unwrap_or_default());
+                    let organization = organizations.get(voter_node_id).cloned().or_else(|| {
+                        key.as_ref()
+                            .and_then(|key| key.organization().map(ToOwned::to_owned))
+                    });
+                    json!({
+                        "voter_public_key": vote["public_key"],
+                        "voter_name": key.as_ref().map(RegisteredKey::name),
+                        "voter_node_id": voter_node_id,
+                        "organization": organization,
+                        "vote": vote["vote"],
+                    })
+                })
+                .collect::<Vec<Value>>();
+            if accepts_csv(&req) {
+                return Ok(csv_response(
+                    &VOTE_COLUMNS,
+                    page.iter().map(|vote| {
+                        VOTE_COLUMNS
+                            .iter()
+                            .map(|column| vote[column].as_str().unwrap_or_default().to_string())
+                            .collect()
+                    }),
+                ));
+            }
+            Ok(json_with_etag(
+                &req,
+                &json!({
+                    "data": page,
+                    "paging": {
+                        "offset": offset,
+                        "limit": limit,
+                        "total": votes.len(),
+                    },
+                }),
+            ))
+        }
+        Err(err) => {
+            error!(
+                "Request {}: unable to list the votes on proposal {}: {}",
+                request_id.as_str(),
+                circuit_id,
+                err
+            );
+            Ok(err.to_response())
+        }
+    }))
+}
+
+/// Votes on a circuit proposal on behalf of this node, signed with the event listener's signer;
+/// requires server_side_signing. Once keys are registered, the signer's key must be an active
+/// registered key.
+///
+/// The vote is given a submission id whose status can be followed with GET /submissions/{id}.
+pub fn vote_on_proposal(
+    req: HttpRequest,
+    request_id: RequestId,
+    api_key: ApiKey,
+    circuit_id: web::Path<String>,
+    vote: web::Json<ProposalVote>,
+    state: web::Data<AppState>,
+) -> Box<dyn Future<Item = HttpResponse, Error = Error>> {
+    if let Err(err) = api_key.require(Role::Member) {
+        return Box::new(future::err(err));
+    }
+    if let Err(response) = rate_limit::limit(&state.rate_limiter, &req, &api_key) {
+        return Box::new(future::ok(response));
+    }
+    let signer = match &state.signer {
+        Some(signer) if state.config.deployment_config().server_side_signing() => signer.clone(),
+        _ => {
+            return Box::new(future::ok(HttpResponse::NotFound().json(json!({
+                "message": "Server-side signing is not enabled; configure server_side_signing \
+                            to enable it",
+            }))))
+        }
+    };
+    if let Err(reason) = state.keys.check_voter(signer.public_key()) {
+        return Box::new(future::ok(HttpResponse::Forbidden().json(json!({
+            "message": format!("The event listener's key may not vote: {}", reason),
+        }))));
+    }
+    let circuit_id = circuit_id.into_inner();
+    let choice = vote.vote;
+    info!("Voting on proposal {} for {}", circuit_id, api_key.name());
+
+    let proposal = splinterd::get_json(
+        &state.client,
+        &state.config,
+        state.token_provider.as_ref(),
+        &request_id,
+        &format!("/admin/proposals/{}", circuit_id),
+    );
+    let node_id = state.node_id.clone();
+    let vote_request_id = request_id.clone();
+    let vote_circuit_id = circuit_id.clone();
+    let voter = signer.public_key().to_string();
+    let payload = proposal
+        .map_err({
+            let request_id = request_id.clone();
+            let circuit_id = circuit_id.clone();
+            move |err| {
+                error!(
+                    "Request {}: unable to fetch proposal {}: {}",
+                    request_id.as_str(),
+                    circuit_id,
+                    err
+                );
+                err.to_response()
+            }
+        })
+        .and_then(move |proposal| {
+            // a remote signer blocks while it signs
+            web::block(move || {
+                vote_payload(&vote_circuit_id, &proposal, choice, &node_id, &*signer)
+            })
+            .map_err(move |err| {
+                error!(
+                    "Request {}: unable to build the vote: {}",
+                    vote_request_id.as_str(),
+                    err
+                );
+                match err {
+                    BlockingError::Error(SignerError::RemoteError(_)) => HttpResponse::BadGateway()
+                        .json(
+                            json!({ "message": "The signing service was unable to sign the vote" }),
+                        ),
+                    _ => HttpResponse::InternalServerError()
+                        .json(json!({ "message": "Unable to build the vote" })),
+                }
+            })
+        });
+    Box::new(payload.then(move |payload| {
+        let request = payload.and_then(|payload| {
+            admin_submit_request(
+                &state.client,
+                &state.config,
+                state.token_provider.as_ref(),
+                &request_id,
+            )
+            .map(|request| (request, payload))
+        });
+        match request {
+            Ok((request, payload)) => Either::A(send_payload(request, request_id, payload).map(
+                move |(status, mut body)| {
+                    let submission_id = Uuid::new_v4().to_simple().to_string();
+                    match status {
+                        StatusCode::ACCEPTED => state.submission_tracker.accepted(
+                            &submission_id,
+                            Some(ExpectedEvent::ProposalVote { circuit_id, voter }),
+                        ),
+                        StatusCode::BAD_REQUEST => state.submission_tracker.invalid(
+                            &submission_id,
+                            body["splinterd_response"]
+                                .as_str()
+                                .unwrap_or_default()
+                                .to_string(),
+                        ),
+                        _ => return HttpResponse::build(status).json(body),
+                    }
+                    body["submission_id"] = json!(submission_id);
+                    HttpResponse::build(status).json(body)
+                },
+            )),
+            Err(response) => Either::B(future::ok(response)),
+        }
+    }))
+}
+
+/// Builds an unsigned CircuitManagementPayload voting on a circuit proposal, for clients that
+/// sign their own votes rather than hand-rolling the protobuf.
+///
+/// The payload's header names the requester and this node. The client signs the header, sets
+/// the signature and submits the payload with POST /submit.
+pub fn build_vote_payload(
+    request_id: RequestId,
+    api_key: ApiKey,
+    circuit_id: web::Path<String>,
+    vote: web::Json<VotePayloadRequest>,
+    state: web::Data<AppState>,
+) -> Box<dyn Future<Item = HttpResponse, Error = Error>> {
+    if let Err(err) = api_key.require(Role::Member) {
+        return Box::new(future::err(err));
+    }
+    let requester = match from_hex(&vote.requester) {
+        Ok(requester) => requester,
+        Err(err) => {
+            return Box::new(future::ok(HttpResponse::BadRequest().json(json!({
+                "message": format!("Invalid requester: {}", err),
+            }))))
+        }
+    };
+    let circuit_id = circuit_id.into_inner();
+    let choice = vote.vote;
+    let node_id = state.node_id.clone();
+
+    let proposal = splinterd::get_json(
+        &state.client,
+        &state.config,
+        state.token_provider.as_ref(),
+        &request_id,
+        &format!("/admin/proposals/{}", circuit_id),
+    );
+    Box::new(proposal.then(move |result| match result {
+        Ok(proposal) => {
+            match unsigned_vote_payload(&circuit_id, &proposal, choice, &node_id, requester) {
+                Ok(payload) => Ok(HttpResponse::Ok().json(json!({
+                    "payload_bytes": to_hex(&payload),
+                }))),
+                Err(err) => {
+                    error!(
+                        "Request {}: unable to build the vote: {}",
+                        request_id.as_str(),
+                        err
+                    );
+                    Ok(HttpResponse::InternalServerError()
+                        .json(json!({ "message": "Unable to build the vote" })))
+                }
+            }
+        }
+        Err(err) => {
+            error!(
+                "Request {}: unable to fetch proposal {}: {}",
+                request_id.as_str(),
+                circuit_id,
+                err
+            );
+            Ok(err.to_response())
+        }
+    }))
+}
+
+/// Builds a signed CircuitManagementPayload voting on the proposal, as reported by splinterd.
+fn vote_payload(
+    circuit_id: &str,
+    proposal: &Value,
+    choice: VoteChoice,
+    node_id: &str,
+    signer: &dyn Signer,
+) -> Result<web::Bytes, SignerError> {
+    let requester = from_hex(signer.public_key()).map_err(SignerError::KeyError)?;
+    let payload = unsigned_vote_payload(circuit_id, proposal, choice, node_id, requester)
+        .map_err(SignerError::SigningError)?;
+    sign_circuit_management_payload(signer, &payload).map(web::Bytes::from)
+}
+
+/// Builds an unsigned CircuitManagementPayload voting on the proposal, as reported by splinterd,
+/// on behalf of the requester.
+fn unsigned_vote_payload(
+    circuit_id: &str,
+    proposal: &Value,
+    choice: VoteChoice,
+    node_id: &str,
+    requester: Vec<u8>,
+) -> Result<Vec<u8>, String> {
+    let circuit_hash = proposal["circuit_hash"]
+        .as_str()
+        .ok_or("splinterd reported no circuit hash")?;
+    let mut vote = CircuitProposalVote::new();
+    vote.set_circuit_id(circuit_id.to_string());
+    vote.set_circuit_hash(circuit_hash.to_string());
+    vote.set_vote(match choice {
+        VoteChoice::Accept => CircuitProposalVote_Vote::ACCEPT,
+        VoteChoice::Reject => CircuitProposalVote_Vote::REJECT,
+    });
+    let vote_bytes = vote.write_to_bytes().map_err(|err| err.to_string())?;
+
+    let mut sha = Sha512::new();
+    sha.input(&vote_bytes);
+    let mut hash = [0; 64];
+    sha.result(&mut hash);
+    let mut header = CircuitManagementPayload_Header::new();
+    header.set_action(CircuitManagementPayload_Action::CIRCUIT_PROPOSAL_VOTE);
+    header.set_requester(requester);
+    header.set_requester_node_id(node_id.to_string());
+    header.set_payload_sha512(hash.to_vec());
+
+    let mut payload = CircuitManagementPayload::new();
+    payload.set_header(header.write_to_bytes().map_err(|err| err.to_string())?);
+    payload.set_circuit_proposal_vote(vote);
+    payload.write_to_bytes().map_err(|err| err.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unsigned_vote_payload_names_the_requester() {
+        let proposal = json!({ "circuit_id": "01234-ABCDE", "circuit_hash": "abcdef" });
+        let requester = vec![2, 171, 205];
+        let bytes = unsigned_vote_payload(
+            "01234-ABCDE",
+            &proposal,
+            VoteChoice::Reject,
+            "node-a",
+            requester.clone(),
+        )
+        .unwrap();
+
+        let payload: CircuitManagementPayload = protobuf::parse_from_bytes(&bytes).unwrap();
+        assert!(payload.get_signature().is_empty());
+        let header: CircuitManagementPayload_Header =
+            protobuf::parse_from_bytes(payload.get_header()).unwrap();
+        assert_eq!(
+            header.get_action(),
+            CircuitManagementPayload_Action::CIRCUIT_PROPOSAL_VOTE
+        );
+        assert_eq!(header.get_requester(), &requester[..]);
+        assert_eq!(header.get_requester_node_id(), "node-a");
+
+        let vote = payload.get_circuit_proposal_vote();
+        assert_eq!(vote.get_circuit_id(), "01234-ABCDE");
+        assert_eq!(vote.get_circuit_hash(), "abcdef");
+        assert_eq!(vote.get_vote(), CircuitProposalVote_Vote::REJECT);
+        let mut sha = Sha512::new();
+        sha.input(&vote.write_to_bytes().unwrap());
+        let mut hash = [0; 64];
+        sha.result(&mut hash);
+        assert_eq!(header.get_payload_sha512(), &hash[..]);
+    }
+
+    #[test]
+    fn unsigned_vote_payload_needs_the_circuit_hash() {
+        let proposal = json!({ "circuit_id": "01234-ABCDE" });
+        assert!(unsigned_vote_payload(
+            "01234-ABCDE",
+            &proposal,
+            VoteChoice::Accept,
+            "node-a",
+            vec![2]
+        )
+        .is_err());
+    }
+}
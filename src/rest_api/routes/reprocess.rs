@@ -0,0 +1,161 @@
+/*
+ * Copyright 2019 Walmart Inc.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * -----------------------------------------------------------------------------
+ */
+
+use actix_web::error::BlockingError;
+use actix_web::{web, Error, HttpRequest, HttpResponse};
+use futures::future::{self, Either, Future};
+use serde_json::Value;
+use splinter::admin::messages::CircuitProposal;
+
+use crate::config::Role;
+use crate::event_handler::{from_hex, EventHandlerError};
+use crate::rest_api::auth::ApiKey;
+use crate::rest_api::rate_limit;
+use crate::rest_api::request_id::RequestId;
+use crate::rest_api::splinterd;
+use crate::rest_api::AppState;
+
+/// Lists the admin events that failed to be exported, oldest first.
+pub fn list_failed_events(
+    api_key: ApiKey,
+    state: web::Data<AppState>,
+) -> Result<HttpResponse, Error> {
+    api_key.require(Role::Admin)?;
+    Ok(HttpResponse::Ok().json(json!({ "data": state.reprocessor.failed_events() })))
+}
+
+/// Exports a failed admin event again.
+pub fn retry_failed_event(
+    req: HttpRequest,
+    api_key: ApiKey,
+    state: web::Data<AppState>,
+    id: web::Path<u64>,
+) -> Box<dyn Future<Item = HttpResponse, Error = Error>> {
+    if let Err(err) = api_key.require(Role::Admin) {
+        return Box::new(future::err(err));
+    }
+    if let Err(response) = rate_limit::limit(&state.rate_limiter, &req, &api_key) {
+        return Box::new(future::ok(response));
+    }
+    let id = id.into_inner();
+    let reprocessor = state.reprocessor.clone();
+    info!("Retrying failed admin event {} for {}", id, api_key.name());
+    Box::new(
+        web::block(move || reprocessor.retry(id)).then(move |result| match result {
+            Ok(true) => Ok(HttpResponse::Ok().json(json!({ "message": "Event exported" }))),
+            Ok(false) => {
+                Ok(HttpResponse::NotFound().json(json!({ "message": "No such failed event" })))
+            }
+            Err(err) => Ok(reprocessing_failed(err)),
+        }),
+    )
+}
+
+/// Exports a proposal and its votes again, as splinterd reports them, so consumers can rebuild
+/// their record of the proposal.
+pub fn resync_proposal(
+    req: HttpRequest,
+    request_id: RequestId,
+    api_key: ApiKey,
+    circuit_id: web::Path<String>,
+    state: web::Data<AppState>,
+) -> Box<dyn Future<Item = HttpResponse, Error = Error>> {
+    if let Err(err) = api_key.require(Role::Admin) {
+        return Box::new(future::err(err));
+    }
+    if let Err(response) = rate_limit::limit(&state.rate_limiter, &req, &api_key) {
+        return Box::new(future::ok(response));
+    }
+    info!("Resyncing proposal {} for {}", circuit_id, api_key.name());
+    let reprocessor = state.reprocessor.clone();
+
+    Box::new(
+        splinterd::get_json(
+            &state.client,
+            &state.config,
+            state.token_provider.as_ref(),
+            &request_id,
+            &format!("/admin/proposals/{}", circuit_id),
+        )
+        .then(move |proposal| {
+            let proposal = match proposal {
+                Ok(proposal) => proposal,
+                Err(err) => {
+                    error!(
+                        "Request {}: unable to fetch proposal {}: {}",
+                        request_id.as_str(),
+                        circuit_id,
+                        err
+                    );
+                    return Either::A(future::ok(err.to_response()));
+                }
+            };
+            let proposal = match parse_proposal(proposal) {
+                Ok(proposal) => proposal,
+                Err(err) => {
+                    error!(
+                        "Request {}: unable to parse proposal {}: {}",
+                        request_id.as_str(),
+                        circuit_id,
+                        err
+                    );
+                    return Either::A(future::ok(HttpResponse::BadGateway().json(json!({
+                        "message": format!("Unable to parse splinterd's proposal: {}", err),
+                    }))));
+                }
+            };
+            Either::B(
+                web::block(move || reprocessor.resync(proposal)).then(|result| match result {
+                    Ok(()) => {
+                        Ok(HttpResponse::Ok().json(json!({ "message": "Proposal exported" })))
+                    }
+                    Err(err) => Ok(reprocessing_failed(err)),
+                }),
+            )
+        }),
+    )
+}
+
+fn reprocessing_failed(err: BlockingError<EventHandlerError>) -> HttpResponse {
+    let err = match err {
+        BlockingError::Error(err) => err.to_string(),
+        BlockingError::Canceled => "the thread pool is shutting down".to_string(),
+    };
+    error!("Unable to reprocess admin event: {}", err);
+    HttpResponse::InternalServerError().json(json!({
+        "message": format!("Unable to export the event: {}", err),
+    }))
+}
+
+/// Converts a proposal from splinterd's REST API, where keys are hex encoded, into the form of
+/// admin events.
+fn parse_proposal(mut proposal: Value) -> Result<CircuitProposal, String> {
+    decode_hex_field(&mut proposal["requester"])?;
+    if let Some(votes) = proposal["votes"].as_array_mut() {
+        for vote in votes {
+            decode_hex_field(&mut vote["public_key"])?;
+        }
+    }
+    serde_json::from_value(proposal).map_err(|err| err.to_string())
+}
+
+fn decode_hex_field(field: &mut Value) -> Result<(), String> {
+    if let Some(hex) = field.as_str() {
+        *field = json!(from_hex(hex)?);
+    }
+    Ok(())
+}
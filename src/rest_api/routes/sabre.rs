@@ -0,0 +1,372 @@
+/*
+ * Copyright 2019 Walmart Inc.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * -----------------------------------------------------------------------------
+ */
+
+use actix_web::error::BlockingError;
+use actix_web::{web, Error, HttpRequest, HttpResponse};
+use futures::future::{self, Either, Future};
+use openssl::base64;
+
+use crate::config::Role;
+use crate::event_handler::sabre::{
+    create_contract_action, create_namespace_registry_action, namespace_permission_action,
+    update_namespace_registry_owners_action, SabreAction, SABRE_FAMILY_NAME, SABRE_FAMILY_VERSION,
+};
+use crate::event_handler::{to_hex, CircuitService, EventHandlerError, ServiceRoster};
+use crate::rest_api::auth::ApiKey;
+use crate::rest_api::rate_limit;
+use crate::rest_api::request_id::RequestId;
+use crate::rest_api::splinterd;
+use crate::rest_api::AppState;
+use crate::signer::{sign_batch, SignerError};
+
+/// largest contract upload accepted, in bytes, with the contract base64 encoded
+pub const MAX_CONTRACT_UPLOAD_SIZE: usize = 16 * 1024 * 1024;
+
+#[derive(Deserialize)]
+pub struct ContractUpload {
+    circuit_id: String,
+    /// defaults to this node's scabbard service on the circuit
+    service_id: Option<String>,
+    name: String,
+    version: String,
+    /// state addresses or prefixes the contract reads
+    inputs: Vec<String>,
+    /// state addresses or prefixes the contract writes
+    outputs: Vec<String>,
+    /// the compiled WASM contract, base64 encoded
+    contract: String,
+}
+
+#[derive(Deserialize)]
+pub struct NamespaceRegistration {
+    circuit_id: String,
+    service_id: Option<String>,
+    /// state address prefix of at least 6 hex characters
+    namespace: String,
+    /// defaults to the scabbard admin keys of the circuit
+    owners: Option<Vec<String>>,
+}
+
+#[derive(Deserialize)]
+pub struct NamespaceOwners {
+    circuit_id: String,
+    service_id: Option<String>,
+    owners: Vec<String>,
+}
+
+#[derive(Deserialize)]
+pub struct NamespacePermission {
+    circuit_id: String,
+    service_id: Option<String>,
+    contract_name: String,
+    #[serde(default)]
+    read: bool,
+    #[serde(default)]
+    write: bool,
+}
+
+/// Uploads a compiled Sabre contract to the scabbard service of a circuit.
+///
+/// With a signer configured, the CreateContractAction is signed and submitted, and the id of its
+/// batch returned. Otherwise the unsigned Sabre payload and the addresses of its
+/// transaction are returned for the client to sign and submit itself.
+pub fn upload_contract(
+    req: HttpRequest,
+    request_id: RequestId,
+    api_key: ApiKey,
+    upload: web::Json<ContractUpload>,
+    state: web::Data<AppState>,
+) -> Box<dyn Future<Item = HttpResponse, Error = Error>> {
+    if let Err(err) = api_key.require(Role::Admin) {
+        return Box::new(future::err(err));
+    }
+    if let Err(response) = rate_limit::limit(&state.rate_limiter, &req, &api_key) {
+        return Box::new(future::ok(response));
+    }
+    let upload = upload.into_inner();
+    let service = match find_service(
+        &state.roster,
+        &upload.circuit_id,
+        upload.service_id.as_ref().map(String::as_str),
+    ) {
+        Ok(service) => service,
+        Err(response) => return Box::new(future::ok(response)),
+    };
+    let contract = match base64::decode_block(&upload.contract) {
+        Ok(contract) => contract,
+        Err(_) => {
+            return Box::new(future::ok(HttpResponse::BadRequest().json(json!({
+                "message": "The contract is not valid base64",
+            }))))
+        }
+    };
+    let action = match create_contract_action(
+        &upload.name,
+        &upload.version,
+        upload.inputs,
+        upload.outputs,
+        contract,
+    ) {
+        Ok(action) => action,
+        Err(err) => {
+            return Box::new(future::ok(HttpResponse::BadRequest().json(json!({
+                "message": format!("Invalid contract: {}", err),
+            }))))
+        }
+    };
+
+    debug!(
+        "Uploading contract {} {} to circuit {} for {}",
+        upload.name,
+        upload.version,
+        service.circuit_id(),
+        api_key.name()
+    );
+    submit_or_return(action, service, state, request_id)
+}
+
+/// Creates the registry of a state namespace on a circuit, signed and submitted or returned
+/// unsigned like an uploaded contract.
+pub fn create_namespace(
+    req: HttpRequest,
+    request_id: RequestId,
+    api_key: ApiKey,
+    registration: web::Json<NamespaceRegistration>,
+    state: web::Data<AppState>,
+) -> Box<dyn Future<Item = HttpResponse, Error = Error>> {
+    if let Err(err) = api_key.require(Role::Admin) {
+        return Box::new(future::err(err));
+    }
+    if let Err(response) = rate_limit::limit(&state.rate_limiter, &req, &api_key) {
+        return Box::new(future::ok(response));
+    }
+    let registration = registration.into_inner();
+    let service = match find_service(
+        &state.roster,
+        &registration.circuit_id,
+        registration.service_id.as_ref().map(String::as_str),
+    ) {
+        Ok(service) => service,
+        Err(response) => return Box::new(future::ok(response)),
+    };
+    let owners = registration
+        .owners
+        .unwrap_or_else(|| service.admin_keys().to_vec());
+
+    debug!(
+        "Creating namespace {} on circuit {} for {}",
+        registration.namespace,
+        service.circuit_id(),
+        api_key.name()
+    );
+    match create_namespace_registry_action(&registration.namespace, owners) {
+        Ok(action) => submit_or_return(action, service, state, request_id),
+        Err(err) => Box::new(future::ok(invalid_action(err))),
+    }
+}
+
+/// Replaces the owners of a namespace registry on a circuit.
+pub fn update_namespace_owners(
+    req: HttpRequest,
+    request_id: RequestId,
+    api_key: ApiKey,
+    namespace: web::Path<String>,
+    update: web::Json<NamespaceOwners>,
+    state: web::Data<AppState>,
+) -> Box<dyn Future<Item = HttpResponse, Error = Error>> {
+    if let Err(err) = api_key.require(Role::Admin) {
+        return Box::new(future::err(err));
+    }
+    if let Err(response) = rate_limit::limit(&state.rate_limiter, &req, &api_key) {
+        return Box::new(future::ok(response));
+    }
+    let update = update.into_inner();
+    let service = match find_service(
+        &state.roster,
+        &update.circuit_id,
+        update.service_id.as_ref().map(String::as_str),
+    ) {
+        Ok(service) => service,
+        Err(response) => return Box::new(future::ok(response)),
+    };
+
+    debug!(
+        "Updating the owners of namespace {} on circuit {} for {}",
+        namespace,
+        service.circuit_id(),
+        api_key.name()
+    );
+    match update_namespace_registry_owners_action(&namespace, update.owners) {
+        Ok(action) => submit_or_return(action, service, state, request_id),
+        Err(err) => Box::new(future::ok(invalid_action(err))),
+    }
+}
+
+/// Grants a contract read and/or write access to a namespace on a circuit.
+pub fn grant_namespace_permission(
+    req: HttpRequest,
+    request_id: RequestId,
+    api_key: ApiKey,
+    namespace: web::Path<String>,
+    permission: web::Json<NamespacePermission>,
+    state: web::Data<AppState>,
+) -> Box<dyn Future<Item = HttpResponse, Error = Error>> {
+    if let Err(err) = api_key.require(Role::Admin) {
+        return Box::new(future::err(err));
+    }
+    if let Err(response) = rate_limit::limit(&state.rate_limiter, &req, &api_key) {
+        return Box::new(future::ok(response));
+    }
+    let permission = permission.into_inner();
+    let service = match find_service(
+        &state.roster,
+        &permission.circuit_id,
+        permission.service_id.as_ref().map(String::as_str),
+    ) {
+        Ok(service) => service,
+        Err(response) => return Box::new(future::ok(response)),
+    };
+
+    debug!(
+        "Granting {} access to namespace {} on circuit {} for {}",
+        permission.contract_name,
+        namespace,
+        service.circuit_id(),
+        api_key.name()
+    );
+    match namespace_permission_action(
+        &namespace,
+        &permission.contract_name,
+        permission.read,
+        permission.write,
+    ) {
+        Ok(action) => submit_or_return(action, service, state, request_id),
+        Err(err) => Box::new(future::ok(invalid_action(err))),
+    }
+}
+
+fn invalid_action(err: EventHandlerError) -> HttpResponse {
+    HttpResponse::BadRequest().json(json!({
+        "message": format!("Invalid Sabre action: {}", err),
+    }))
+}
+
+/// Returns this node's scabbard service on the circuit, or the response to send if it has none
+/// or it is not the requested one.
+pub(super) fn find_service(
+    roster: &ServiceRoster,
+    circuit_id: &str,
+    service_id: Option<&str>,
+) -> Result<CircuitService, HttpResponse> {
+    let service = roster.get(circuit_id).ok_or_else(|| {
+        HttpResponse::NotFound().json(json!({
+            "message": format!("This node has no scabbard service on circuit {}", circuit_id),
+        }))
+    })?;
+    match service_id {
+        Some(service_id) if service_id != service.service_id() => Err(HttpResponse::BadRequest()
+            .json(json!({
+                "message": format!(
+                    "This node's scabbard service on circuit {} is {}",
+                    circuit_id,
+                    service.service_id()
+                ),
+            }))),
+        _ => Ok(service),
+    }
+}
+
+/// Signs the action and submits it to the service if a signer is configured; otherwise responds
+/// with the unsigned action.
+pub(super) fn submit_or_return(
+    action: SabreAction,
+    service: CircuitService,
+    state: web::Data<AppState>,
+    request_id: RequestId,
+) -> Box<dyn Future<Item = HttpResponse, Error = Error>> {
+    let signer = match &state.signer {
+        Some(signer) => signer.clone(),
+        None => {
+            return Box::new(future::ok(HttpResponse::Ok().json(json!({
+                "circuit_id": service.circuit_id(),
+                "service_id": service.service_id(),
+                "family_name": SABRE_FAMILY_NAME,
+                "family_version": SABRE_FAMILY_VERSION,
+                "inputs": action.addresses,
+                "outputs": action.addresses,
+                "payload": to_hex(&action.payload),
+            }))))
+        }
+    };
+
+    // a remote signer blocks while it signs
+    Box::new(
+        web::block(move || sign_batch(&*signer, vec![action])).then(move |signed| {
+            let (batch_id, batch_list) = match signed {
+                Ok(signed) => signed,
+                Err(BlockingError::Error(SignerError::RemoteError(err))) => {
+                    error!(
+                        "Request {}: the signing service was unable to sign Sabre batch: {}",
+                        request_id.as_str(),
+                        err
+                    );
+                    return Either::A(future::ok(HttpResponse::BadGateway().json(json!({
+                        "message": "The signing service was unable to sign the Sabre transaction",
+                    }))));
+                }
+                Err(err) => {
+                    error!(
+                        "Request {}: unable to sign Sabre batch: {}",
+                        request_id.as_str(),
+                        err
+                    );
+                    return Either::A(future::ok(HttpResponse::InternalServerError().json(
+                        json!({
+                            "message": "Unable to sign the Sabre transaction",
+                        }),
+                    )));
+                }
+            };
+            Either::B(
+                splinterd::submit_batches(
+                    &state.client,
+                    &state.config,
+                    state.token_provider.as_ref(),
+                    &request_id,
+                    service.circuit_id(),
+                    service.service_id(),
+                    batch_list,
+                )
+                .then(move |result| match result {
+                    Ok(()) => Ok(HttpResponse::Accepted().json(json!({
+                        "batch_id": batch_id,
+                        "circuit_id": service.circuit_id(),
+                        "service_id": service.service_id(),
+                    }))),
+                    Err(err) => {
+                        error!(
+                            "Request {}: unable to submit Sabre batch: {}",
+                            request_id.as_str(),
+                            err
+                        );
+                        Ok(err.to_response())
+                    }
+                }),
+            )
+        }),
+    )
+}
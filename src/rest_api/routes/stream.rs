@@ -0,0 +1,59 @@
+/*
+ * Copyright 2019 Walmart Inc.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * -----------------------------------------------------------------------------
+ */
+
+use actix_web::{error, web, Error, HttpRequest, HttpResponse};
+use futures::Stream;
+
+use crate::config::Role;
+use crate::rest_api::auth::ApiKey;
+use crate::rest_api::AppState;
+
+const LAST_EVENT_ID: &str = "Last-Event-ID";
+
+/// Streams proposal and vote state changes as Server-Sent Events.
+///
+/// A client reconnecting with a Last-Event-ID header first receives the retained changes it
+/// missed.
+pub fn stream_events(
+    api_key: ApiKey,
+    req: HttpRequest,
+    state: web::Data<AppState>,
+) -> Result<HttpResponse, Error> {
+    api_key.require(Role::ReadOnly)?;
+    let last_id = req
+        .headers()
+        .get(LAST_EVENT_ID)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<u64>().ok());
+
+    let events = state
+        .broadcaster
+        .subscribe_after(last_id)
+        .map(|change| {
+            web::Bytes::from(format!(
+                "id: {}\ndata: {}\n\n",
+                change.id(),
+                change.to_json()
+            ))
+        })
+        .map_err(|_| error::ErrorInternalServerError("event stream closed"));
+
+    Ok(HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .header("Cache-Control", "no-cache")
+        .streaming(events))
+}
@@ -0,0 +1,35 @@
+/*
+ * Copyright 2019 Walmart Inc.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * -----------------------------------------------------------------------------
+ */
+
+use actix_web::{web, Error, HttpResponse};
+
+use crate::config::Role;
+use crate::rest_api::auth::ApiKey;
+use crate::rest_api::AppState;
+
+/// Reports the status of a payload submitted through POST /submit.
+pub fn fetch_submission(
+    api_key: ApiKey,
+    state: web::Data<AppState>,
+    submission_id: web::Path<String>,
+) -> Result<HttpResponse, Error> {
+    api_key.require(Role::ReadOnly)?;
+    match state.submission_tracker.get(&submission_id) {
+        Some(submission) => Ok(HttpResponse::Ok().json(submission)),
+        None => Ok(HttpResponse::NotFound().json(json!({ "message": "No such submission" }))),
+    }
+}
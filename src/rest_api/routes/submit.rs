@@ -0,0 +1,352 @@
+/*
+ * Copyright 2019 Walmart Inc.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * -----------------------------------------------------------------------------
+ */
+
+use actix_web::client::{Client, ClientRequest};
+use actix_web::dev::Body;
+use actix_web::error::BlockingError;
+use actix_web::http::{header, StatusCode};
+use actix_web::{web, Error, HttpRequest, HttpResponse};
+use futures::future::{self, Either, Future};
+use serde_json::Value;
+use splinter::protos::admin::{
+    CircuitManagementPayload, CircuitManagementPayload_Action, CircuitManagementPayload_Header,
+};
+use uuid::Uuid;
+
+use crate::authorization::TokenProvider;
+use crate::config::{EventListenerConfig, Role};
+use crate::event_handler::to_hex;
+use crate::rest_api::auth::ApiKey;
+use crate::rest_api::idempotency::{Reservation, ReservedKey, IDEMPOTENCY_KEY_HEADER};
+use crate::rest_api::rate_limit;
+use crate::rest_api::request_id::{RequestId, REQUEST_ID_HEADER};
+use crate::rest_api::submissions::ExpectedEvent;
+use crate::rest_api::AppState;
+use crate::signer::{sign_circuit_management_payload, SignerError};
+
+/// Forwards a signed CircuitManagementPayload to splinterd's admin service, so clients do not
+/// need network access to splinterd.
+///
+/// Votes require the member role; every other action, such as proposing a circuit, requires
+/// the admin role. Each client, identified by its API key or else its address, is rate limited.
+/// A client may send an Idempotency-Key header to safely retry a submission: retries return the
+/// response to the first request instead of submitting the payload again.
+///
+/// Accepted and rejected payloads are given a submission id whose status can be followed with
+/// GET /submissions/{id}.
+///
+/// Once keys are registered, votes must be signed by an active registered key.
+///
+/// With server_side_signing enabled, a payload sent without a signature is signed with the
+/// event listener's signer, whose public key becomes its requester.
+pub fn submit_signed_payload(
+    req: HttpRequest,
+    request_id: RequestId,
+    api_key: ApiKey,
+    state: web::Data<AppState>,
+    signed_payload: web::Bytes,
+) -> Box<dyn Future<Item = HttpResponse, Error = Error>> {
+    let client_id = match rate_limit::limit(&state.rate_limiter, &req, &api_key) {
+        Ok(client_id) => client_id,
+        Err(response) => return Box::new(future::ok(response)),
+    };
+
+    // checked before signing, so clients without the role cannot use the signer
+    let required_role = match parse_payload(&signed_payload) {
+        Ok((required_role, _)) => required_role,
+        Err(err) => return Box::new(future::ok(invalid_payload(err))),
+    };
+    if let Err(err) = api_key.require(required_role) {
+        return Box::new(future::err(err));
+    }
+
+    let signer = match &state.signer {
+        Some(signer)
+            if state.config.deployment_config().server_side_signing()
+                && is_unsigned(&signed_payload) =>
+        {
+            signer.clone()
+        }
+        _ => return relay_payload(req, request_id, api_key, state, client_id, signed_payload),
+    };
+    // a remote signer blocks while it signs
+    Box::new(
+        web::block(move || sign_circuit_management_payload(&*signer, &signed_payload)).then(
+            move |signed| match signed {
+                Ok(signed_payload) => relay_payload(
+                    req,
+                    request_id,
+                    api_key,
+                    state,
+                    client_id,
+                    web::Bytes::from(signed_payload),
+                ),
+                Err(BlockingError::Error(SignerError::RemoteError(err))) => {
+                    error!("Unable to sign a payload with the remote signer: {}", err);
+                    Box::new(future::ok(HttpResponse::BadGateway().json(json!({
+                        "message": "The signing service was unable to sign the payload",
+                    }))))
+                }
+                Err(err) => Box::new(future::ok(HttpResponse::BadRequest().json(json!({
+                    "message": format!("Unable to sign the payload: {}", err),
+                })))),
+            },
+        ),
+    )
+}
+
+/// Relays a payload whose role the client was checked for, replaying the response to an
+/// earlier submission with the same idempotency key.
+fn relay_payload(
+    req: HttpRequest,
+    request_id: RequestId,
+    api_key: ApiKey,
+    state: web::Data<AppState>,
+    client_id: String,
+    signed_payload: web::Bytes,
+) -> Box<dyn Future<Item = HttpResponse, Error = Error>> {
+    // parsed again as signing sets the requester, which is the voter of a vote
+    let expected_event = match parse_payload(&signed_payload) {
+        Ok((_, expected_event)) => expected_event,
+        Err(err) => return Box::new(future::ok(invalid_payload(err))),
+    };
+    if let Some(ExpectedEvent::ProposalVote { voter, .. }) = &expected_event {
+        if let Err(reason) = state.keys.check_voter(voter) {
+            return Box::new(future::ok(HttpResponse::Forbidden().json(json!({
+                "message": format!("The vote was not signed by an accepted key: {}", reason),
+            }))));
+        }
+    }
+
+    debug!("Relaying signed payload from {}", api_key.name());
+    let request = match admin_submit_request(
+        &state.client,
+        &state.config,
+        state.token_provider.as_ref(),
+        &request_id,
+    ) {
+        Ok(request) => request,
+        Err(response) => return Box::new(future::ok(response)),
+    };
+
+    let idempotency_key = match req.headers().get(IDEMPOTENCY_KEY_HEADER) {
+        Some(value) => match value.to_str() {
+            Ok(value) if !value.is_empty() => Some(format!("{}:{}", client_id, value)),
+            _ => {
+                return Box::new(future::ok(HttpResponse::BadRequest().json(json!({
+                    "message": "Invalid Idempotency-Key header",
+                }))))
+            }
+        },
+        None => None,
+    };
+    let reserved_key = match &idempotency_key {
+        Some(idempotency_key) => match state
+            .idempotency_cache
+            .reserve(idempotency_key, &signed_payload)
+        {
+            Reservation::Reserved(reserved_key) => Some(reserved_key),
+            Reservation::Replay(status, body) => {
+                debug!("Replaying submission response to {}", api_key.name());
+                return Box::new(future::ok(
+                    HttpResponse::build(status)
+                        .header("Idempotent-Replayed", "true")
+                        .json(body),
+                ));
+            }
+            Reservation::InProgress => {
+                return Box::new(future::ok(HttpResponse::Conflict().json(json!({
+                    "message": "A submission with this Idempotency-Key is in progress",
+                }))))
+            }
+            Reservation::Mismatch => {
+                return Box::new(future::ok(HttpResponse::UnprocessableEntity().json(json!({
+                    "message": "The Idempotency-Key was used for a different payload",
+                }))))
+            }
+        },
+        None => None,
+    };
+
+    Box::new(
+        send_payload(request, request_id, signed_payload).map(move |(status, mut body)| {
+            let submission_id = Uuid::new_v4().to_simple().to_string();
+            match status {
+                StatusCode::ACCEPTED => state
+                    .submission_tracker
+                    .accepted(&submission_id, expected_event),
+                StatusCode::BAD_REQUEST => state.submission_tracker.invalid(
+                    &submission_id,
+                    body["splinterd_response"]
+                        .as_str()
+                        .unwrap_or_default()
+                        .to_string(),
+                ),
+                _ => return finish(reserved_key, status, body),
+            }
+            body["submission_id"] = json!(submission_id);
+            finish(reserved_key, status, body)
+        }),
+    )
+}
+
+/// Builds the request relaying a payload to splinterd's admin service, or the response to send
+/// if the event listener cannot authenticate with splinterd.
+pub(super) fn admin_submit_request(
+    client: &Client,
+    config: &EventListenerConfig,
+    token_provider: Option<&TokenProvider>,
+    request_id: &RequestId,
+) -> Result<ClientRequest, HttpResponse> {
+    let request = client
+        .post(format!("{}/admin/submit", config.splinterd_url()))
+        .header(header::CONTENT_TYPE, "application/octet-stream")
+        .header(REQUEST_ID_HEADER, request_id.as_str());
+    match token_provider.map(TokenProvider::authorization_header) {
+        Some(Ok(authorization)) => Ok(request.header(header::AUTHORIZATION, authorization)),
+        Some(Err(err)) => {
+            error!("Unable to read splinterd token: {}", err);
+            Err(HttpResponse::InternalServerError()
+                .json(json!({ "message": "Unable to authenticate with splinterd" })))
+        }
+        None => Ok(request),
+    }
+}
+
+/// Sends a signed payload to splinterd, resolving to the status and body of the response to
+/// give the client.
+pub(super) fn send_payload(
+    request: ClientRequest,
+    request_id: RequestId,
+    signed_payload: web::Bytes,
+) -> impl Future<Item = (StatusCode, Value), Error = Error> {
+    request
+        .send_body(Body::Bytes(signed_payload))
+        .then(move |response| match response {
+            Ok(mut response) => {
+                let status = response.status();
+                Either::A(response.body().then(move |body| {
+                    let body = body
+                        .map(|bytes| String::from_utf8_lossy(&bytes).into_owned())
+                        .unwrap_or_default();
+                    Ok(translate_response(status, body))
+                }))
+            }
+            Err(err) => {
+                error!(
+                    "Request {}: unable to reach splinterd: {}",
+                    request_id.as_str(),
+                    err
+                );
+                Either::B(future::ok((
+                    StatusCode::SERVICE_UNAVAILABLE,
+                    json!({ "message": "Unable to reach splinterd" }),
+                )))
+            }
+        })
+}
+
+/// Returns true for a CircuitManagementPayload sent without a signature.
+fn is_unsigned(payload: &[u8]) -> bool {
+    protobuf::parse_from_bytes::<CircuitManagementPayload>(payload)
+        .map(|payload| payload.get_signature().is_empty())
+        .unwrap_or(false)
+}
+
+/// Builds the response to a payload that is not a valid CircuitManagementPayload.
+fn invalid_payload(err: protobuf::ProtobufError) -> HttpResponse {
+    HttpResponse::BadRequest().json(json!({
+        "message": format!("Invalid CircuitManagementPayload: {}", err),
+    }))
+}
+
+/// Records the response for retries with the same idempotency key, and builds it.
+fn finish(reserved_key: Option<ReservedKey>, status: StatusCode, body: Value) -> HttpResponse {
+    if let Some(reserved_key) = reserved_key {
+        reserved_key.complete(status, &body);
+    }
+    HttpResponse::build(status).json(body)
+}
+
+/// Returns the role required to submit the payload, and the admin event expected once it is
+/// committed.
+fn parse_payload(
+    signed_payload: &[u8],
+) -> Result<(Role, Option<ExpectedEvent>), protobuf::ProtobufError> {
+    let payload: CircuitManagementPayload = protobuf::parse_from_bytes(signed_payload)?;
+    let header: CircuitManagementPayload_Header = protobuf::parse_from_bytes(payload.get_header())?;
+    match header.get_action() {
+        CircuitManagementPayload_Action::CIRCUIT_PROPOSAL_VOTE => Ok((
+            Role::Member,
+            Some(ExpectedEvent::ProposalVote {
+                circuit_id: payload.get_circuit_proposal_vote().get_circuit_id().into(),
+                voter: to_hex(header.get_requester()),
+            }),
+        )),
+        CircuitManagementPayload_Action::CIRCUIT_CREATE_REQUEST => Ok((
+            Role::Admin,
+            Some(ExpectedEvent::ProposalSubmitted {
+                circuit_id: payload
+                    .get_circuit_create_request()
+                    .get_circuit()
+                    .get_circuit_id()
+                    .into(),
+            }),
+        )),
+        _ => Ok((Role::Admin, None)),
+    }
+}
+
+/// Maps splinterd's response to one meaningful to the client.
+///
+/// Only a rejected payload is the client's fault; other failures are reported as gateway
+/// errors, including splinterd refusing the event listener's own credentials.
+fn translate_response(status: StatusCode, body: String) -> (StatusCode, Value) {
+    match status {
+        StatusCode::ACCEPTED => (
+            StatusCode::ACCEPTED,
+            json!({ "message": "The payload was submitted successfully" }),
+        ),
+        StatusCode::BAD_REQUEST => (
+            StatusCode::BAD_REQUEST,
+            json!({
+                "message": "splinterd rejected the payload",
+                "splinterd_response": body,
+            }),
+        ),
+        StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => {
+            error!(
+                "splinterd refused the event listener's credentials: {}",
+                body
+            );
+            (
+                StatusCode::BAD_GATEWAY,
+                json!({ "message": "splinterd refused the event listener's credentials" }),
+            )
+        }
+        status => {
+            error!(
+                "splinterd responded to a submission with {}: {}",
+                status, body
+            );
+            (
+                StatusCode::BAD_GATEWAY,
+                json!({ "message": format!("splinterd responded with status {}", status) }),
+            )
+        }
+    }
+}
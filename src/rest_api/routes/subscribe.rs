@@ -0,0 +1,71 @@
+/*
+ * Copyright 2019 Walmart Inc.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * -----------------------------------------------------------------------------
+ */
+
+use actix::{Actor, ActorContext, AsyncContext, StreamHandler};
+use actix_web::{web, Error, HttpRequest, HttpResponse};
+use actix_web_actors::ws;
+
+use crate::broadcast::{Broadcaster, StateChange};
+use crate::config::Role;
+use crate::rest_api::auth::ApiKey;
+use crate::rest_api::AppState;
+
+/// Pushes every state change to one websocket client.
+struct SubscriptionSession {
+    broadcaster: Broadcaster,
+}
+
+impl Actor for SubscriptionSession {
+    type Context = ws::WebsocketContext<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        ctx.add_stream(self.broadcaster.subscribe());
+    }
+}
+
+impl StreamHandler<ws::Message, ws::ProtocolError> for SubscriptionSession {
+    fn handle(&mut self, msg: ws::Message, ctx: &mut Self::Context) {
+        match msg {
+            ws::Message::Ping(msg) => ctx.pong(&msg),
+            ws::Message::Close(_) => ctx.stop(),
+            _ => (),
+        }
+    }
+}
+
+impl StreamHandler<StateChange, ()> for SubscriptionSession {
+    fn handle(&mut self, change: StateChange, ctx: &mut Self::Context) {
+        ctx.text(change.to_json().to_string())
+    }
+}
+
+/// Upgrades the request to a websocket receiving proposal and vote state changes as JSON.
+pub fn subscribe(
+    api_key: ApiKey,
+    req: HttpRequest,
+    stream: web::Payload,
+    state: web::Data<AppState>,
+) -> Result<HttpResponse, Error> {
+    api_key.require(Role::ReadOnly)?;
+    ws::start(
+        SubscriptionSession {
+            broadcaster: state.broadcaster.clone(),
+        },
+        &req,
+        stream,
+    )
+}
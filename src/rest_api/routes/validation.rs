@@ -0,0 +1,53 @@
+/*
+ * Copyright 2019 Walmart Inc.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * -----------------------------------------------------------------------------
+ */
+
+use actix_web::{web, Error, HttpResponse};
+use futures::future::{self, Future};
+
+use crate::config::Role;
+use crate::rest_api::auth::ApiKey;
+use crate::rest_api::AppState;
+use crate::validation::validate_proposed;
+
+/// Runs every configuration check against a proposed deployment configuration, given as YAML
+/// or JSON, without applying it.
+pub fn validate_config(
+    api_key: ApiKey,
+    state: web::Data<AppState>,
+    proposed: String,
+) -> Box<dyn Future<Item = HttpResponse, Error = Error>> {
+    if let Err(err) = api_key.require(Role::Admin) {
+        return Box::new(future::err(err));
+    }
+    let config = state.config.clone();
+    let token_provider = state.token_provider.clone();
+    // the checks connect to the Kafka brokers and splinterd
+    Box::new(
+        web::block(move || {
+            Ok::<_, ()>(validate_proposed(
+                &config,
+                &proposed,
+                token_provider.as_ref(),
+            ))
+        })
+        .then(|result| match result {
+            Ok(report) => Ok(HttpResponse::Ok().json(report)),
+            Err(_) => Ok(HttpResponse::InternalServerError()
+                .json(json!({ "message": "Unable to validate the configuration" }))),
+        }),
+    )
+}
@@ -0,0 +1,207 @@
+/*
+ * Copyright 2019 Walmart Inc.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * -----------------------------------------------------------------------------
+ */
+
+//! Requests the REST API makes to splinterd on behalf of its clients.
+
+use std::error::Error;
+use std::fmt;
+
+use actix_web::client::Client;
+use actix_web::dev::Body;
+use actix_web::http::{header, StatusCode};
+use actix_web::HttpResponse;
+use futures::future::{self, Either, Future};
+use serde_json::Value;
+use splinter::node_registry::Node;
+
+use crate::authorization::TokenProvider;
+use crate::config::EventListenerConfig;
+use crate::rest_api::node_cache::NodeCache;
+use crate::rest_api::request_id::{RequestId, REQUEST_ID_HEADER};
+
+/// largest splinterd response body read, in bytes
+const MAX_RESPONSE_SIZE: usize = 4 * 1024 * 1024;
+
+#[derive(Debug)]
+pub enum SplinterdError {
+    /// The splinterd token could not be read
+    Token(String),
+    Unreachable(String),
+    NotFound,
+    /// splinterd found the request invalid, with the reason it gave
+    Rejected(String),
+    /// splinterd responded with an unexpected status
+    Status(StatusCode),
+    InvalidResponse(String),
+}
+
+impl SplinterdError {
+    /// Builds the response reporting this error to the client.
+    pub fn to_response(&self) -> HttpResponse {
+        match self {
+            SplinterdError::Token(_) => HttpResponse::InternalServerError()
+                .json(json!({ "message": "Unable to authenticate with splinterd" })),
+            SplinterdError::Unreachable(_) => HttpResponse::ServiceUnavailable()
+                .json(json!({ "message": "Unable to reach splinterd" })),
+            SplinterdError::NotFound => {
+                HttpResponse::NotFound().json(json!({ "message": "Not found" }))
+            }
+            SplinterdError::Rejected(reason) => HttpResponse::BadRequest().json(json!({
+                "message": "splinterd rejected the request",
+                "splinterd_response": reason,
+            })),
+            SplinterdError::Status(_) | SplinterdError::InvalidResponse(_) => {
+                HttpResponse::BadGateway().json(json!({ "message": self.to_string() }))
+            }
+        }
+    }
+}
+
+impl Error for SplinterdError {}
+
+impl fmt::Display for SplinterdError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SplinterdError::Token(err) => write!(f, "Unable to read splinterd token: {}", err),
+            SplinterdError::Unreachable(err) => write!(f, "Unable to reach splinterd: {}", err),
+            SplinterdError::NotFound => write!(f, "splinterd responded with status 404"),
+            SplinterdError::Rejected(reason) => {
+                write!(f, "splinterd rejected the request: {}", reason)
+            }
+            SplinterdError::Status(status) => {
+                write!(f, "splinterd responded with status {}", status)
+            }
+            SplinterdError::InvalidResponse(err) => {
+                write!(f, "splinterd sent an invalid response: {}", err)
+            }
+        }
+    }
+}
+
+/// Sends a GET request for `path` to splinterd, on behalf of the request with the given id,
+/// and parses the JSON response.
+pub fn get_json(
+    client: &Client,
+    config: &EventListenerConfig,
+    token_provider: Option<&TokenProvider>,
+    request_id: &RequestId,
+    path: &str,
+) -> Box<dyn Future<Item = Value, Error = SplinterdError>> {
+    let mut request = client
+        .get(format!("{}{}", config.splinterd_url(), path))
+        .header(REQUEST_ID_HEADER, request_id.as_str());
+    if let Some(token_provider) = token_provider {
+        match token_provider.authorization_header() {
+            Ok(authorization) => request = request.header(header::AUTHORIZATION, authorization),
+            Err(err) => return Box::new(future::err(SplinterdError::Token(err.to_string()))),
+        }
+    }
+
+    Box::new(
+        request
+            .send()
+            .map_err(|err| SplinterdError::Unreachable(err.to_string()))
+            .and_then(|mut response| match response.status() {
+                StatusCode::OK => Either::A(
+                    response
+                        .body()
+                        .limit(MAX_RESPONSE_SIZE)
+                        .map_err(|err| SplinterdError::InvalidResponse(err.to_string()))
+                        .and_then(|body| {
+                            serde_json::from_slice(&body)
+                                .map_err(|err| SplinterdError::InvalidResponse(err.to_string()))
+                        }),
+                ),
+                StatusCode::NOT_FOUND => Either::B(future::err(SplinterdError::NotFound)),
+                status => Either::B(future::err(SplinterdError::Status(status))),
+            }),
+    )
+}
+
+/// Returns splinterd's node registry, from the cache unless it is stale.
+pub fn fetch_nodes(
+    client: &Client,
+    config: &EventListenerConfig,
+    token_provider: Option<&TokenProvider>,
+    request_id: &RequestId,
+    node_cache: &NodeCache,
+) -> Box<dyn Future<Item = Vec<Node>, Error = SplinterdError>> {
+    if let Some(nodes) = node_cache.get() {
+        return Box::new(future::ok(nodes));
+    }
+    let node_cache = node_cache.clone();
+    Box::new(
+        get_json(client, config, token_provider, request_id, "/nodes").and_then(
+            move |mut response| {
+                let nodes: Vec<Node> = serde_json::from_value(response["data"].take())
+                    .map_err(|err| SplinterdError::InvalidResponse(err.to_string()))?;
+                node_cache.store(nodes.clone());
+                Ok(nodes)
+            },
+        ),
+    )
+}
+
+/// Submits a serialized batch list to the scabbard service of a circuit, on behalf of the
+/// request with the given id.
+pub fn submit_batches(
+    client: &Client,
+    config: &EventListenerConfig,
+    token_provider: Option<&TokenProvider>,
+    request_id: &RequestId,
+    circuit_id: &str,
+    service_id: &str,
+    batch_list: Vec<u8>,
+) -> Box<dyn Future<Item = (), Error = SplinterdError>> {
+    let mut request = client
+        .post(format!(
+            "{}/scabbard/{}/{}/batches",
+            config.splinterd_url(),
+            circuit_id,
+            service_id
+        ))
+        .header(header::CONTENT_TYPE, "application/octet-stream")
+        .header(REQUEST_ID_HEADER, request_id.as_str());
+    if let Some(token_provider) = token_provider {
+        match token_provider.authorization_header() {
+            Ok(authorization) => request = request.header(header::AUTHORIZATION, authorization),
+            Err(err) => return Box::new(future::err(SplinterdError::Token(err.to_string()))),
+        }
+    }
+
+    Box::new(
+        request
+            .send_body(Body::Bytes(batch_list.into()))
+            .map_err(|err| SplinterdError::Unreachable(err.to_string()))
+            .and_then(|mut response| match response.status() {
+                StatusCode::ACCEPTED => Either::A(future::ok(())),
+                StatusCode::BAD_REQUEST => Either::B(
+                    response
+                        .body()
+                        .limit(MAX_RESPONSE_SIZE)
+                        .map_err(|err| SplinterdError::InvalidResponse(err.to_string()))
+                        .and_then(|body| {
+                            Err(SplinterdError::Rejected(
+                                String::from_utf8_lossy(&body).into_owned(),
+                            ))
+                        }),
+                ),
+                StatusCode::NOT_FOUND => Either::A(future::err(SplinterdError::NotFound)),
+                status => Either::A(future::err(SplinterdError::Status(status))),
+            }),
+    )
+}
@@ -0,0 +1,310 @@
+/*
+ * Copyright 2019 Walmart Inc.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * -----------------------------------------------------------------------------
+ */
+
+//! Status of the payloads relayed to splinterd, as seen by the event listener.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+
+use futures::{Future, Stream};
+
+use crate::broadcast::StateChange;
+use crate::clock::Clock;
+
+/// number of submissions remembered; the oldest are forgotten first
+const MAX_SUBMISSIONS: usize = 10_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub enum SubmissionStatus {
+    /// splinterd accepted the payload; its admin event has not been received yet
+    Pending,
+    /// splinterd accepted the payload; its outcome is not tracked
+    Accepted,
+    /// The admin event resulting from the payload was received
+    Committed,
+    /// splinterd rejected the payload
+    Invalid,
+}
+
+/// The admin event expected once a payload is committed
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExpectedEvent {
+    ProposalSubmitted { circuit_id: String },
+    ProposalVote { circuit_id: String, voter: String },
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Submission {
+    id: String,
+    status: SubmissionStatus,
+    /// Time the payload was submitted, in seconds since the epoch
+    submitted_at: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+    #[serde(skip)]
+    expected: Option<ExpectedEvent>,
+}
+
+struct Submissions {
+    by_id: HashMap<String, Submission>,
+    /// ids in the order they were submitted, to forget the oldest
+    order: VecDeque<String>,
+}
+
+/// Remembers the recent submissions and marks them committed as their admin events arrive.
+///
+/// Clones share the same submissions.
+#[derive(Clone)]
+pub struct SubmissionTracker {
+    submissions: Arc<Mutex<Submissions>>,
+    clock: Arc<dyn Clock>,
+}
+
+impl SubmissionTracker {
+    pub fn new(clock: Arc<dyn Clock>) -> Self {
+        SubmissionTracker {
+            submissions: Arc::new(Mutex::new(Submissions {
+                by_id: HashMap::new(),
+                order: VecDeque::new(),
+            })),
+            clock,
+        }
+    }
+
+    /// Records a payload splinterd accepted, awaiting the expected admin event if any.
+    pub fn accepted(&self, id: &str, expected: Option<ExpectedEvent>) {
+        let status = if expected.is_some() {
+            SubmissionStatus::Pending
+        } else {
+            SubmissionStatus::Accepted
+        };
+        self.insert(id, status, None, expected)
+    }
+
+    /// Records a payload splinterd rejected.
+    pub fn invalid(&self, id: &str, error: String) {
+        self.insert(id, SubmissionStatus::Invalid, Some(error), None)
+    }
+
+    pub fn get(&self, id: &str) -> Option<Submission> {
+        match self.submissions.lock() {
+            Ok(submissions) => submissions.by_id.get(id).cloned(),
+            Err(_) => {
+                error!("Submission tracker lock was poisoned");
+                None
+            }
+        }
+    }
+
+    /// Returns a future marking submissions committed as the state changes arrive, to be run
+    /// for as long as the REST API.
+    pub fn track<S>(&self, changes: S) -> impl Future<Item = (), Error = ()>
+    where
+        S: Stream<Item = StateChange, Error = ()>,
+    {
+        let tracker = self.clone();
+        changes.for_each(move |change| {
+            tracker.observe(&change);
+            Ok(())
+        })
+    }
+
+    fn observe(&self, change: &StateChange) {
+        let event = change.to_json();
+        let event_type = event["type"].as_str().unwrap_or_default();
+        let circuit_id = event["circuit_id"].as_str().unwrap_or_default();
+        let voter = event["voter"].as_str().unwrap_or_default();
+        let mut submissions = match self.submissions.lock() {
+            Ok(submissions) => submissions,
+            Err(_) => {
+                error!("Submission tracker lock was poisoned");
+                return;
+            }
+        };
+        for submission in submissions.by_id.values_mut() {
+            if submission.status != SubmissionStatus::Pending {
+                continue;
+            }
+            let committed = match &submission.expected {
+                Some(ExpectedEvent::ProposalSubmitted {
+                    circuit_id: expected,
+                }) => event_type == "proposal_submitted" && expected == circuit_id,
+                Some(ExpectedEvent::ProposalVote {
+                    circuit_id: expected,
+                    voter: expected_voter,
+                }) => {
+                    event_type == "proposal_vote"
+                        && expected == circuit_id
+                        && expected_voter == voter
+                }
+                None => false,
+            };
+            if committed {
+                submission.status = SubmissionStatus::Committed;
+            }
+        }
+    }
+
+    fn insert(
+        &self,
+        id: &str,
+        status: SubmissionStatus,
+        error: Option<String>,
+        expected: Option<ExpectedEvent>,
+    ) {
+        let submitted_at = self.clock.now_secs();
+        let mut submissions = match self.submissions.lock() {
+            Ok(submissions) => submissions,
+            Err(_) => {
+                error!("Submission tracker lock was poisoned, submission not recorded");
+                return;
+            }
+        };
+        if submissions.order.len() >= MAX_SUBMISSIONS {
+            if let Some(oldest) = submissions.order.pop_front() {
+                submissions.by_id.remove(&oldest);
+            }
+        }
+        submissions.order.push_back(id.to_string());
+        submissions.by_id.insert(
+            id.to_string(),
+            Submission {
+                id: id.to_string(),
+                status,
+                submitted_at,
+                error,
+                expected,
+            },
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use serde_json::Value;
+
+    use crate::broadcast::Broadcaster;
+    use crate::clock::FixedClock;
+
+    fn tracker() -> SubmissionTracker {
+        SubmissionTracker::new(Arc::new(FixedClock(
+            UNIX_EPOCH + Duration::from_secs(1_600_000_000),
+        )))
+    }
+
+    /// Runs the tracker over the given admin events' state changes.
+    fn observe_all(tracker: &SubmissionTracker, events: Vec<Value>) {
+        let broadcaster = Broadcaster::new(0);
+        let changes = broadcaster.subscribe();
+        for event in events {
+            broadcaster.broadcast(event);
+        }
+        drop(broadcaster);
+        tracker.track(changes).wait().unwrap();
+    }
+
+    fn status(tracker: &SubmissionTracker, id: &str) -> Option<SubmissionStatus> {
+        tracker.get(id).map(|submission| submission.status)
+    }
+
+    #[test]
+    fn vote_is_committed_by_its_admin_event() {
+        let tracker = tracker();
+        tracker.accepted(
+            "vote",
+            Some(ExpectedEvent::ProposalVote {
+                circuit_id: "01234-ABCDE".into(),
+                voter: "02abcd".into(),
+            }),
+        );
+        tracker.accepted(
+            "proposal",
+            Some(ExpectedEvent::ProposalSubmitted {
+                circuit_id: "01234-ABCDE".into(),
+            }),
+        );
+
+        observe_all(
+            &tracker,
+            vec![
+                json!({ "type": "proposal_vote", "circuit_id": "01234-ABCDE", "voter": "03ffff" }),
+                json!({ "type": "proposal_vote", "circuit_id": "56789-FGHIJ", "voter": "02abcd" }),
+            ],
+        );
+        assert_eq!(status(&tracker, "vote"), Some(SubmissionStatus::Pending));
+
+        observe_all(
+            &tracker,
+            vec![
+                json!({ "type": "proposal_vote", "circuit_id": "01234-ABCDE", "voter": "02abcd" }),
+            ],
+        );
+        assert_eq!(status(&tracker, "vote"), Some(SubmissionStatus::Committed));
+        assert_eq!(
+            status(&tracker, "proposal"),
+            Some(SubmissionStatus::Pending)
+        );
+
+        observe_all(
+            &tracker,
+            vec![json!({ "type": "proposal_submitted", "circuit_id": "01234-ABCDE" })],
+        );
+        assert_eq!(
+            status(&tracker, "proposal"),
+            Some(SubmissionStatus::Committed)
+        );
+    }
+
+    #[test]
+    fn untracked_submissions_are_not_committed() {
+        let tracker = tracker();
+        tracker.accepted("batch", None);
+        tracker.invalid("rejected", "invalid signature".into());
+
+        observe_all(
+            &tracker,
+            vec![json!({ "type": "proposal_submitted", "circuit_id": "01234-ABCDE" })],
+        );
+        assert_eq!(status(&tracker, "batch"), Some(SubmissionStatus::Accepted));
+        let rejected = tracker.get("rejected").unwrap();
+        assert_eq!(rejected.status, SubmissionStatus::Invalid);
+        assert_eq!(rejected.error, Some("invalid signature".to_string()));
+        assert_eq!(rejected.submitted_at, 1_600_000_000);
+    }
+
+    #[test]
+    fn oldest_submissions_are_forgotten() {
+        let tracker = tracker();
+        for i in 0..MAX_SUBMISSIONS {
+            tracker.accepted(&i.to_string(), None);
+        }
+        assert!(tracker.get("0").is_some());
+
+        tracker.accepted("latest", None);
+        assert!(tracker.get("0").is_none());
+        assert!(tracker.get("1").is_some());
+        assert!(tracker.get("latest").is_some());
+        assert_eq!(
+            tracker.submissions.lock().unwrap().by_id.len(),
+            MAX_SUBMISSIONS
+        );
+    }
+}
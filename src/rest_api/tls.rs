@@ -0,0 +1,73 @@
+/*
+ * Copyright 2019 Walmart Inc.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * -----------------------------------------------------------------------------
+ */
+
+//! HTTPS termination for deployments without a reverse proxy.
+
+use actix_web::http::header;
+use actix_web::{web, HttpRequest, HttpResponse};
+use openssl::ssl::{SslAcceptor, SslAcceptorBuilder, SslFiletype, SslMethod, SslVerifyMode};
+
+use crate::config::TlsConfig;
+
+use super::RestApiServerError;
+
+/// Builds the TLS acceptor for the configured certificate, requiring client certificates if a
+/// client CA is configured.
+pub fn acceptor(config: &TlsConfig) -> Result<SslAcceptorBuilder, RestApiServerError> {
+    let tls_error = |err: openssl::error::ErrorStack| {
+        RestApiServerError::StartUpError(format!("Unable to set up TLS: {}", err))
+    };
+    let mut builder = SslAcceptor::mozilla_intermediate(SslMethod::tls()).map_err(tls_error)?;
+    builder
+        .set_private_key_file(config.key_path(), SslFiletype::PEM)
+        .map_err(tls_error)?;
+    builder
+        .set_certificate_chain_file(config.cert_path())
+        .map_err(tls_error)?;
+    builder.check_private_key().map_err(tls_error)?;
+    if let Some(client_ca_path) = config.client_ca_path() {
+        builder.set_ca_file(client_ca_path).map_err(tls_error)?;
+        builder.set_verify(SslVerifyMode::PEER | SslVerifyMode::FAIL_IF_NO_PEER_CERT);
+    }
+    Ok(builder)
+}
+
+/// The port HTTPS is served on, for redirects
+#[derive(Clone, Copy)]
+pub struct HttpsPort(pub u16);
+
+/// Redirects a plain HTTP request to the same URL over HTTPS.
+pub fn redirect_to_https(req: HttpRequest, https_port: web::Data<HttpsPort>) -> HttpResponse {
+    let host = req
+        .headers()
+        .get(header::HOST)
+        .and_then(|value| value.to_str().ok())
+        .map(|host| match host.rfind(':') {
+            // keep IPv6 addresses, such as [::1], whole
+            Some(index) if !host[index..].contains(']') => &host[..index],
+            _ => host,
+        })
+        .unwrap_or("localhost");
+    let location = if https_port.0 == 443 {
+        format!("https://{}{}", host, req.uri())
+    } else {
+        format!("https://{}:{}{}", host, https_port.0, req.uri())
+    };
+    HttpResponse::PermanentRedirect()
+        .header(header::LOCATION, location)
+        .finish()
+}
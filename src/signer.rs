@@ -0,0 +1,431 @@
+/*
+ * Copyright 2019 Walmart Inc.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * -----------------------------------------------------------------------------
+ */
+
+//! Server-side signing of the payloads the REST API builds or relays, for deployments whose
+//! clients cannot sign them.
+//!
+//! The key is used through the `Signer` trait, so it can be kept in a local file or behind a
+//! signing service, such as one backed by an HSM.
+
+use std::error::Error;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+use hyper::header::CONTENT_TYPE;
+use hyper::{Body, Request, StatusCode};
+use openssl::ssl::{SslConnector, SslFiletype, SslMethod};
+use protobuf::Message;
+use sawtooth_sdk::signing::create_context;
+use sawtooth_sdk::signing::secp256k1::{Secp256k1PrivateKey, Secp256k1PublicKey};
+use serde_json::Value;
+use splinter::protos::admin::{CircuitManagementPayload, CircuitManagementPayload_Header};
+
+use crate::config::{DeploymentConfig, RemoteSignerConfig};
+use crate::event_handler::sabre::{
+    create_batch, create_batch_list_from_one, create_txn, SabreAction,
+};
+use crate::event_handler::{from_hex, to_hex, EventHandlerError};
+use crate::publisher::BlockingClient;
+
+#[derive(Debug)]
+pub enum SignerError {
+    /// The key could not be loaded or is invalid
+    KeyError(String),
+    SigningError(String),
+    /// The signing service failed to sign
+    RemoteError(String),
+}
+
+impl Error for SignerError {}
+
+impl fmt::Display for SignerError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SignerError::KeyError(err) => write!(f, "Invalid signing key: {}", err),
+            SignerError::SigningError(err) => write!(f, "Unable to sign: {}", err),
+            SignerError::RemoteError(err) => write!(f, "Signing service failed: {}", err),
+        }
+    }
+}
+
+impl From<sawtooth_sdk::signing::Error> for SignerError {
+    fn from(err: sawtooth_sdk::signing::Error) -> Self {
+        SignerError::SigningError(err.to_string())
+    }
+}
+
+/// Signs messages with a secp256k1 key, as Sawtooth does: over the SHA-256 of the message.
+///
+/// Implement it to keep the key somewhere other than a local file or the supported signing
+/// service.
+pub trait Signer: Send + Sync {
+    /// Returns the public key, as hex.
+    fn public_key(&self) -> &str;
+
+    /// Returns the signature of the message, as hex.
+    fn sign(&self, message: &[u8]) -> Result<String, SignerError>;
+}
+
+/// Loads the signer configured with signing_key_file, signing_key_env or remote_signer, if any.
+pub fn load(config: &DeploymentConfig) -> Result<Option<Arc<dyn Signer>>, SignerError> {
+    match (
+        config.signing_key_file(),
+        config.signing_key_env(),
+        config.remote_signer(),
+    ) {
+        (Some(path), None, None) => Ok(Some(Arc::new(LocalSigner::from_file(path)?))),
+        (None, Some(var), None) => Ok(Some(Arc::new(LocalSigner::from_env(var)?))),
+        (None, None, Some(remote)) => Ok(Some(Arc::new(RemoteSigner::new(remote)?))),
+        (None, None, None) => Ok(None),
+        _ => Err(SignerError::KeyError(
+            "only one of signing_key_file, signing_key_env and remote_signer may be set".into(),
+        )),
+    }
+}
+
+/// Signs with a private key held in memory.
+pub struct LocalSigner {
+    private_key: Secp256k1PrivateKey,
+    /// the public key, as hex
+    public_key: String,
+}
+
+impl LocalSigner {
+    /// Reads a hex encoded private key from a file.
+    pub fn from_file(path: &str) -> Result<Self, SignerError> {
+        let private_key = std::fs::read_to_string(path)
+            .map_err(|err| SignerError::KeyError(format!("Unable to read {}: {}", path, err)))?;
+        LocalSigner::from_hex(private_key.trim())
+    }
+
+    /// Reads a hex encoded private key from an environment variable.
+    pub fn from_env(var: &str) -> Result<Self, SignerError> {
+        let private_key = std::env::var(var)
+            .map_err(|err| SignerError::KeyError(format!("Unable to read ${}: {}", var, err)))?;
+        LocalSigner::from_hex(private_key.trim())
+    }
+
+    pub fn from_hex(private_key: &str) -> Result<Self, SignerError> {
+        let context = create_context("secp256k1")?;
+        let private_key = Secp256k1PrivateKey::from_hex(private_key)
+            .map_err(|err| SignerError::KeyError(err.to_string()))?;
+        let public_key = context.get_public_key(&private_key)?.as_hex();
+        Ok(LocalSigner {
+            private_key,
+            public_key,
+        })
+    }
+}
+
+impl Signer for LocalSigner {
+    fn public_key(&self) -> &str {
+        &self.public_key
+    }
+
+    fn sign(&self, message: &[u8]) -> Result<String, SignerError> {
+        let context = create_context("secp256k1")?;
+        Ok(context.sign(message, &self.private_key)?)
+    }
+}
+
+/// Signs by posting each message to a signing service, optionally over mutual TLS.
+///
+/// Every signature returned is verified against the configured public key.
+pub struct RemoteSigner {
+    url: String,
+    public_key: String,
+    client: Mutex<BlockingClient>,
+}
+
+impl RemoteSigner {
+    pub fn new(config: &RemoteSignerConfig) -> Result<Self, SignerError> {
+        Secp256k1PublicKey::from_hex(config.public_key())
+            .map_err(|err| SignerError::KeyError(err.to_string()))?;
+        let tls_error = |err: openssl::error::ErrorStack| {
+            SignerError::KeyError(format!("Unable to set up TLS: {}", err))
+        };
+        let mut tls = SslConnector::builder(SslMethod::tls()).map_err(tls_error)?;
+        if let Some(ca_path) = config.ca_path() {
+            tls.set_ca_file(ca_path).map_err(tls_error)?;
+        }
+        match (config.client_cert_path(), config.client_key_path()) {
+            (Some(cert_path), Some(key_path)) => {
+                tls.set_certificate_chain_file(cert_path)
+                    .map_err(tls_error)?;
+                tls.set_private_key_file(key_path, SslFiletype::PEM)
+                    .map_err(tls_error)?;
+                tls.check_private_key().map_err(tls_error)?;
+            }
+            (None, None) => (),
+            _ => {
+                return Err(SignerError::KeyError(
+                    "remote_signer needs both client_cert_path and client_key_path".into(),
+                ))
+            }
+        }
+        let client = BlockingClient::with_tls(tls)
+            .map_err(|err| SignerError::RemoteError(err.to_string()))?;
+        Ok(RemoteSigner {
+            url: config.url().to_string(),
+            public_key: config.public_key().to_string(),
+            client: Mutex::new(client),
+        })
+    }
+}
+
+impl Signer for RemoteSigner {
+    fn public_key(&self) -> &str {
+        &self.public_key
+    }
+
+    fn sign(&self, message: &[u8]) -> Result<String, SignerError> {
+        let body = json!({
+            "public_key": self.public_key,
+            "message": to_hex(message),
+        });
+        let request = Request::post(self.url.as_str())
+            .header(CONTENT_TYPE, "application/json")
+            .body(Body::from(body.to_string()))
+            .map_err(|err| SignerError::RemoteError(err.to_string()))?;
+        let (status, body) = self
+            .client
+            .lock()
+            .map_err(|_| SignerError::RemoteError("signing client lock was poisoned".into()))?
+            .send(request)
+            .map_err(SignerError::RemoteError)?;
+        if status != StatusCode::OK {
+            return Err(SignerError::RemoteError(format!(
+                "responded with status {}: {}",
+                status,
+                String::from_utf8_lossy(&body)
+            )));
+        }
+        let response: Value = serde_json::from_slice(&body)
+            .map_err(|err| SignerError::RemoteError(format!("invalid response: {}", err)))?;
+        let signature = response["signature"]
+            .as_str()
+            .ok_or_else(|| SignerError::RemoteError("response has no signature".into()))?;
+
+        let context = create_context("secp256k1")?;
+        let public_key = Secp256k1PublicKey::from_hex(&self.public_key)
+            .map_err(|err| SignerError::KeyError(err.to_string()))?;
+        if !context.verify(signature, message, &public_key)? {
+            return Err(SignerError::RemoteError(
+                "the signature does not match the public key".into(),
+            ));
+        }
+        Ok(signature.to_string())
+    }
+}
+
+/// Signs a CircuitManagementPayload as its requester, replacing the requester and signature of
+/// its header.
+pub fn sign_circuit_management_payload(
+    signer: &dyn Signer,
+    payload: &[u8],
+) -> Result<Vec<u8>, SignerError> {
+    let mut payload: CircuitManagementPayload =
+        protobuf::parse_from_bytes(payload).map_err(|err| {
+            SignerError::SigningError(format!("Invalid CircuitManagementPayload: {}", err))
+        })?;
+    let mut header: CircuitManagementPayload_Header =
+        protobuf::parse_from_bytes(payload.get_header())
+            .map_err(|err| SignerError::SigningError(format!("Invalid payload header: {}", err)))?;
+    header.set_requester(from_hex(signer.public_key()).map_err(SignerError::KeyError)?);
+    let header_bytes = header
+        .write_to_bytes()
+        .map_err(|err| SignerError::SigningError(err.to_string()))?;
+
+    let signature = signer.sign(&header_bytes)?;
+    payload.set_signature(from_hex(&signature).map_err(SignerError::SigningError)?);
+    payload.set_header(header_bytes);
+    payload
+        .write_to_bytes()
+        .map_err(|err| SignerError::SigningError(err.to_string()))
+}
+
+/// Signs a transaction for each Sabre action, in order, and a batch holding them.
+///
+/// Returns the id of the batch and the serialized batch list to submit to scabbard.
+pub fn sign_batch(
+    signer: &dyn Signer,
+    actions: Vec<SabreAction>,
+) -> Result<(String, Vec<u8>), SignerError> {
+    let txns = actions
+        .into_iter()
+        .map(|action| create_txn(action.addresses, action.payload, signer))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(signer_error)?;
+    let batch = create_batch(txns, signer).map_err(signer_error)?;
+    let batch_id = batch.header_signature.clone();
+    let bytes = create_batch_list_from_one(batch)
+        .write_to_bytes()
+        .map_err(|err| {
+            SignerError::SigningError(format!("failed to serialize batch list: {}", err))
+        })?;
+    Ok((batch_id, bytes))
+}
+
+/// Returns the signer's own error, such as the signing service failing, so callers can still
+/// tell it apart, or else a signing error describing the failure.
+fn signer_error(err: EventHandlerError) -> SignerError {
+    match err {
+        EventHandlerError::SignerError(err) => err,
+        err => SignerError::SigningError(err.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+    use std::thread;
+
+    const PRIVATE_KEY: &str = "2f1e7b7a130d7ba9da0068b3bb0ba1d79e7e77110302c9f746c3c2a63fe40088";
+    const PUBLIC_KEY: &str = "026a2c795a9776f75464aa3bda3534c3154a6e91b357b1181d3f515110f84b67c5";
+    const OTHER_PRIVATE_KEY: &str =
+        "4a7d3d5b4c3c6c9f5d0e8b1a2c3d4e5f60718293a4b5c6d7e8f90a1b2c3d4e5f";
+
+    /// Answers a single request with the given status and body, returning the signer's URL.
+    fn signing_service(status: &'static str, body: String) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/sign", listener.local_addr().unwrap());
+        thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = Vec::new();
+            let mut buf = [0; 1024];
+            loop {
+                let read = stream.read(&mut buf).unwrap();
+                request.extend_from_slice(&buf[..read]);
+                let text = String::from_utf8_lossy(&request).to_string();
+                if let Some(end) = text.find("\r\n\r\n") {
+                    let content_length = text[..end]
+                        .lines()
+                        .filter_map(|line| {
+                            let mut parts = line.splitn(2, ':');
+                            match (parts.next(), parts.next()) {
+                                (Some(name), Some(value))
+                                    if name.eq_ignore_ascii_case("content-length") =>
+                                {
+                                    value.trim().parse::<usize>().ok()
+                                }
+                                _ => None,
+                            }
+                        })
+                        .next()
+                        .unwrap_or(0);
+                    if request.len() >= end + 4 + content_length {
+                        break;
+                    }
+                }
+                if read == 0 {
+                    break;
+                }
+            }
+            write!(
+                stream,
+                "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\
+                 Connection: close\r\n\r\n{}",
+                status,
+                body.len(),
+                body
+            )
+            .unwrap();
+        });
+        url
+    }
+
+    fn remote_signer(url: &str) -> RemoteSigner {
+        let config: RemoteSignerConfig =
+            serde_yaml::from_str(&format!("url: {}\npublic_key: {}", url, PUBLIC_KEY)).unwrap();
+        RemoteSigner::new(&config).unwrap()
+    }
+
+    #[test]
+    fn local_signer_derives_the_public_key() {
+        let signer = LocalSigner::from_hex(PRIVATE_KEY).unwrap();
+        assert_eq!(signer.public_key(), PUBLIC_KEY);
+    }
+
+    #[test]
+    fn local_signer_refuses_an_invalid_key() {
+        match LocalSigner::from_hex("not a key") {
+            Err(SignerError::KeyError(_)) => (),
+            Err(err) => panic!("expected a key error, got {}", err),
+            Ok(_) => panic!("expected a key error"),
+        }
+    }
+
+    #[test]
+    fn signatures_verify_only_against_the_signing_key() {
+        let context = create_context("secp256k1").unwrap();
+        let signature = LocalSigner::from_hex(PRIVATE_KEY)
+            .unwrap()
+            .sign(b"message")
+            .unwrap();
+
+        let public_key = Secp256k1PublicKey::from_hex(PUBLIC_KEY).unwrap();
+        assert!(context.verify(&signature, b"message", &public_key).unwrap());
+        assert!(!context.verify(&signature, b"other", &public_key).unwrap());
+
+        let other = LocalSigner::from_hex(OTHER_PRIVATE_KEY).unwrap();
+        let other_key = Secp256k1PublicKey::from_hex(other.public_key()).unwrap();
+        assert!(!context.verify(&signature, b"message", &other_key).unwrap());
+    }
+
+    #[test]
+    fn remote_signer_returns_a_verified_signature() {
+        let signature = LocalSigner::from_hex(PRIVATE_KEY)
+            .unwrap()
+            .sign(b"message")
+            .unwrap();
+        let url = signing_service("200 OK", json!({ "signature": signature }).to_string());
+
+        assert_eq!(remote_signer(&url).sign(b"message").unwrap(), signature);
+    }
+
+    #[test]
+    fn remote_signer_refuses_a_signature_from_another_key() {
+        let signature = LocalSigner::from_hex(OTHER_PRIVATE_KEY)
+            .unwrap()
+            .sign(b"message")
+            .unwrap();
+        let url = signing_service("200 OK", json!({ "signature": signature }).to_string());
+
+        match remote_signer(&url).sign(b"message") {
+            Err(SignerError::RemoteError(_)) => (),
+            Err(err) => panic!("expected a remote error, got {}", err),
+            Ok(_) => panic!("expected a remote error"),
+        }
+    }
+
+    #[test]
+    fn remote_signer_reports_a_failing_service() {
+        let url = signing_service(
+            "500 Internal Server Error",
+            json!({ "message": "HSM unavailable" }).to_string(),
+        );
+
+        match remote_signer(&url).sign(b"message") {
+            Err(SignerError::RemoteError(err)) => assert!(err.contains("HSM unavailable")),
+            Err(err) => panic!("expected a remote error, got {}", err),
+            Ok(_) => panic!("expected a remote error"),
+        }
+    }
+}
@@ -0,0 +1,218 @@
+/*
+ * Copyright 2019 Walmart Inc.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * -----------------------------------------------------------------------------
+ */
+
+//! Dry-run validation of a configuration, so a change can be checked before it is deployed.
+
+use std::collections::HashSet;
+use std::fs::File;
+use std::time::Duration;
+
+use kafka::producer::Producer;
+
+use crate::authorization::TokenProvider;
+use crate::config::{
+    get_node, DataReaderConfigBuilder, DeploymentConfig, EventListenerConfig, FilterRule,
+    SinkConfig,
+};
+use crate::key_registry::validate_public_key;
+use crate::metrics::Metrics;
+use crate::publisher::check_sink_config;
+use crate::signer;
+
+/// time to wait for the Kafka broker while checking connectivity
+const KAFKA_CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The outcome of a single validation check
+#[derive(Debug, Serialize)]
+pub struct ValidationCheck {
+    name: String,
+    passed: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    message: Option<String>,
+}
+
+impl ValidationCheck {
+    fn new(name: &str, result: Result<(), String>) -> Self {
+        ValidationCheck {
+            name: name.to_string(),
+            passed: result.is_ok(),
+            message: result.err(),
+        }
+    }
+}
+
+/// The outcome of every validation check run against a configuration
+#[derive(Debug, Serialize)]
+pub struct ValidationReport {
+    valid: bool,
+    checks: Vec<ValidationCheck>,
+}
+
+impl ValidationReport {
+    fn new(checks: Vec<ValidationCheck>) -> Self {
+        ValidationReport {
+            valid: checks.iter().all(|check| check.passed),
+            checks,
+        }
+    }
+
+    pub fn is_valid(&self) -> bool {
+        self.valid
+    }
+}
+
+/// Runs every check against the configuration without starting the event listener.
+pub fn validate(
+    builder: DataReaderConfigBuilder,
+    token_provider: Option<&TokenProvider>,
+) -> ValidationReport {
+    let config = match builder.build() {
+        Ok(config) => config,
+        Err(err) => {
+            return ValidationReport::new(vec![ValidationCheck::new(
+                "configuration",
+                Err(err.to_string()),
+            )])
+        }
+    };
+    validate_config(&config, token_provider)
+}
+
+/// Runs every check against a deployment configuration proposed to replace the one in use,
+/// given as YAML or JSON, connecting to the same splinterd endpoints.
+pub fn validate_proposed(
+    config: &EventListenerConfig,
+    proposed: &str,
+    token_provider: Option<&TokenProvider>,
+) -> ValidationReport {
+    match DeploymentConfig::parse(proposed.as_bytes()) {
+        Ok(deployment_config) => validate_config(
+            &config.with_deployment_config(deployment_config),
+            token_provider,
+        ),
+        Err(err) => ValidationReport::new(vec![ValidationCheck::new(
+            "configuration",
+            Err(err.to_string()),
+        )]),
+    }
+}
+
+fn validate_config(
+    config: &EventListenerConfig,
+    token_provider: Option<&TokenProvider>,
+) -> ValidationReport {
+    let mut checks = vec![
+        ValidationCheck::new("configuration", Ok(())),
+        ValidationCheck::new("tp_path", check_tp_path(config)),
+        ValidationCheck::new("tp_prefix", check_tp_prefix(config)),
+        ValidationCheck::new("signer", check_signer(config)),
+        ValidationCheck::new("registered_keys", check_registered_keys(config)),
+        ValidationCheck::new("event_filters", check_event_filters(config)),
+        ValidationCheck::new(
+            "kafka",
+            check_kafka(&[config.deployment_config().kafka_url().to_string()]),
+        ),
+        ValidationCheck::new(
+            "splinterd",
+            get_node(
+                config.splinterd_endpoints(),
+                token_provider,
+                &Metrics::default(),
+            )
+            .map(|_| ())
+            .map_err(|err| err.to_string()),
+        ),
+    ];
+    for (index, sink) in config.deployment_config().export_sinks().iter().enumerate() {
+        checks.push(ValidationCheck::new(
+            &format!("export_sinks[{}]", index),
+            check_sink_config(sink),
+        ));
+        if let SinkConfig::Kafka { brokers, topic, .. } = sink.sink() {
+            checks.push(ValidationCheck::new(
+                &format!("export_sinks kafka {}", topic),
+                check_kafka(brokers),
+            ));
+        }
+    }
+    ValidationReport::new(checks)
+}
+
+fn check_tp_path(config: &EventListenerConfig) -> Result<(), String> {
+    File::open(config.deployment_config().tp_path())
+        .map(|_| ())
+        .map_err(|err| format!("Unable to open contract: {}", err))
+}
+
+fn check_tp_prefix(config: &EventListenerConfig) -> Result<(), String> {
+    let prefix = config.deployment_config().tp_prefix();
+    if prefix.len() == 6 && prefix.chars().all(|c| c.is_ascii_hexdigit()) {
+        Ok(())
+    } else {
+        Err(format!(
+            "Namespace prefix must be 6 hexadecimal characters: {}",
+            prefix
+        ))
+    }
+}
+
+fn check_signer(config: &EventListenerConfig) -> Result<(), String> {
+    match signer::load(config.deployment_config()) {
+        Ok(None) if config.deployment_config().server_side_signing() => Err(
+            "server_side_signing requires signing_key_file, signing_key_env or remote_signer"
+                .to_string(),
+        ),
+        Ok(_) => Ok(()),
+        Err(err) => Err(err.to_string()),
+    }
+}
+
+fn check_registered_keys(config: &EventListenerConfig) -> Result<(), String> {
+    let mut public_keys = HashSet::new();
+    config
+        .deployment_config()
+        .registered_keys()
+        .iter()
+        .try_for_each(|key| {
+            validate_public_key(key.public_key())?;
+            if public_keys.insert(key.public_key().to_lowercase()) {
+                Ok(())
+            } else {
+                Err(format!(
+                    "The key {} is registered more than once",
+                    key.public_key()
+                ))
+            }
+        })
+}
+
+fn check_event_filters(config: &EventListenerConfig) -> Result<(), String> {
+    config
+        .deployment_config()
+        .event_filters()
+        .iter()
+        .flat_map(FilterRule::requesters)
+        .try_for_each(|requester| validate_public_key(requester.as_str()))
+}
+
+fn check_kafka(brokers: &[String]) -> Result<(), String> {
+    Producer::from_hosts(brokers.to_vec())
+        .with_ack_timeout(KAFKA_CHECK_TIMEOUT)
+        .create()
+        .map(|_| ())
+        .map_err(|err| format!("Unable to connect to Kafka: {}", err))
+}